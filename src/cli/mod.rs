@@ -1,21 +1,37 @@
-use clap::Parser;
+use clap::{ Parser, Subcommand };
 
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
-    /// Path to the .sql/.surql database dump file to process
+    /// Path to the .sql/.surql/.json/.jsonl/.csv dump file or SQLite database file, or a directory or glob of them
     #[arg(short = 'f', env = "DUMP_FILE", long, default_value = "./surreal.surql")]
     pub dump_file: String,
 
-    /// Target vector database: redis|chroma|milvus|qdrant|surreal|pinecone
+    /// Dump format, skipping detection: mysql|postgres|mssql|sqlite|oracle|surreal|json|csv|sqlite-db
+    #[arg(long, env = "DUMP_FORMAT")]
+    pub format: Option<String>,
+
+    /// Table name for every record of a JSON/JSONL input (default: per-record field, else file name)
+    #[arg(long, env = "JSON_TABLE")]
+    pub json_table: Option<String>,
+
+    /// JSON/JSONL field holding each record's table name, e.g. added by an export script
+    #[arg(long, env = "JSON_TABLE_FIELD", default_value = "_collection")]
+    pub json_table_field: String,
+
+    /// Field delimiter for CSV inputs: a single character or `tab` (default: tab for .tsv, else comma)
+    #[arg(long, env = "CSV_DELIMITER")]
+    pub csv_delimiter: Option<String>,
+
+    /// Target vector database: redis|chroma|milvus|qdrant|surreal|pinecone|pgvector, or a local file sink: jsonl|parquet|npy
     #[arg(short = 't', env = "EXPORT_TYPE", long, default_value = "redis")]
     pub vector_export_type: String,
 
-    /// Username for database authentication (Milvus, SurrealDB)
+    /// Username for database authentication (Milvus, SurrealDB, pgvector)
     #[arg(short = 'u', env = "USER", long, default_value = "root")]
     pub user: String,
 
-    /// Password for database authentication (Milvus, SurrealDB, Redis)
+    /// Password for database authentication (Milvus, SurrealDB, Redis, pgvector)
     #[arg(short = 'p', env = "PASS", long, default_value = "")]
     pub pass: String,
 
@@ -31,11 +47,15 @@ pub struct Args {
     #[arg(long, env = "DEBUG", default_value = "false")]
     pub debug: bool,
 
-    /// Vector database URL/host endpoint (e.g. redis://127.0.0.1:6379)
+    /// Vector database URL/host endpoint (e.g. redis://127.0.0.1:6379, postgres://localhost:5432/db)
     #[arg(long, env = "VECTOR_HOST", default_value = "redis://127.0.0.1:6379")]
     pub vector_host: String,
 
-    /// Target database name (Chroma, Milvus, Qdrant, Surreal)
+    /// Directory for the jsonl, parquet and npy file sinks (one file or directory per table)
+    #[arg(long, env = "OUTPUT_DIR", default_value = "./vectors")]
+    pub output_dir: String,
+
+    /// Target database name (Chroma, Milvus, Qdrant, Surreal, pgvector if not in the URL)
     #[arg(long, env = "DATABASE", default_value = "default_database")]
     pub database: String,
 
@@ -51,6 +71,18 @@ pub struct Args {
     #[arg(long, env = "REGION", default_value = "us-east-1")]
     pub region: String,
 
+    /// pgvector index type: hnsw|ivfflat|none (uses --metric for the operator class)
+    #[arg(long, env = "PG_INDEX", default_value = "hnsw")]
+    pub pg_index: String,
+
+    /// pgvector write mode: insert (multi-row INSERT ... ON CONFLICT) or copy (COPY BINARY)
+    #[arg(long, env = "PG_INSERT_MODE", default_value = "insert")]
+    pub pg_insert_mode: String,
+
+    /// pgvector: also store scalar metadata fields in typed columns
+    #[arg(long, env = "PG_TYPED_COLUMNS", default_value = "false")]
+    pub pg_typed_columns: bool,
+
     /// Tenant name for multi-tenant DBs (Chroma)
     #[arg(long, env = "TENANT", default_value = "default_tenant")]
     pub tenant: String,
@@ -75,11 +107,11 @@ pub struct Args {
     #[arg(short = 'c', env = "CHUNK_SIZE", long, default_value = "10")]
     pub chunk_size: usize,
 
-    /// Which embedding provider to use: ollama, tei, or google
+    /// Which embedding provider to use: ollama, tei, google, or openai (any /v1/embeddings server)
     #[arg(long, env = "EMBEDDING_PROVIDER", default_value = "ollama")]
     pub embedding_provider: String,
 
-    /// API Key for Google Gemini (required if --embedding-provider=google)
+    /// API key for Google Gemini (required) or an OpenAI-compatible server
     #[arg(long, env = "EMBEDDING_API_KEY")]
     pub embedding_api_key: Option<String>,
 
@@ -87,11 +119,11 @@ pub struct Args {
     #[arg(long, env = "EMBEDDING_MODEL", default_value = "nomic-embed-text")]
     pub embedding_model: String,
 
-    /// URL endpoint for Ollama or Google embeddings
+    /// URL endpoint for Ollama/TEI, or base URL of an OpenAI-compatible server
     #[arg(long, env = "EMBEDDING_URL")]
     pub embedding_url: Option<String>,
 
-    /// Parallel embedding requests
+    /// Embedding batches in flight at once (results are stored in dump order)
     #[arg(long, env = "EMBEDDING_MAX_CONCURRENCY", default_value = "4")]
     pub embedding_concurrency: usize,
 
@@ -107,6 +139,14 @@ pub struct Args {
     #[arg(long, env = "OLLAMA_TIMEOUT", default_value = "60")]
     pub embedding_timeout: u64,
 
+    /// How the OpenAI-compatible API key is sent: auto|bearer|api-key (auto uses api-key for Azure)
+    #[arg(long, env = "EMBEDDING_AUTH", default_value = "auto")]
+    pub embedding_auth: String,
+
+    /// Send --dimension as the `dimensions` parameter (OpenAI-compatible provider)
+    #[arg(long, env = "EMBEDDING_SEND_DIMENSIONS", default_value = "false")]
+    pub embedding_send_dimensions: bool,
+
     /// Task type for Google Gemini (default: SEMANTIC_SIMILARITY)
     #[arg(long, env = "EMBEDDING_TASK_TYPE", default_value = "SEMANTIC_SIMILARITY")]
     pub embedding_task_type: String,
@@ -131,4 +171,77 @@ pub struct Args {
     /// Apply exclusion rules from config/exclude.json to remove sensitive fields
     #[arg(long, env = "USE_EXCLUDE", default_value = "false")]
     pub use_exclude: bool,
+
+    /// JSON file mapping table names to primary key columns, overriding the dump's DDL
+    #[arg(long, env = "PRIMARY_KEYS_FILE", default_value = "config/primary_keys.json")]
+    pub primary_keys_file: String,
+
+    /// JSON file of per-table embedding text templates; tables without one embed every field
+    #[arg(long, env = "TEMPLATES_FILE", default_value = "config/templates.json")]
+    pub templates_file: String,
+
+    /// Split long texts into overlapping chunks instead of truncating: off|chars|sentences|tokens
+    #[arg(long, env = "TEXT_CHUNKING", default_value = "off")]
+    pub text_chunking: String,
+
+    /// Chunk length in --text-chunking units (default: derived from --embedding-max-tokens, 8 sentences)
+    #[arg(long, env = "TEXT_CHUNK_SIZE")]
+    pub text_chunk_size: Option<usize>,
+
+    /// Units shared by consecutive chunks (default: a tenth of the size, 1 sentence)
+    #[arg(long, env = "TEXT_CHUNK_OVERLAP")]
+    pub text_chunk_overlap: Option<usize>,
+
+    /// Join related rows into each document before embedding, e.g. "orders.customer_id -> customers.id"
+    /// or "order_items.order_id -> orders.id as items[]" (repeatable; JOINS separates specs with ';')
+    #[arg(long = "join", env = "JOINS", value_delimiter = ';')]
+    pub joins: Vec<String>,
+
+    /// Write rows the parsers reject to this file, one JSON object per line
+    #[arg(long, env = "REJECT_FILE")]
+    pub reject_file: Option<String>,
+
+    /// Fail the run when more than this fraction (0-1) of the input rows is rejected
+    #[arg(long, env = "MAX_REJECT_RATIO")]
+    pub max_reject_ratio: Option<f64>,
+
+    /// Resume an interrupted migration from the checkpoint file
+    #[arg(long, env = "RESUME", default_value = "false")]
+    pub resume: bool,
+
+    /// Path of the checkpoint file written after every stored batch
+    #[arg(long, env = "CHECKPOINT_FILE", default_value = ".db2vec-state.json")]
+    pub checkpoint_file: String,
+
+    /// Without a subcommand, the dump is migrated
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Embed a query and run a similarity search against the configured vector database
+    Search(SearchArgs),
+    /// Read the dump without embedding or storing it and print the records per table,
+    /// rejected rows and suggested --join options
+    Inspect,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct SearchArgs {
+    /// Text to search for; embedded with the configured embedding provider
+    #[arg(long)]
+    pub query: String,
+
+    /// Source table to search (its collection, index, namespace or table in the target)
+    #[arg(long)]
+    pub table: String,
+
+    /// Number of results to return
+    #[arg(long, default_value = "10")]
+    pub top_k: usize,
+
+    /// Only return records whose metadata matches these values, e.g. '{"category":"books"}'
+    #[arg(long)]
+    pub filter: Option<String>,
 }
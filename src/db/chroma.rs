@@ -1,7 +1,7 @@
 use log::{ info, warn, debug };
 use reqwest::blocking::Client;
 use serde_json::Value;
-use super::{ Database, DbError };
+use super::{ Database, DbError, SearchFilter, SearchHit };
 
 pub struct ChromaDatabase {
     client: Client,
@@ -37,6 +37,26 @@ impl ChromaDatabase {
             metric,
         })
     }
+
+    fn collections_url(&self) -> String {
+        format!("{}/tenants/{}/databases/{}/collections", self.url, self.tenant, self.database)
+    }
+
+    /// Looks up the id of the collection named `name`.
+    fn find_collection(&self, name: &str) -> Result<Option<String>, DbError> {
+        let mut list_req = self.client.get(self.collections_url());
+        if let Some(ref token) = self.auth_token {
+            list_req = list_req.header("X-Chroma-Token", token);
+        }
+        let cols_json: Value = list_req.send()?.json()?;
+        Ok(
+            cols_json
+                .as_array()
+                .and_then(|arr| arr.iter().find(|col| col["name"].as_str() == Some(name)))
+                .and_then(|col| col["id"].as_str())
+                .map(|s| s.to_string())
+        )
+    }
 }
 
 impl Database for ChromaDatabase {
@@ -84,28 +104,8 @@ impl Database for ChromaDatabase {
             info!("Chroma database '{}' created", self.database);
         }
 
-        let collections_url = format!(
-            "{}/tenants/{}/databases/{}/collections",
-            self.url,
-            self.tenant,
-            self.database
-        );
-        let mut list_req = self.client.get(&collections_url);
-        if let Some(ref token) = self.auth_token {
-            list_req = list_req.header("X-Chroma-Token", token);
-        }
-        let cols_res = list_req.send()?;
-        let cols_json: Value = cols_res.json()?;
-        let mut collection_id: Option<String> = None;
-        if let Some(arr) = cols_json.as_array() {
-            for col in arr {
-                if col["name"].as_str() == Some(&normalized_table) {
-                    collection_id = col["id"].as_str().map(|s| s.to_string());
-                    break;
-                }
-            }
-        }
-        let collection_id = match collection_id {
+        let collections_url = self.collections_url();
+        let collection_id = match self.find_collection(&normalized_table)? {
             Some(id) => id,
             None => {
                 let col_body =
@@ -205,7 +205,7 @@ impl Database for ChromaDatabase {
         });
 
         let add_url = format!(
-            "{}/tenants/{}/databases/{}/collections/{}/add",
+            "{}/tenants/{}/databases/{}/collections/{}/upsert",
             self.url,
             self.tenant,
             self.database,
@@ -222,7 +222,7 @@ impl Database for ChromaDatabase {
         debug!("Chroma insert response ({}): {}", status, body_text);
 
         if status.is_success() {
-            info!("Chroma: inserted {} vectors into '{}' (original: '{}')", 
+            info!("Chroma: upserted {} vectors into '{}' (original: '{}')", 
                   items.len(), normalized_table, table);
             Ok(())
         } else if body_text.contains("Error in compaction") {
@@ -237,4 +237,66 @@ impl Database for ChromaDatabase {
             Err(format!("Chroma bulk insert failed: {}", body_text).into())
         }
     }
+
+    fn search(
+        &self,
+        table: &str,
+        vector: &[f32],
+        k: usize,
+        filter: Option<&SearchFilter>
+    ) -> Result<Vec<SearchHit>, DbError> {
+        let normalized_table = table.to_lowercase();
+        let collection_id = self
+            .find_collection(&normalized_table)?
+            .ok_or_else(|| format!("Chroma collection '{}' not found", normalized_table))?;
+
+        let mut body =
+            serde_json::json!({
+            "query_embeddings": [vector],
+            "n_results": k,
+            "include": ["metadatas", "distances"]
+        });
+        if let Some(filter) = filter {
+            let conditions: Vec<Value> = filter
+                .iter()
+                .map(|(key, value)| serde_json::json!({ key: { "$eq": value } }))
+                .collect();
+            body["where"] = if conditions.len() == 1 {
+                conditions[0].clone()
+            } else {
+                serde_json::json!({ "$and": conditions })
+            };
+        }
+
+        let query_url = format!("{}/{}/query", self.collections_url(), collection_id);
+        let mut req = self.client.post(&query_url).json(&body);
+        if let Some(ref token) = self.auth_token {
+            req = req.header("X-Chroma-Token", token);
+        }
+        let resp = req.send()?;
+        if !resp.status().is_success() {
+            let err = resp.text()?;
+            return Err(format!("Chroma query failed: {}", err).into());
+        }
+
+        let json: Value = resp.json()?;
+        debug!("Chroma query response: {}", json);
+        let ids = json["ids"][0]
+            .as_array()
+            .ok_or_else(|| format!("Unexpected Chroma query response: {}", json))?;
+        let prefix = format!("{}:", normalized_table);
+        let hits = ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| {
+                let id = id.as_str().unwrap_or_default();
+                SearchHit {
+                    id: id.strip_prefix(&prefix).unwrap_or(id).to_string(),
+                    score: json["distances"][0][i].as_f64().unwrap_or_default() as f32,
+                    metadata: json["metadatas"][0][i].clone(),
+                }
+            })
+            .collect();
+        Ok(hits)
+    }
 }
@@ -0,0 +1,56 @@
+use log::info;
+use serde_json::{ json, Value };
+use std::fs::OpenOptions;
+use std::io::{ BufWriter, Write };
+use std::sync::Mutex;
+use super::output::{ fit_dimension, truncate_lines, OutputDir };
+use super::{ Database, DbError };
+
+/// Writes `<output-dir>/<table>.jsonl`, one `{"id", "vector", "metadata"}`
+/// object per line.
+pub struct JsonlDatabase {
+    out: OutputDir,
+    dimension: usize,
+    write_lock: Mutex<()>,
+}
+
+impl JsonlDatabase {
+    pub fn new(args: &crate::cli::Args) -> Result<Self, DbError> {
+        info!("Writing JSONL files to '{}'", args.output_dir);
+        Ok(JsonlDatabase {
+            out: OutputDir::new(args)?,
+            dimension: args.dimension,
+            write_lock: Mutex::new(()),
+        })
+    }
+}
+
+impl Database for JsonlDatabase {
+    fn store_vector(&self, table: &str, items: &[(String, Vec<f32>, Value)]) -> Result<(), DbError> {
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        let _guard = self.write_lock.lock().unwrap();
+        let path = self.out.path(table, ".jsonl");
+        if let Some(keep) = self.out.first_write(table) {
+            truncate_lines(&path, keep)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        let mut writer = BufWriter::new(file);
+        for (id, vec, meta) in items {
+            let line = json!({
+                "id": id,
+                "vector": fit_dimension(id, vec, self.dimension),
+                "metadata": meta,
+            });
+            serde_json::to_writer(&mut writer, &line)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+
+        info!("JSONL: wrote {} records to {}", items.len(), path.display());
+        Ok(())
+    }
+}
@@ -1,7 +1,9 @@
 use reqwest::blocking::Client;
 use serde_json::{ json, Value };
-use super::{ Database, DbError };
+use super::{ Database, DbError, SearchFilter, SearchHit };
+use crate::parser::schema::{ ColumnKind, DumpSchema, TableSchema };
 use log::{ debug, error, info, warn };
+use std::sync::Arc;
 
 pub struct MilvusDatabase {
     url: String,
@@ -10,6 +12,7 @@ pub struct MilvusDatabase {
     dimension: usize,
     db_name: String,
     metric: String,
+    schema: Arc<DumpSchema>,
 }
 
 impl MilvusDatabase {
@@ -50,9 +53,16 @@ impl MilvusDatabase {
             dimension: args.dimension,
             db_name,
             metric,
+            schema: Arc::default(),
         })
     }
 
+    /// Takes declared column types from `schema` when creating collections.
+    pub fn with_schema(mut self, schema: Arc<DumpSchema>) -> Self {
+        self.schema = schema;
+        self
+    }
+
     fn add_auth(
         &self,
         req_builder: reqwest::blocking::RequestBuilder
@@ -151,6 +161,8 @@ impl Database for MilvusDatabase {
             info!("Milvus database '{}' already exists.", self.db_name);
         }
 
+        let schema = self.schema.table(table);
+
         let stats_url = format!("{}/v2/vectordb/collections/get_stats", self.url);
         let stats_payload =
             json!({
@@ -223,6 +235,29 @@ impl Database for MilvusDatabase {
             info!("Collection '{}' not found in database '{}'. Creating...", normalized_collection, self.db_name);
             let create_coll_url = format!("{}/v2/vectordb/collections/create", self.url);
 
+            let mut fields = vec![
+                json!({
+                    "fieldName": "id",
+                    "dataType": "VarChar",
+                    "isPrimary": true,
+                    "elementTypeParams": {
+                        "max_length": 256 
+                    }
+                }),
+                json!({
+                    "fieldName": "vector",
+                    "dataType": "FloatVector",
+                    "elementTypeParams": {
+                        "dim": self.dimension.to_string()
+                    }
+                })
+            ];
+            if let Some(schema) = &schema {
+                let typed = milvus_schema_fields(schema);
+                debug!("Milvus: {} typed fields from the dump schema of '{}'", typed.len(), table);
+                fields.extend(typed);
+            }
+
             let create_coll_payload =
                 json!({
                 "dbName": self.db_name,
@@ -230,23 +265,7 @@ impl Database for MilvusDatabase {
                 "schema": {
                     "autoId": false,
                     "enableDynamicField": true,
-                    "fields": [
-                        {
-                            "fieldName": "id",
-                            "dataType": "VarChar",
-                            "isPrimary": true,
-                            "elementTypeParams": {
-                                "max_length": 256 
-                            }
-                        },
-                        {
-                            "fieldName": "vector",
-                            "dataType": "FloatVector",
-                            "elementTypeParams": {
-                                "dim": self.dimension.to_string()
-                            }
-                        }
-                    ]
+                    "fields": fields
                 },
                 "indexParams": [
                     {
@@ -300,7 +319,11 @@ impl Database for MilvusDatabase {
                                     clean_key
                                 );
                             }
-                            entity_obj[clean_key] = v_meta.clone();
+                            let kind = schema.as_ref().and_then(|cols| milvus_field_kind(cols, k));
+                            entity_obj[clean_key] = match kind {
+                                Some(kind) => milvus_typed_value(kind, v_meta),
+                                None => v_meta.clone(),
+                            };
                         }
                     }
                 }
@@ -308,7 +331,7 @@ impl Database for MilvusDatabase {
             })
             .collect();
 
-        let insert_url = format!("{}/v2/vectordb/entities/insert", self.url);
+        let insert_url = format!("{}/v2/vectordb/entities/upsert", self.url);
         let insert_payload =
             json!({
             "dbName": self.db_name,
@@ -317,9 +340,9 @@ impl Database for MilvusDatabase {
         });
 
         let insert_req = self.client.post(&insert_url).json(&insert_payload);
-        self.send_request(self.add_auth(insert_req), "insert entities")?;
+        self.send_request(self.add_auth(insert_req), "upsert entities")?;
         info!(
-            "Milvus: inserted {} entities into collection '{}' (original: '{}') in database '{}'.",
+            "Milvus: upserted {} entities into collection '{}' (original: '{}') in database '{}'.",
             items.len(),
             normalized_collection,
             table,
@@ -328,4 +351,120 @@ impl Database for MilvusDatabase {
 
         Ok(())
     }
+
+    fn search(
+        &self,
+        table: &str,
+        vector: &[f32],
+        k: usize,
+        filter: Option<&SearchFilter>
+    ) -> Result<Vec<SearchHit>, DbError> {
+        let normalized_collection = table.to_lowercase();
+        let mut payload =
+            json!({
+            "dbName": self.db_name,
+            "collectionName": normalized_collection,
+            "data": [vector],
+            "annsField": "vector",
+            "limit": k,
+            "outputFields": ["*"]
+        });
+        if let Some(filter) = filter {
+            let conditions: Vec<String> = filter
+                .iter()
+                .map(|(key, value)| format!("{} == {}", key.replace('.', "_"), value))
+                .collect();
+            payload["filter"] = Value::String(conditions.join(" and "));
+        }
+
+        let search_url = format!("{}/v2/vectordb/entities/search", self.url);
+        let search_req = self.client.post(&search_url).json(&payload);
+        let resp = self.send_request(self.add_auth(search_req), "search entities")?;
+
+        let hits = resp
+            .get("data")
+            .and_then(|data| data.as_array())
+            .ok_or_else(|| format!("Unexpected Milvus search response: {}", resp))?
+            .iter()
+            .map(|entity| {
+                let mut metadata = entity.clone();
+                if let Some(obj) = metadata.as_object_mut() {
+                    obj.remove("id");
+                    obj.remove("distance");
+                    obj.remove("vector");
+                }
+                SearchHit {
+                    id: entity["id"].as_str().unwrap_or_default().to_string(),
+                    score: entity["distance"].as_f64().unwrap_or_default() as f32,
+                    metadata,
+                }
+            })
+            .collect();
+        Ok(hits)
+    }
+}
+
+/// Milvus data type for a declared column type. Binary columns are left out.
+fn milvus_data_type(kind: ColumnKind) -> Option<&'static str> {
+    match kind {
+        ColumnKind::Integer => Some("Int64"),
+        ColumnKind::Float => Some("Double"),
+        ColumnKind::Boolean => Some("Bool"),
+        ColumnKind::Text | ColumnKind::Timestamp | ColumnKind::Date => Some("VarChar"),
+        ColumnKind::Json | ColumnKind::Array => Some("JSON"),
+        ColumnKind::Binary => None,
+    }
+}
+
+fn is_milvus_field_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') &&
+        chars.all(|c| c.is_ascii_alphanumeric() || c == '_') &&
+        name != "id" &&
+        name != "vector"
+}
+
+/// Nullable collection fields for the columns of a dump table, so Milvus
+/// stores them with their declared types instead of as dynamic JSON.
+pub fn milvus_schema_fields(schema: &TableSchema) -> Vec<Value> {
+    schema
+        .iter()
+        .filter(|(name, _)| is_milvus_field_name(name))
+        .filter_map(|(name, kind)| {
+            let data_type = milvus_data_type(*kind)?;
+            let mut field = json!({
+                "fieldName": name,
+                "dataType": data_type,
+                "nullable": true
+            });
+            if data_type == "VarChar" {
+                field["elementTypeParams"] = json!({ "max_length": 65535 });
+            }
+            Some(field)
+        })
+        .collect()
+}
+
+/// Declared type of `field` when it was created as a typed collection field.
+fn milvus_field_kind(schema: &TableSchema, field: &str) -> Option<ColumnKind> {
+    schema
+        .iter()
+        .find(|(name, kind)| name == field && is_milvus_field_name(name) && milvus_data_type(*kind).is_some())
+        .map(|(_, kind)| *kind)
+}
+
+/// Fits a metadata value to its typed field; values that cannot be stored
+/// under the declared type become null rather than failing the upsert.
+pub fn milvus_typed_value(kind: ColumnKind, value: &Value) -> Value {
+    match (kind, value) {
+        (_, Value::Null) => Value::Null,
+        (ColumnKind::Integer, Value::Number(n)) if n.is_i64() => value.clone(),
+        (ColumnKind::Float, Value::Number(_)) => value.clone(),
+        (ColumnKind::Boolean, Value::Bool(_)) => value.clone(),
+        (ColumnKind::Text | ColumnKind::Timestamp | ColumnKind::Date, Value::String(_)) => value.clone(),
+        (ColumnKind::Text | ColumnKind::Timestamp | ColumnKind::Date, Value::Number(_) | Value::Bool(_)) =>
+            Value::String(value.to_string()),
+        (ColumnKind::Json | ColumnKind::Array, _) => value.clone(),
+        _ => Value::Null,
+    }
 }
@@ -4,31 +4,87 @@ pub mod chroma;
 pub mod milvus;
 pub mod surreal;
 pub mod pinecone;
+pub mod pgvector;
+pub mod jsonl;
+pub mod parquet;
+pub mod npy;
+mod output;
 pub use redis::RedisDatabase;
 pub use milvus::MilvusDatabase;
 pub use qdrant::QdrantDatabase;
 pub use chroma::ChromaDatabase;
 pub use surreal::SurrealDatabase;
 pub use pinecone::PineconeDatabase;
-use serde_json::Value;
+pub use pgvector::PgVectorDatabase;
+pub use jsonl::JsonlDatabase;
+pub use parquet::ParquetDatabase;
+pub use npy::NpyDatabase;
+use serde_json::{ Map, Value };
 use std::error::Error;
 use crate::cli::Args;
+use crate::parser::schema::DumpSchema;
+use std::sync::Arc;
 
 pub type DbError = Box<dyn Error + Send + Sync>;
 
+/// Metadata field equality conditions, all of which must match.
+pub type SearchFilter = Map<String, Value>;
+
+/// One result of [`Database::search`]. `score` is reported as the backend
+/// returns it: a similarity for some metrics and a distance for others.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub id: String,
+    pub score: f32,
+    pub metadata: Value,
+}
+
+/// Number of the last closed part file per table, for sinks that write
+/// numbered parts.
+pub type OutputParts = std::collections::BTreeMap<String, usize>;
+
 pub trait Database: Send + Sync {
     
     fn store_vector(&self, table: &str, items: &[(String, Vec<f32>, Value)]) -> Result<(), DbError>;
+
+    /// Called after every stored batch. Returns the last closed part file per
+    /// table once everything stored so far is durable, or `None` while the
+    /// sink still buffers rows; the checkpoint only advances on `Some`.
+    fn commit(&self) -> Result<Option<OutputParts>, DbError> {
+        Ok(Some(OutputParts::new()))
+    }
+
+    /// Called once after the last batch; sinks that buffer output flush it here.
+    fn finish(&self) -> Result<(), DbError> {
+        Ok(())
+    }
+
+    /// Returns the `k` stored vectors of `table` nearest to `vector`, best first.
+    fn search(
+        &self,
+        _table: &str,
+        _vector: &[f32],
+        _k: usize,
+        _filter: Option<&SearchFilter>
+    ) -> Result<Vec<SearchHit>, DbError> {
+        Err("This sink does not support search".into())
+    }
 }
 
-pub fn select_database(args: &Args) -> Result<Box<dyn Database>, DbError> {
+/// Opens the sink named by `--vector-export-type`. Sinks that create typed
+/// columns take the declared types from `schema`.
+pub fn select_database(args: &Args, schema: Arc<DumpSchema>) -> Result<Box<dyn Database>, DbError> {
     let database: Box<dyn Database> = match args.vector_export_type.as_str() {
-        "redis" => Box::new(RedisDatabase::new(args)?),
+        "redis" => Box::new(RedisDatabase::new(args)?.with_schema(schema)),
         "qdrant" => Box::new(QdrantDatabase::new(args)?),
         "chroma" => Box::new(ChromaDatabase::new(args)?),
-        "milvus" => Box::new(MilvusDatabase::new(args)?),
+        "milvus" => Box::new(MilvusDatabase::new(args)?.with_schema(schema)),
         "surreal" => Box::new(SurrealDatabase::new(args)?),
         "pinecone" => Box::new(PineconeDatabase::new(args)?),
+        "pgvector" => Box::new(PgVectorDatabase::new(args)?.with_schema(schema)),
+        "jsonl" => Box::new(JsonlDatabase::new(args)?),
+        "parquet" => Box::new(ParquetDatabase::new(args)?),
+        "npy" => Box::new(NpyDatabase::new(args)?),
         _ => {
             return Err("Unsupported database type".into());
         }
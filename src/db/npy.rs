@@ -0,0 +1,114 @@
+use byteorder::{ LittleEndian, WriteBytesExt };
+use log::info;
+use serde_json::{ json, Value };
+use std::fs::{ File, OpenOptions };
+use std::io::{ BufWriter, Seek, SeekFrom, Write };
+use std::sync::Mutex;
+use super::output::{ fit_dimension, missing_rows, remove_if_exists, truncate_lines, OutputDir };
+use super::{ Database, DbError };
+
+/// Fixed header size, so the row count can be rewritten in place as the
+/// array grows. 128 bytes leaves room for any realistic shape.
+const HEADER_LEN: usize = 128;
+const MAGIC: &[u8] = b"\x93NUMPY\x01\x00";
+
+/// Writes `<output-dir>/<table>.npy`, a `float32` array of shape
+/// `(rows, dimension)`, plus `<table>.metadata.jsonl` with the `id` and
+/// `metadata` of each row in the same order.
+pub struct NpyDatabase {
+    out: OutputDir,
+    dimension: usize,
+    write_lock: Mutex<()>,
+}
+
+impl NpyDatabase {
+    pub fn new(args: &crate::cli::Args) -> Result<Self, DbError> {
+        info!("Writing NumPy files to '{}'", args.output_dir);
+        Ok(NpyDatabase {
+            out: OutputDir::new(args)?,
+            dimension: args.dimension,
+            write_lock: Mutex::new(()),
+        })
+    }
+}
+
+/// `.npy` v1.0 header for a C-ordered little-endian float32 array.
+pub fn npy_header(rows: usize, dimension: usize) -> Vec<u8> {
+    let dict = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}", rows, dimension);
+    let mut header = MAGIC.to_vec();
+    header.extend(((HEADER_LEN - MAGIC.len() - 2) as u16).to_le_bytes());
+    header.extend(dict.as_bytes());
+    header.resize(HEADER_LEN - 1, b' ');
+    header.push(b'\n');
+    header
+}
+
+fn existing_rows(file: &File, dimension: usize) -> Result<usize, DbError> {
+    let len = file.metadata()?.len() as usize;
+    if len == 0 {
+        return Ok(0);
+    }
+    let row_bytes = dimension * 4;
+    if len < HEADER_LEN || !(len - HEADER_LEN).is_multiple_of(row_bytes) {
+        return Err(
+            format!("Existing .npy file does not hold {}-dimensional float32 rows", dimension).into()
+        );
+    }
+    Ok((len - HEADER_LEN) / row_bytes)
+}
+
+impl Database for NpyDatabase {
+    fn store_vector(&self, table: &str, items: &[(String, Vec<f32>, Value)]) -> Result<(), DbError> {
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        let _guard = self.write_lock.lock().unwrap();
+        let npy_path = self.out.path(table, ".npy");
+        let meta_path = self.out.path(table, ".metadata.jsonl");
+        let keep = self.out.first_write(table);
+        if keep == Some(0) {
+            remove_if_exists(&npy_path)?;
+            remove_if_exists(&meta_path)?;
+        }
+
+        let mut file = OpenOptions::new().create(true).truncate(false).read(true).write(true).open(&npy_path)?;
+        let mut rows = existing_rows(&file, self.dimension)?;
+        if let Some(keep) = keep && keep > 0 {
+            if rows < keep {
+                return Err(missing_rows(&npy_path, rows, keep).into());
+            }
+            info!("NumPy: keeping the {} checkpointed rows of {}", keep, npy_path.display());
+            file.set_len((HEADER_LEN + keep * self.dimension * 4) as u64)?;
+            truncate_lines(&meta_path, keep)?;
+            rows = keep;
+        }
+        if rows == 0 {
+            file.set_len(HEADER_LEN as u64)?;
+        }
+
+        file.seek(SeekFrom::End(0))?;
+        let mut data = BufWriter::new(&file);
+        for (id, vec, _) in items {
+            for v in fit_dimension(id, vec, self.dimension).iter() {
+                data.write_f32::<LittleEndian>(*v)?;
+            }
+        }
+        data.flush()?;
+        drop(data);
+
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&npy_header(rows + items.len(), self.dimension))?;
+
+        let meta_file = OpenOptions::new().create(true).append(true).open(&meta_path)?;
+        let mut meta_writer = BufWriter::new(meta_file);
+        for (id, _, meta) in items {
+            serde_json::to_writer(&mut meta_writer, &json!({ "id": id, "metadata": meta }))?;
+            meta_writer.write_all(b"\n")?;
+        }
+        meta_writer.flush()?;
+
+        info!("NumPy: wrote {} rows to {}", items.len(), npy_path.display());
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+use crate::cli::Args;
+use crate::util::checkpoint::Checkpoint;
+use log::{ info, warn };
+use std::borrow::Cow;
+use std::collections::{ BTreeMap, HashSet };
+use std::fs::{ self, File, OpenOptions };
+use std::io::{ self, BufRead, BufReader };
+use std::path::{ Path, PathBuf };
+use std::sync::Mutex;
+use super::{ DbError, OutputParts };
+
+/// Per-table output locations shared by the file sinks.
+///
+/// The first write to a table in a fresh run replaces whatever a previous run
+/// left there. With `--resume` earlier output is cut back to the rows the
+/// checkpoint counts as stored and appended to, so rows written after the last
+/// checkpoint are not duplicated.
+pub(crate) struct OutputDir {
+    dir: PathBuf,
+    /// Rows per table the checkpoint counts as stored; empty in a fresh run.
+    committed: BTreeMap<String, usize>,
+    /// Last part file per table the checkpoint counts as closed.
+    parts: OutputParts,
+    started: Mutex<HashSet<String>>,
+}
+
+impl OutputDir {
+    pub(crate) fn new(args: &Args) -> Result<Self, DbError> {
+        fs::create_dir_all(&args.output_dir)?;
+        let (committed, parts) = match Checkpoint::load(&args.checkpoint_file)? {
+            Some(checkpoint) if args.resume => (checkpoint.vectors, checkpoint.parts),
+            _ => (BTreeMap::new(), OutputParts::new()),
+        };
+        Ok(Self {
+            dir: PathBuf::from(&args.output_dir),
+            committed,
+            parts,
+            started: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// Path of `<dir>/<table><suffix>`, with the table name made file-safe.
+    pub(crate) fn path(&self, table: &str, suffix: &str) -> PathBuf {
+        let stem: String = table
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+            .collect();
+        self.dir.join(format!("{}{}", stem, suffix))
+    }
+
+    /// On the first write to `table` in this run, returns how many rows of
+    /// existing output to keep: 0 in a fresh run, the checkpointed count with
+    /// `--resume`. Returns `None` on later writes.
+    pub(crate) fn first_write(&self, table: &str) -> Option<usize> {
+        let first = self.started.lock().unwrap().insert(table.to_string());
+        first.then(|| self.committed.get(table).copied().unwrap_or(0))
+    }
+
+    /// Last part file of `table` the checkpoint counts as closed, if any.
+    pub(crate) fn committed_part(&self, table: &str) -> Option<usize> {
+        self.parts.get(table).copied()
+    }
+}
+
+/// Cuts the line-per-row file at `path` back to its first `keep` lines.
+pub(crate) fn truncate_lines(path: &Path, keep: usize) -> io::Result<()> {
+    if keep == 0 {
+        return remove_if_exists(path);
+    }
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut offset = 0;
+    let mut line = Vec::new();
+    for rows in 0..keep {
+        line.clear();
+        let n = reader.read_until(b'\n', &mut line)?;
+        if n == 0 || !line.ends_with(b"\n") {
+            return Err(missing_rows(path, rows, keep));
+        }
+        offset += n as u64;
+    }
+    let file = OpenOptions::new().write(true).open(path)?;
+    if file.metadata()?.len() > offset {
+        info!("Discarding rows of {} written after the checkpoint", path.display());
+        file.set_len(offset)?;
+    }
+    Ok(())
+}
+
+/// Error for resumed output that holds fewer rows than the checkpoint recorded.
+pub(crate) fn missing_rows(path: &Path, found: usize, keep: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "{} holds {} rows but the checkpoint recorded {}; refusing to resume",
+            path.display(),
+            found,
+            keep
+        )
+    )
+}
+
+/// Removes `path` if it exists.
+pub(crate) fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Returns `vec`, or zeros with a warning when its length is not `dimension`,
+/// matching how the server sinks treat malformed vectors.
+pub(crate) fn fit_dimension<'a>(id: &str, vec: &'a [f32], dimension: usize) -> Cow<'a, [f32]> {
+    if vec.len() == dimension {
+        Cow::Borrowed(vec)
+    } else {
+        warn!("ID={}: vector length {} ≠ {}, filling zeros", id, vec.len(), dimension);
+        Cow::Owned(vec![0.0; dimension])
+    }
+}
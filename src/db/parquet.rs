@@ -0,0 +1,209 @@
+use arrow_array::builder::{ FixedSizeListBuilder, Float32Builder };
+use arrow_array::{ ArrayRef, RecordBatch, StringArray };
+use arrow_schema::{ DataType, Field, Schema };
+use ::parquet::arrow::ArrowWriter;
+use ::parquet::basic::Compression;
+use ::parquet::file::properties::WriterProperties;
+use ::parquet::file::reader::{ FileReader, SerializedFileReader };
+use log::info;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::{ self, File };
+use std::path::{ Path, PathBuf };
+use std::sync::{ Arc, Mutex };
+use super::output::{ fit_dimension, missing_rows, OutputDir };
+use super::{ Database, DbError, OutputParts };
+
+/// Rows buffered per row group before it is written out.
+const ROW_GROUP_SIZE: usize = 8192;
+
+/// Rows written across all tables before the open parts are closed and the
+/// checkpoint may advance past them.
+const PART_ROWS: usize = 8 * ROW_GROUP_SIZE;
+
+/// Writes `<output-dir>/<table>/part-NNNNN.parquet` with `id` (string),
+/// `vector` (fixed-size list of float32) and `metadata` (JSON string) columns.
+///
+/// Open parts are closed in [`Database::commit`] once `PART_ROWS` rows have
+/// been written, and only then is the checkpoint advanced, so every row it
+/// counts is readable after a crash. The checkpoint records the last closed
+/// part of each table; a resumed run removes later parts and adds new ones.
+pub struct ParquetDatabase {
+    out: OutputDir,
+    dimension: usize,
+    schema: Arc<Schema>,
+    parts: Mutex<OpenParts>,
+}
+
+#[derive(Default)]
+struct OpenParts {
+    /// Open writer and its part number per table.
+    writers: HashMap<String, (usize, ArrowWriter<File>)>,
+    /// Last closed part per table.
+    closed: OutputParts,
+    /// Rows written to the open parts.
+    rows: usize,
+}
+
+impl ParquetDatabase {
+    pub fn new(args: &crate::cli::Args) -> Result<Self, DbError> {
+        info!("Writing Parquet files to '{}'", args.output_dir);
+        Ok(ParquetDatabase {
+            out: OutputDir::new(args)?,
+            dimension: args.dimension,
+            schema: Arc::new(vector_schema(args.dimension)),
+            parts: Mutex::new(OpenParts::default()),
+        })
+    }
+
+    fn open_writer(&self, table: &str) -> Result<(usize, ArrowWriter<File>), DbError> {
+        let dir = self.out.path(table, "");
+        if let Some(keep) = self.out.first_write(table) && dir.exists() {
+            keep_parts(&dir, self.out.committed_part(table), keep)?;
+        }
+        fs::create_dir_all(&dir)?;
+
+        let part = part_files(&dir)?.last().map_or(0, |(number, _)| number + 1);
+        let path = dir.join(format!("part-{:05}.parquet", part));
+        info!("Parquet: creating {}", path.display());
+
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_max_row_group_size(ROW_GROUP_SIZE)
+            .build();
+        let writer = ArrowWriter::try_new(File::create(path)?, self.schema.clone(), Some(props))?;
+        Ok((part, writer))
+    }
+
+    /// Closes every open part and returns the last closed part per table.
+    fn close_all(&self) -> Result<OutputParts, DbError> {
+        let mut parts = self.parts.lock().unwrap();
+        let writers: Vec<_> = parts.writers.drain().collect();
+        for (table, (part, writer)) in writers {
+            let metadata = writer.close()?;
+            info!("Parquet: closed part {} of `{}` with {} rows", part, table, metadata.num_rows);
+            parts.closed.insert(table, part);
+        }
+        parts.rows = 0;
+        Ok(parts.closed.clone())
+    }
+}
+
+pub fn vector_schema(dimension: usize) -> Schema {
+    Schema::new(
+        vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new(
+                "vector",
+                DataType::FixedSizeList(
+                    Arc::new(Field::new("item", DataType::Float32, true)),
+                    dimension as i32
+                ),
+                false
+            ),
+            Field::new("metadata", DataType::Utf8, false)
+        ]
+    )
+}
+
+/// Number of a `part-NNNNN.parquet` file.
+fn part_number(path: &Path) -> Option<usize> {
+    path.file_name()?.to_str()?.strip_prefix("part-")?.strip_suffix(".parquet")?.parse().ok()
+}
+
+/// Part files of `dir`, ordered by part number.
+fn part_files(dir: &Path) -> Result<Vec<(usize, PathBuf)>, DbError> {
+    let mut parts = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if let Some(number) = part_number(&path) {
+            parts.push((number, path));
+        }
+    }
+    parts.sort_by_key(|(number, _)| *number);
+    Ok(parts)
+}
+
+/// Keeps the parts of `dir` up to `last`, which must hold `keep` rows, and
+/// removes the rest.
+fn keep_parts(dir: &Path, last: Option<usize>, keep: usize) -> Result<(), DbError> {
+    let mut rows = 0;
+    for (number, path) in part_files(dir)? {
+        if last.is_none_or(|last| number > last) {
+            info!("Parquet: removing {} (written after the checkpoint)", path.display());
+            fs::remove_file(path)?;
+            continue;
+        }
+        let reader = SerializedFileReader::new(File::open(&path)?)?;
+        rows += reader.metadata().file_metadata().num_rows() as usize;
+    }
+    if rows != keep {
+        return Err(missing_rows(dir, rows, keep).into());
+    }
+    Ok(())
+}
+
+impl Database for ParquetDatabase {
+    fn store_vector(&self, table: &str, items: &[(String, Vec<f32>, Value)]) -> Result<(), DbError> {
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        let ids = StringArray::from_iter_values(items.iter().map(|(id, _, _)| id.as_str()));
+        let mut vectors = FixedSizeListBuilder::with_capacity(
+            Float32Builder::with_capacity(items.len() * self.dimension),
+            self.dimension as i32,
+            items.len()
+        ).with_field(Arc::new(Field::new("item", DataType::Float32, true)));
+        for (id, vec, _) in items {
+            vectors.values().append_slice(&fit_dimension(id, vec, self.dimension));
+            vectors.append(true);
+        }
+        let metadata = items
+            .iter()
+            .map(|(_, _, meta)| serde_json::to_string(meta))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let batch = RecordBatch::try_new(
+            self.schema.clone(),
+            vec![
+                Arc::new(ids) as ArrayRef,
+                Arc::new(vectors.finish()) as ArrayRef,
+                Arc::new(StringArray::from(metadata)) as ArrayRef
+            ]
+        )?;
+
+        let mut parts = self.parts.lock().unwrap();
+        if !parts.writers.contains_key(table) {
+            let writer = self.open_writer(table)?;
+            parts.writers.insert(table.to_string(), writer);
+        }
+        parts.writers.get_mut(table).unwrap().1.write(&batch)?;
+        parts.rows += items.len();
+
+        info!("Parquet: buffered {} rows for `{}`", items.len(), table);
+        Ok(())
+    }
+
+    fn commit(&self) -> Result<Option<OutputParts>, DbError> {
+        let parts = self.parts.lock().unwrap();
+        if parts.rows < PART_ROWS && !parts.writers.is_empty() {
+            return Ok(None);
+        }
+        drop(parts);
+        self.close_all().map(Some)
+    }
+
+    fn finish(&self) -> Result<(), DbError> {
+        self.close_all()?;
+        Ok(())
+    }
+}
+
+impl Drop for ParquetDatabase {
+    fn drop(&mut self) {
+        if let Err(e) = self.close_all() {
+            log::error!("Failed to finalize Parquet output: {}", e);
+        }
+    }
+}
@@ -0,0 +1,534 @@
+use byteorder::{ BigEndian, WriteBytesExt };
+use log::{ debug, info, warn };
+use postgres::types::ToSql;
+use postgres::{ Client, Config, NoTls };
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::{ Arc, Mutex };
+use super::{ Database, DbError, SearchFilter, SearchHit };
+use crate::parser::schema::{ ColumnKind, DumpSchema };
+
+/// Postgres caps a statement at 65535 bind parameters.
+const MAX_BIND_PARAMS: usize = 65535;
+const MAX_ROWS_PER_INSERT: usize = 1000;
+const RESERVED_COLUMNS: [&str; 3] = ["id", "embedding", "metadata"];
+
+/// SQL type of a metadata field promoted to its own column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Text,
+    BigInt,
+    Double,
+    Boolean,
+}
+
+impl ColumnType {
+    pub fn sql(&self) -> &'static str {
+        match self {
+            ColumnType::Text => "text",
+            ColumnType::BigInt => "bigint",
+            ColumnType::Double => "double precision",
+            ColumnType::Boolean => "boolean",
+        }
+    }
+
+    fn of(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(_) => Some(ColumnType::Text),
+            Value::Bool(_) => Some(ColumnType::Boolean),
+            Value::Number(n) if n.is_i64() || n.is_u64() => Some(ColumnType::BigInt),
+            Value::Number(_) => Some(ColumnType::Double),
+            _ => None,
+        }
+    }
+
+    /// Column type for a type declared in the dump; JSON, array and binary
+    /// columns stay in `metadata` only.
+    fn from_kind(kind: ColumnKind) -> Option<Self> {
+        match kind {
+            ColumnKind::Integer => Some(ColumnType::BigInt),
+            ColumnKind::Float => Some(ColumnType::Double),
+            ColumnKind::Boolean => Some(ColumnType::Boolean),
+            ColumnKind::Text | ColumnKind::Timestamp | ColumnKind::Date => Some(ColumnType::Text),
+            ColumnKind::Json | ColumnKind::Array | ColumnKind::Binary => None,
+        }
+    }
+
+    /// Only the types db2vec creates; other columns are left alone.
+    fn from_information_schema(data_type: &str) -> Option<Self> {
+        match data_type {
+            "text" => Some(ColumnType::Text),
+            "bigint" => Some(ColumnType::BigInt),
+            "double precision" => Some(ColumnType::Double),
+            "boolean" => Some(ColumnType::Boolean),
+            _ => None,
+        }
+    }
+
+    /// Converts a metadata value for this column; mismatched values become NULL
+    /// (they are still kept in `metadata`).
+    fn convert(&self, value: Option<&Value>) -> Option<String> {
+        let value = value?;
+        match (self, value) {
+            (_, Value::Null) => None,
+            (ColumnType::Text, Value::String(s)) => Some(s.clone()),
+            (ColumnType::Text, other) => Some(other.to_string()),
+            (ColumnType::BigInt, Value::Number(n)) => n.as_i64().map(|n| n.to_string()),
+            (ColumnType::BigInt, Value::String(s)) => s.trim().parse::<i64>().ok().map(|n| n.to_string()),
+            (ColumnType::Double, Value::Number(n)) => n.as_f64().map(|n| n.to_string()),
+            (ColumnType::Double, Value::String(s)) => s.trim().parse::<f64>().ok().map(|n| n.to_string()),
+            (ColumnType::Boolean, Value::Bool(b)) => Some(b.to_string()),
+            (ColumnType::Boolean, Value::Number(n)) => n.as_i64().map(|n| (n != 0).to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Lowercases `name` and replaces anything but `[a-z0-9_]` with `_`.
+pub fn normalize_name(name: &str) -> String {
+    let name: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) { format!("_{}", name) } else { name }
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+pub fn create_table_sql(table: &str, dimension: usize, columns: &[(String, ColumnType)]) -> String {
+    let mut defs = vec![
+        "id text PRIMARY KEY".to_string(),
+        format!("embedding vector({}) NOT NULL", dimension),
+        "metadata jsonb NOT NULL".to_string()
+    ];
+    defs.extend(columns.iter().map(|(name, ty)| format!("{} {}", quote_ident(name), ty.sql())));
+    format!("CREATE TABLE IF NOT EXISTS {} ({})", quote_ident(table), defs.join(", "))
+}
+
+/// Distance operator matching `metric`; smaller values are closer.
+fn distance_operator(metric: &str) -> &'static str {
+    match metric.to_lowercase().as_str() {
+        "l2" | "euclidean" => "<->",
+        "ip" | "dotproduct" | "dot" => "<#>",
+        _ => "<=>",
+    }
+}
+
+/// Index DDL for `--pg-index` (`hnsw`, `ivfflat` or `none`) using the operator
+/// class matching `metric`.
+pub fn create_index_sql(table: &str, index: &str, metric: &str) -> Result<Option<String>, DbError> {
+    let ops = match metric.to_lowercase().as_str() {
+        "cosine" => "vector_cosine_ops",
+        "l2" | "euclidean" => "vector_l2_ops",
+        "ip" | "dotproduct" | "dot" => "vector_ip_ops",
+        other => {
+            warn!("Unknown metric '{}', falling back to cosine", other);
+            "vector_cosine_ops"
+        }
+    };
+    let method = match index.to_lowercase().as_str() {
+        "none" => {
+            return Ok(None);
+        }
+        "hnsw" => "hnsw".to_string(),
+        "ivfflat" => "ivfflat".to_string(),
+        other => {
+            return Err(format!("Unsupported pgvector index type: {}", other).into());
+        }
+    };
+    let with = if method == "ivfflat" { " WITH (lists = 100)" } else { "" };
+    Ok(
+        Some(
+            format!(
+                "CREATE INDEX IF NOT EXISTS {} ON {} USING {} (embedding {}){}",
+                quote_ident(&format!("{}_embedding_{}_idx", table, method)),
+                quote_ident(table),
+                method,
+                ops,
+                with
+            )
+        )
+    )
+}
+
+pub struct PgVectorDatabase {
+    client: Mutex<Client>,
+    dimension: usize,
+    metric: String,
+    index: String,
+    copy_mode: bool,
+    typed_columns: bool,
+    tables: Mutex<HashMap<String, Vec<(String, ColumnType)>>>,
+    schema: Arc<DumpSchema>,
+}
+
+impl PgVectorDatabase {
+    pub fn new(args: &crate::cli::Args) -> Result<Self, DbError> {
+        let mut config: Config = args.vector_host.parse()?;
+        if args.use_auth {
+            if !args.user.is_empty() {
+                config.user(&args.user);
+            }
+            if !args.pass.is_empty() {
+                config.password(&args.pass);
+            }
+        }
+        if config.get_dbname().is_none() {
+            config.dbname(&args.database);
+        }
+
+        let copy_mode = match args.pg_insert_mode.to_lowercase().as_str() {
+            "insert" => false,
+            "copy" => true,
+            other => {
+                return Err(format!("Unsupported pgvector insert mode: {}", other).into());
+            }
+        };
+        create_index_sql("probe", &args.pg_index, &args.metric)?;
+
+        let mut client = config.connect(NoTls)?;
+        client.batch_execute("CREATE EXTENSION IF NOT EXISTS vector")?;
+        info!("Connected to PostgreSQL with pgvector");
+
+        Ok(PgVectorDatabase {
+            client: Mutex::new(client),
+            dimension: args.dimension,
+            metric: args.metric.clone(),
+            index: args.pg_index.clone(),
+            copy_mode,
+            typed_columns: args.pg_typed_columns,
+            tables: Mutex::new(HashMap::new()),
+            schema: Arc::default(),
+        })
+    }
+
+    /// Takes declared column types from `schema` when creating tables.
+    pub fn with_schema(mut self, schema: Arc<DumpSchema>) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /// Creates the table and index on first use and returns its typed columns,
+    /// as they exist in the database (so a resumed run reuses them).
+    fn ensure_table(
+        &self,
+        client: &mut Client,
+        table: &str,
+        items: &[(String, Vec<f32>, Value)]
+    ) -> Result<Vec<(String, ColumnType)>, DbError> {
+        if let Some(columns) = self.tables.lock().unwrap().get(table) {
+            return Ok(columns.clone());
+        }
+
+        let mut inferred: Vec<(String, ColumnType)> = Vec::new();
+        if self.typed_columns {
+            // Types declared in the dump win over types guessed from the batch.
+            for (col, kind) in self.schema.table(table).unwrap_or_default() {
+                let name = normalize_name(&col);
+                if RESERVED_COLUMNS.contains(&name.as_str()) || inferred.iter().any(|(n, _)| *n == name) {
+                    continue;
+                }
+                if let Some(ty) = ColumnType::from_kind(kind) {
+                    inferred.push((name, ty));
+                }
+            }
+            let declared = inferred.len();
+            for (_, _, meta) in items {
+                let Some(obj) = meta.as_object() else {
+                    continue;
+                };
+                for (key, value) in obj {
+                    let name = normalize_name(key);
+                    let Some(ty) = ColumnType::of(value) else {
+                        continue;
+                    };
+                    if RESERVED_COLUMNS.contains(&name.as_str()) {
+                        continue;
+                    }
+                    if inferred[..declared].iter().any(|(n, _)| *n == name) {
+                        continue;
+                    }
+                    match inferred.iter_mut().find(|(n, _)| *n == name) {
+                        Some((_, existing)) if *existing != ty => {
+                            *existing = match (*existing, ty) {
+                                (ColumnType::BigInt, ColumnType::Double) |
+                                (ColumnType::Double, ColumnType::BigInt) => ColumnType::Double,
+                                _ => ColumnType::Text,
+                            };
+                        }
+                        Some(_) => {}
+                        None => inferred.push((name, ty)),
+                    }
+                }
+            }
+        }
+
+        info!("Creating pgvector table '{}' with dimension {}", table, self.dimension);
+        client.batch_execute(&create_table_sql(table, self.dimension, &inferred))?;
+        if let Some(sql) = create_index_sql(table, &self.index, &self.metric)? {
+            debug!("{}", sql);
+            client.batch_execute(&sql)?;
+        }
+
+        let rows = client.query(
+            "SELECT column_name::text, data_type::text FROM information_schema.columns \
+             WHERE table_schema = current_schema() AND table_name = $1 ORDER BY ordinal_position",
+            &[&table]
+        )?;
+        let columns: Vec<(String, ColumnType)> = rows
+            .iter()
+            .map(|row| (row.get::<_, String>(0), row.get::<_, String>(1)))
+            .filter(|(name, _)| !RESERVED_COLUMNS.contains(&name.as_str()))
+            .filter_map(|(name, data_type)| {
+                ColumnType::from_information_schema(&data_type).map(|ty| (name, ty))
+            })
+            .collect();
+
+        self.tables.lock().unwrap().insert(table.to_string(), columns.clone());
+        Ok(columns)
+    }
+
+    fn vector_for<'a>(&self, id: &str, vec: &'a [f32]) -> std::borrow::Cow<'a, [f32]> {
+        if vec.len() == self.dimension {
+            std::borrow::Cow::Borrowed(vec)
+        } else {
+            warn!("ID={}: vector length {} ≠ {}, filling zeros", id, vec.len(), self.dimension);
+            std::borrow::Cow::Owned(vec![0.0; self.dimension])
+        }
+    }
+
+    fn insert_rows(
+        &self,
+        client: &mut Client,
+        table: &str,
+        columns: &[(String, ColumnType)],
+        items: &[&(String, Vec<f32>, Value)]
+    ) -> Result<(), DbError> {
+        let params_per_row = 3 + columns.len();
+        let rows_per_stmt = (MAX_BIND_PARAMS / params_per_row).clamp(1, MAX_ROWS_PER_INSERT);
+        let column_list = column_list(columns);
+
+        for rows in items.chunks(rows_per_stmt) {
+            let mut params: Vec<Option<String>> = Vec::with_capacity(rows.len() * params_per_row);
+            let mut values = Vec::with_capacity(rows.len());
+            for (id, vec, meta) in rows {
+                let base = params.len();
+                let mut placeholders = vec![
+                    format!("${}", base + 1),
+                    format!("${}::text::vector", base + 2),
+                    format!("${}::text::jsonb", base + 3)
+                ];
+                params.push(Some(id.clone()));
+                params.push(Some(vector_literal(&self.vector_for(id, vec))));
+                params.push(Some(serde_json::to_string(meta)?));
+                for (name, ty) in columns {
+                    placeholders.push(format!("${}::text::{}", params.len() + 1, ty.sql()));
+                    params.push(ty.convert(field(meta, name)));
+                }
+                values.push(format!("({})", placeholders.join(", ")));
+            }
+
+            let sql = format!(
+                "INSERT INTO {} ({}) VALUES {} {}",
+                quote_ident(table),
+                column_list,
+                values.join(", "),
+                upsert_clause(columns)
+            );
+            let refs: Vec<&(dyn ToSql + Sync)> = params
+                .iter()
+                .map(|p| p as &(dyn ToSql + Sync))
+                .collect();
+            client.execute(sql.as_str(), &refs)?;
+        }
+        Ok(())
+    }
+
+    /// Streams rows into a temporary staging table with `COPY ... (FORMAT
+    /// BINARY)`, then upserts them into the target table in one statement.
+    fn copy_rows(
+        &self,
+        client: &mut Client,
+        table: &str,
+        columns: &[(String, ColumnType)],
+        items: &[&(String, Vec<f32>, Value)]
+    ) -> Result<(), DbError> {
+        let stage = quote_ident(&format!("db2vec_stage_{}", table));
+        let column_list = column_list(columns);
+
+        let mut tx = client.transaction()?;
+        tx.batch_execute(
+            &format!(
+                "CREATE TEMP TABLE IF NOT EXISTS {} (LIKE {}) ON COMMIT DELETE ROWS",
+                stage,
+                quote_ident(table)
+            )
+        )?;
+
+        let mut buf = Vec::new();
+        buf.write_all(b"PGCOPY\n\xff\r\n\0")?;
+        buf.write_i32::<BigEndian>(0)?;
+        buf.write_i32::<BigEndian>(0)?;
+        for (id, vec, meta) in items {
+            buf.write_i16::<BigEndian>((3 + columns.len()) as i16)?;
+            write_field(&mut buf, Some(id.as_bytes()))?;
+
+            let vector = self.vector_for(id, vec);
+            let mut vec_bytes = Vec::with_capacity(4 + vector.len() * 4);
+            vec_bytes.write_u16::<BigEndian>(vector.len() as u16)?;
+            vec_bytes.write_u16::<BigEndian>(0)?;
+            for v in vector.iter() {
+                vec_bytes.write_f32::<BigEndian>(*v)?;
+            }
+            write_field(&mut buf, Some(&vec_bytes))?;
+
+            let mut json_bytes = vec![1u8];
+            json_bytes.extend(serde_json::to_vec(meta)?);
+            write_field(&mut buf, Some(&json_bytes))?;
+
+            for (name, ty) in columns {
+                let bytes = ty.convert(field(meta, name)).map(|v| encode_binary(*ty, &v));
+                write_field(&mut buf, bytes.as_deref())?;
+            }
+        }
+        buf.write_i16::<BigEndian>(-1)?;
+
+        let mut writer = tx.copy_in(
+            &format!("COPY {} ({}) FROM STDIN (FORMAT BINARY)", stage, column_list)
+        )?;
+        writer.write_all(&buf)?;
+        writer.finish()?;
+
+        tx.batch_execute(
+            &format!(
+                "INSERT INTO {} ({cols}) SELECT {cols} FROM {} {}",
+                quote_ident(table),
+                stage,
+                upsert_clause(columns),
+                cols = column_list
+            )
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+/// Looks up the metadata field a typed column was created from.
+fn field<'a>(meta: &'a Value, column: &str) -> Option<&'a Value> {
+    let obj = meta.as_object()?;
+    obj.get(column).or_else(|| {
+        obj.iter()
+            .find(|(key, _)| normalize_name(key) == column)
+            .map(|(_, value)| value)
+    })
+}
+
+fn column_list(columns: &[(String, ColumnType)]) -> String {
+    let mut names = vec!["id".to_string(), "embedding".to_string(), "metadata".to_string()];
+    names.extend(columns.iter().map(|(name, _)| quote_ident(name)));
+    names.join(", ")
+}
+
+fn upsert_clause(columns: &[(String, ColumnType)]) -> String {
+    let mut sets = vec![
+        "embedding = EXCLUDED.embedding".to_string(),
+        "metadata = EXCLUDED.metadata".to_string()
+    ];
+    sets.extend(
+        columns.iter().map(|(name, _)| format!("{0} = EXCLUDED.{0}", quote_ident(name)))
+    );
+    format!("ON CONFLICT (id) DO UPDATE SET {}", sets.join(", "))
+}
+
+fn vector_literal(vec: &[f32]) -> String {
+    let parts: Vec<String> = vec
+        .iter()
+        .map(|v| v.to_string())
+        .collect();
+    format!("[{}]", parts.join(","))
+}
+
+fn write_field(buf: &mut Vec<u8>, data: Option<&[u8]>) -> std::io::Result<()> {
+    match data {
+        Some(bytes) => {
+            buf.write_i32::<BigEndian>(bytes.len() as i32)?;
+            buf.write_all(bytes)
+        }
+        None => buf.write_i32::<BigEndian>(-1),
+    }
+}
+
+/// Binary COPY encoding of a value already validated by `ColumnType::convert`.
+fn encode_binary(ty: ColumnType, value: &str) -> Vec<u8> {
+    match ty {
+        ColumnType::Text => value.as_bytes().to_vec(),
+        ColumnType::BigInt => value.parse::<i64>().unwrap_or_default().to_be_bytes().to_vec(),
+        ColumnType::Double => value.parse::<f64>().unwrap_or_default().to_be_bytes().to_vec(),
+        ColumnType::Boolean => vec![(value == "true") as u8],
+    }
+}
+
+impl Database for PgVectorDatabase {
+    fn store_vector(&self, table: &str, items: &[(String, Vec<f32>, Value)]) -> Result<(), DbError> {
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        let table = normalize_name(table);
+        let mut client = self.client.lock().unwrap();
+        let columns = self.ensure_table(&mut client, &table, items)?;
+
+        // One statement cannot update the same row twice; keep the last copy.
+        let mut seen = HashMap::new();
+        for (i, (id, _, _)) in items.iter().enumerate() {
+            seen.insert(id.as_str(), i);
+        }
+        let rows: Vec<&(String, Vec<f32>, Value)> = items
+            .iter()
+            .enumerate()
+            .filter(|(i, (id, _, _))| seen[id.as_str()] == *i)
+            .map(|(_, item)| item)
+            .collect();
+
+        if self.copy_mode {
+            self.copy_rows(&mut client, &table, &columns, &rows)?;
+        } else {
+            self.insert_rows(&mut client, &table, &columns, &rows)?;
+        }
+        info!("pgvector: upserted {} rows into `{}`", rows.len(), table);
+        Ok(())
+    }
+
+    fn search(
+        &self,
+        table: &str,
+        vector: &[f32],
+        k: usize,
+        filter: Option<&SearchFilter>
+    ) -> Result<Vec<SearchHit>, DbError> {
+        let table = normalize_name(table);
+        let filter = Value::Object(filter.cloned().unwrap_or_default());
+        let sql = format!(
+            "SELECT id, metadata::text, (embedding {} $1::text::vector)::float8 AS score FROM {} \
+             WHERE metadata @> $2::text::jsonb ORDER BY score LIMIT {}",
+            distance_operator(&self.metric),
+            quote_ident(&table),
+            k
+        );
+        let mut client = self.client.lock().unwrap();
+        let rows = client.query(sql.as_str(), &[&vector_literal(vector), &filter.to_string()])?;
+        rows.iter()
+            .map(|row| {
+                Ok(SearchHit {
+                    id: row.get(0),
+                    metadata: serde_json::from_str(row.get::<_, &str>(1))?,
+                    score: row.get::<_, f64>(2) as f32,
+                })
+            })
+            .collect()
+    }
+}
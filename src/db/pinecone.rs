@@ -1,7 +1,7 @@
 use reqwest::blocking::Client;
 use serde_json::{ Value, json };
 use log::{ info, warn, error };
-use super::{ Database, DbError };
+use super::{ Database, DbError, SearchFilter, SearchHit };
 
 pub struct PineconeDatabase {
     control_plane_url: String,
@@ -149,6 +149,24 @@ impl PineconeDatabase {
 
         Ok(pd)
     }
+
+    fn data_plane_post(&self, url: &str) -> Result<reqwest::blocking::RequestBuilder, DbError> {
+        let mut req = self.client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .header("Accept", "application/json")
+            .header("X-Pinecone-API-Version", &self.api_version);
+
+        if self.use_auth {
+            if let Some(key) = self.api_key.as_ref() {
+                req = req.header("Api-Key", key);
+            } else {
+                error!("Pinecone auth enabled but no API key available.");
+                return Err("Pinecone auth enabled but no API key available.".into());
+            }
+        }
+        Ok(req)
+    }
 }
 impl Database for PineconeDatabase {
  
@@ -223,22 +241,7 @@ impl Database for PineconeDatabase {
             "namespace": normalized_namespace  
         });
 
-        let mut req = self.client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .header("Accept", "application/json")
-            .header("X-Pinecone-API-Version", &self.api_version);
-
-        if self.use_auth {
-            if let Some(key) = self.api_key.as_ref() {
-                req = req.header("Api-Key", key);
-            } else {
-                error!("Pinecone auth enabled but no API key available.");
-                return Err("Pinecone auth enabled but no API key available.".into());
-            }
-        }
-
-        let resp = req.json(&payload).send()?;
+        let resp = self.data_plane_post(&url)?.json(&payload).send()?;
         if resp.status().is_success() {
             let j: Value = resp.json()?;
             let count = j
@@ -256,4 +259,50 @@ impl Database for PineconeDatabase {
             Err(format!("Pinecone bulk upsert error for namespace '{}': {}", table, txt).into())
         }
     }
+
+    fn search(
+        &self,
+        table: &str,
+        vector: &[f32],
+        k: usize,
+        filter: Option<&SearchFilter>
+    ) -> Result<Vec<SearchHit>, DbError> {
+        let normalized_namespace = table.to_lowercase();
+        let mut payload =
+            json!({
+            "namespace": normalized_namespace,
+            "vector": vector,
+            "topK": k,
+            "includeMetadata": true
+        });
+        if let Some(filter) = filter {
+            let conditions: serde_json::Map<String, Value> = filter
+                .iter()
+                .map(|(key, value)| (key.clone(), json!({ "$eq": value })))
+                .collect();
+            payload["filter"] = Value::Object(conditions);
+        }
+
+        let url = format!("{}/query", self.data_plane_url);
+        let resp = self.data_plane_post(&url)?.json(&payload).send()?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let txt = resp.text()?;
+            return Err(format!("Pinecone query failed for namespace '{}' ({}): {}", table, status, txt).into());
+        }
+
+        let j: Value = resp.json()?;
+        let hits = j
+            .get("matches")
+            .and_then(|m| m.as_array())
+            .ok_or_else(|| format!("Unexpected Pinecone query response: {}", j))?
+            .iter()
+            .map(|m| SearchHit {
+                id: m["id"].as_str().unwrap_or_default().to_string(),
+                score: m["score"].as_f64().unwrap_or_default() as f32,
+                metadata: m.get("metadata").cloned().unwrap_or(Value::Null),
+            })
+            .collect();
+        Ok(hits)
+    }
 }
@@ -1,7 +1,7 @@
 use log::{ info, warn };
 use reqwest::blocking::Client;
 use serde_json::{ json, Value };
-use super::{ Database, DbError };
+use super::{ Database, DbError, SearchFilter, SearchHit };
 
 pub struct QdrantDatabase {
     client: Client,
@@ -114,4 +114,49 @@ impl Database for QdrantDatabase {
             Err(format!("Qdrant upsert failed: {}", txt).into())
         }
     }
+
+    fn search(
+        &self,
+        table: &str,
+        vector: &[f32],
+        k: usize,
+        filter: Option<&SearchFilter>
+    ) -> Result<Vec<SearchHit>, DbError> {
+        let normalized_table = table.to_lowercase();
+        let mut body = json!({ "vector": vector, "limit": k, "with_payload": true });
+        if let Some(filter) = filter {
+            let must: Vec<Value> = filter
+                .iter()
+                .map(|(key, value)| json!({ "key": key, "match": { "value": value } }))
+                .collect();
+            body["filter"] = json!({ "must": must });
+        }
+
+        let url = format!("{}/collections/{}/points/search", self.url, normalized_table);
+        let mut req = self.client.post(&url).json(&body);
+        if let Some(k) = &self.api_key {
+            req = req.header("api-key", k);
+        }
+        let resp = req.send()?;
+        if !resp.status().is_success() {
+            let txt = resp.text()?;
+            return Err(format!("Qdrant search failed: {}", txt).into());
+        }
+
+        let json: Value = resp.json()?;
+        let hits = json["result"]
+            .as_array()
+            .ok_or_else(|| format!("Unexpected Qdrant search response: {}", json))?
+            .iter()
+            .map(|point| SearchHit {
+                id: match &point["id"] {
+                    Value::String(id) => id.clone(),
+                    other => other.to_string(),
+                },
+                score: point["score"].as_f64().unwrap_or_default() as f32,
+                metadata: point["payload"].clone(),
+            })
+            .collect();
+        Ok(hits)
+    }
 }
@@ -1,8 +1,10 @@
 use redis::Client;
 use serde_json::Value;
 use log::{ info, warn, debug };
-use std::io::{ Error as IoError, ErrorKind };
-use super::{ Database, DbError };
+use std::io::Error as IoError;
+use std::sync::Arc;
+use super::{ Database, DbError, SearchFilter, SearchHit };
+use crate::parser::schema::{ ColumnKind, DumpSchema };
 
 pub struct RedisDatabase {
     client: Client,
@@ -10,6 +12,7 @@ pub struct RedisDatabase {
     dimension: usize,
     metric: String,
     group_redis: bool,
+    schema: Arc<DumpSchema>,
 }
 
 impl RedisDatabase {
@@ -18,7 +21,7 @@ impl RedisDatabase {
         let client = Client::open(args.vector_host.as_str()).map_err(
             |e|
                 Box::new(
-                    IoError::new(ErrorKind::Other, format!("Failed to open Redis client: {}", e))
+                    IoError::other(format!("Failed to open Redis client: {}", e))
                 ) as DbError
         )?;
         let password = if args.use_auth && !args.pass.is_empty() {
@@ -32,8 +35,7 @@ impl RedisDatabase {
             .map_err(
                 |e|
                     Box::new(
-                        IoError::new(
-                            ErrorKind::Other,
+                        IoError::other(
                             format!("Failed to get Redis connection: {}", e)
                         )
                     ) as DbError
@@ -46,7 +48,7 @@ impl RedisDatabase {
                 .map_err(
                     |e|
                         Box::new(
-                            IoError::new(ErrorKind::Other, format!("Redis AUTH failed: {}", e))
+                            IoError::other(format!("Redis AUTH failed: {}", e))
                         ) as DbError
                 )?;
             info!("Redis AUTH successful");
@@ -58,7 +60,7 @@ impl RedisDatabase {
             .map_err(
                 |e|
                     Box::new(
-                        IoError::new(ErrorKind::Other, format!("Redis PING failed: {}", e))
+                        IoError::other(format!("Redis PING failed: {}", e))
                     ) as DbError
             )?;
         if pong != "PONG" {
@@ -73,17 +75,23 @@ impl RedisDatabase {
             dimension: args.dimension,
             metric: args.metric.clone(),
             group_redis: args.group_redis,
+            schema: Arc::default(),
         })
     }
 
+    /// Takes declared column types from `schema` when creating indexes.
+    pub fn with_schema(mut self, schema: Arc<DumpSchema>) -> Self {
+        self.schema = schema;
+        self
+    }
+
     fn get_connection(&self) -> Result<redis::Connection, DbError> {
         let mut con = self.client
             .get_connection()
             .map_err(
                 |e|
                     Box::new(
-                        IoError::new(
-                            ErrorKind::Other,
+                        IoError::other(
                             format!("Failed to get Redis connection: {}", e)
                         )
                     ) as DbError
@@ -96,7 +104,7 @@ impl RedisDatabase {
                 .map_err(
                     |e|
                         Box::new(
-                            IoError::new(ErrorKind::Other, format!("Redis AUTH failed: {}", e))
+                            IoError::other(format!("Redis AUTH failed: {}", e))
                         ) as DbError
                 )?;
         }
@@ -168,22 +176,23 @@ impl RedisDatabase {
                 }
             }
 
+            let schema = self.schema.table(table);
             for (field, value) in data_map {
                 if field == "vector" || field == "source_table" || field == "original_id" {
                     continue;
                 }
 
-                let idx_ty = match value {
-                    Value::String(_) => "TEXT",
-                    Value::Number(_) => "NUMERIC",
-                    Value::Bool(_) => "NUMERIC",
-                    _ => {
-                        continue;
-                    }
+                let kind = schema.as_ref().and_then(|cols| {
+                    cols.iter()
+                        .find(|(col, _)| col.eq_ignore_ascii_case(field))
+                        .map(|(_, kind)| *kind)
+                });
+                let Some((path, idx_ty)) = redis_field_type(field, kind, value) else {
+                    continue;
                 };
 
-                debug!("Adding discovered field to schema: $.{} AS {} {}", field, field, idx_ty);
-                ft.arg(format!("$.{}", field)).arg("AS").arg(field).arg(idx_ty);
+                debug!("Adding discovered field to schema: {} AS {} {}", path, field, idx_ty);
+                ft.arg(path).arg("AS").arg(field).arg(idx_ty);
                 if idx_ty == "TEXT" {
                     ft.arg("SORTABLE");
                 }
@@ -207,8 +216,7 @@ impl RedisDatabase {
                 } else {
                     Err(
                         Box::new(
-                            IoError::new(
-                                ErrorKind::Other,
+                            IoError::other(
                                 format!("FT.CREATE failed for index '{}': {}", index_name, msg)
                             )
                         ) as DbError
@@ -219,6 +227,63 @@ impl RedisDatabase {
     }
 }
 
+/// Index path and RediSearch type for a metadata field. The column type
+/// declared in the dump wins; otherwise the type of the sample `value` is used.
+/// Booleans are stored as JSON `true`/`false` and indexed as TAG fields, as
+/// are arrays, element-wise.
+pub fn redis_field_type(
+    field: &str,
+    kind: Option<ColumnKind>,
+    value: &Value
+) -> Option<(String, &'static str)> {
+    let path = format!("$.{}", field);
+    let idx_ty = match (kind, value) {
+        (Some(ColumnKind::Integer | ColumnKind::Float), _) => "NUMERIC",
+        (Some(ColumnKind::Boolean), _) => "TAG",
+        (Some(ColumnKind::Text | ColumnKind::Timestamp | ColumnKind::Date), _) => "TEXT",
+        (Some(ColumnKind::Array), _) => {
+            return Some((format!("{}[*]", path), "TAG"));
+        }
+        (Some(ColumnKind::Json | ColumnKind::Binary), _) => {
+            return None;
+        }
+        (None, Value::String(_)) => "TEXT",
+        (None, Value::Number(_)) => "NUMERIC",
+        (None, Value::Bool(_)) => "TAG",
+        (None, _) => {
+            return None;
+        }
+    };
+    Some((path, idx_ty))
+}
+
+/// Builds the RediSearch pre-filter for `FT.SEARCH`: phrase matches for
+/// strings, exact ranges for numbers and tag matches for booleans.
+pub fn redis_filter_query(filter: Option<&SearchFilter>) -> String {
+    let clauses: Vec<String> = filter
+        .into_iter()
+        .flatten()
+        .map(|(field, value)| {
+            match value {
+                Value::Number(n) => format!("@{}:[{} {}]", field, n, n),
+                Value::Bool(b) => format!("@{}:{{{}}}", field, b),
+                Value::String(s) =>
+                    format!("@{}:\"{}\"", field, s.replace('\\', "\\\\").replace('"', "\\\"")),
+                other => format!("@{}:\"{}\"", field, other.to_string().replace('"', "\\\"")),
+            }
+        })
+        .collect();
+    if clauses.is_empty() { "*".to_string() } else { format!("({})", clauses.join(" ")) }
+}
+
+fn redis_text(value: &redis::Value) -> Option<String> {
+    match value {
+        redis::Value::BulkString(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+        redis::Value::SimpleString(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
 impl Database for RedisDatabase {
  
     fn store_vector(
@@ -248,7 +313,7 @@ impl Database for RedisDatabase {
                     obj.insert("vector".to_string(), serde_json::to_value(vec).unwrap());
                     if let Value::Object(map) = data {
                         for (k, v) in map {
-                            if k != "vector" {
+                            if k != "vector" && k != "id" {
                                 obj.insert(k.clone(), v.clone());
                             }
                         }
@@ -267,8 +332,7 @@ impl Database for RedisDatabase {
                 .query::<()>(&mut con)
                 .map_err(|e| {
                     Box::new(
-                        IoError::new(
-                            ErrorKind::Other,
+                        IoError::other(
                             format!("Redis JSON.SET failed for '{}': {}", key, e)
                         )
                     ) as DbError
@@ -316,8 +380,7 @@ impl Database for RedisDatabase {
             .query::<()>(&mut con)
             .map_err(|e| {
                 Box::new(
-                    IoError::new(
-                        ErrorKind::Other,
+                    IoError::other(
                         format!("Redis pipeline failed for table '{}': {}", table, e)
                     )
                 ) as DbError
@@ -327,4 +390,88 @@ impl Database for RedisDatabase {
               items.len(), normalized_table, table);
         Ok(())
     }
+
+    fn search(
+        &self,
+        table: &str,
+        vector: &[f32],
+        k: usize,
+        filter: Option<&SearchFilter>
+    ) -> Result<Vec<SearchHit>, DbError> {
+        if self.group_redis {
+            return Err("Search needs the FT index, which is not created with --group-redis".into());
+        }
+
+        let normalized_table = table.to_lowercase();
+        let index_name = format!("idx:{}", normalized_table);
+        let query = format!(
+            "{}=>[KNN {} @vector $vec AS __score]",
+            redis_filter_query(filter),
+            k
+        );
+        let blob: Vec<u8> = vector
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+
+        let mut con = self.get_connection()?;
+        let reply: redis::Value = redis
+            ::cmd("FT.SEARCH")
+            .arg(&index_name)
+            .arg(&query)
+            .arg("PARAMS")
+            .arg("2")
+            .arg("vec")
+            .arg(blob)
+            .arg("SORTBY")
+            .arg("__score")
+            .arg("LIMIT")
+            .arg("0")
+            .arg(k)
+            .arg("DIALECT")
+            .arg("2")
+            .query(&mut con)
+            .map_err(|e| {
+                Box::new(
+                    IoError::other(format!("FT.SEARCH failed for index '{}': {}", index_name, e))
+                ) as DbError
+            })?;
+
+        let redis::Value::Array(items) = reply else {
+            return Err(format!("Unexpected FT.SEARCH reply: {:?}", reply).into());
+        };
+        let prefix = format!("item:{}:", normalized_table);
+        let mut hits = Vec::new();
+        for pair in items.get(1..).unwrap_or_default().chunks(2) {
+            let [key, redis::Value::Array(fields)] = pair else {
+                continue;
+            };
+            let key = redis_text(key).unwrap_or_default();
+            let mut hit = SearchHit {
+                id: key.strip_prefix(&prefix).unwrap_or(&key).to_string(),
+                score: 0.0,
+                metadata: Value::Null,
+            };
+            for field in fields.chunks(2) {
+                let [name, value] = field else {
+                    continue;
+                };
+                match (redis_text(name).as_deref(), redis_text(value)) {
+                    (Some("__score"), Some(score)) => {
+                        hit.score = score.parse().unwrap_or_default();
+                    }
+                    (Some("$"), Some(doc)) => {
+                        let mut doc: Value = serde_json::from_str(&doc)?;
+                        if let Some(obj) = doc.as_object_mut() {
+                            obj.remove("vector");
+                        }
+                        hit.metadata = doc;
+                    }
+                    _ => {}
+                }
+            }
+            hits.push(hit);
+        }
+        Ok(hits)
+    }
 }
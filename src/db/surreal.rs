@@ -2,7 +2,7 @@ use base64::{ engine::general_purpose::STANDARD, Engine as _ };
 use log::{ info, error, warn };
 use reqwest::blocking::Client;
 use serde_json::Value;
-use super::{ Database, DbError };
+use super::{ Database, DbError, SearchFilter, SearchHit };
 
 pub struct SurrealDatabase {
     url: String,
@@ -10,6 +10,7 @@ pub struct SurrealDatabase {
     db: String,
     auth_header: Option<String>,
     client: Client,
+    metric: String,
 }
 
 impl SurrealDatabase {
@@ -68,7 +69,7 @@ impl SurrealDatabase {
             error!("Failed to execute DEFINE DATABASE (Status: {}): {}", status_db, text_db);
         }
 
-        Ok(SurrealDatabase { url: base_url, ns, db, auth_header, client })
+        Ok(SurrealDatabase { url: base_url, ns, db, auth_header, client, metric: args.metric.clone() })
     }
 
     fn ensure_table_exists(&self, table: &str) -> Result<(), DbError> {
@@ -144,7 +145,7 @@ impl Database for SurrealDatabase {
         for (id, data) in &records {
             let record_id = format!("{}:`{}`", normalized_table, id);
             let content_json = serde_json::to_string(&data)?;
-            import_data.push_str(&format!("CREATE {} CONTENT {};\n", record_id, content_json));
+            import_data.push_str(&format!("UPSERT {} CONTENT {};\n", record_id, content_json));
         }
 
         info!("SurrealDB Import URL: {}", import_url);
@@ -180,4 +181,91 @@ impl Database for SurrealDatabase {
               records.len(), normalized_table, table);
         Ok(())
     }
+
+    fn search(
+        &self,
+        table: &str,
+        vector: &[f32],
+        k: usize,
+        filter: Option<&SearchFilter>
+    ) -> Result<Vec<SearchHit>, DbError> {
+        let normalized_table = table.to_lowercase();
+        let (score_fn, order) = match self.metric.to_lowercase().as_str() {
+            "l2" | "euclidean" => ("vector::distance::euclidean", "ASC"),
+            "ip" | "dotproduct" | "dot" => ("vector::dot", "DESC"),
+            _ => ("vector::similarity::cosine", "DESC"),
+        };
+        let mut conditions = Vec::new();
+        if let Some(filter) = filter {
+            for (key, value) in filter {
+                conditions.push(format!("`{}` = {}", key.replace('`', ""), value));
+            }
+        }
+        let where_clause = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", conditions.join(" AND "))
+        };
+        let query = format!(
+            "LET $q = {}; SELECT * OMIT vector, {}(vector, $q) AS score FROM `{}`{} ORDER BY score {} LIMIT {};",
+            serde_json::to_string(vector)?,
+            score_fn,
+            normalized_table,
+            where_clause,
+            order,
+            k
+        );
+
+        let sql_url = format!("{}/sql", self.url.trim_end_matches('/'));
+        let mut req = self.client
+            .post(&sql_url)
+            .header("Content-Type", "text/plain")
+            .header("Accept", "application/json")
+            .header("Surreal-NS", &self.ns)
+            .header("Surreal-DB", &self.db)
+            .body(query);
+        if let Some(ref auth) = self.auth_header {
+            req = req.header("Authorization", auth);
+        }
+
+        let resp = req.send()?;
+        let status = resp.status();
+        let text = resp.text()?;
+        if !status.is_success() {
+            return Err(format!("SurrealDB search failed ({}): {}", status, text).into());
+        }
+
+        let json: Value = serde_json::from_str(&text)?;
+        let last = json
+            .as_array()
+            .and_then(|results| results.last())
+            .ok_or_else(|| format!("Unexpected SurrealDB search response: {}", text))?;
+        if last["status"].as_str() != Some("OK") {
+            return Err(format!("SurrealDB search failed: {}", last["result"]).into());
+        }
+
+        let prefix = format!("{}:", normalized_table);
+        let hits = last["result"]
+            .as_array()
+            .map(|rows| rows.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .map(|row| {
+                let mut metadata = row.clone();
+                let mut score = 0.0;
+                let mut id = String::new();
+                if let Some(obj) = metadata.as_object_mut() {
+                    score = obj.remove("score").and_then(|s| s.as_f64()).unwrap_or_default() as f32;
+                    let record_id = obj.remove("id").and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default();
+                    id = record_id
+                        .strip_prefix(&prefix)
+                        .unwrap_or(&record_id)
+                        .trim_matches(|c| matches!(c, '`' | '⟨' | '⟩'))
+                        .to_string();
+                }
+                SearchHit { id, score, metadata }
+            })
+            .collect();
+        Ok(hits)
+    }
 }
@@ -0,0 +1,146 @@
+use crate::cli::Args;
+use std::error::Error as StdError;
+
+/// Unit used to split long texts into overlapping windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkMode {
+    /// One vector per record; long texts are truncated.
+    Off,
+    Chars,
+    Sentences,
+    /// Whitespace-separated words, a cheap stand-in for model tokens.
+    Tokens,
+}
+
+impl ChunkMode {
+    pub fn parse(mode: &str) -> Result<Self, Box<dyn StdError + Send + Sync>> {
+        match mode.to_lowercase().as_str() {
+            "off" | "none" => Ok(ChunkMode::Off),
+            "chars" | "characters" => Ok(ChunkMode::Chars),
+            "sentences" => Ok(ChunkMode::Sentences),
+            "tokens" | "words" => Ok(ChunkMode::Tokens),
+            other => Err(format!("Unsupported text chunking mode: {}", other).into()),
+        }
+    }
+}
+
+/// Splits record texts into overlapping chunks of `size` units, each sharing
+/// `overlap` units with the previous one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunker {
+    pub mode: ChunkMode,
+    pub size: usize,
+    pub overlap: usize,
+}
+
+impl Chunker {
+    /// Builds a chunker from `--text-chunking`, `--text-chunk-size` and
+    /// `--text-chunk-overlap`. Unset sizes are derived from
+    /// `embedding_max_tokens` so every chunk fits the model.
+    pub fn from_args(args: &Args) -> Result<Self, Box<dyn StdError + Send + Sync>> {
+        let mode = ChunkMode::parse(&args.text_chunking)?;
+        let default_size = match mode {
+            ChunkMode::Off => 0,
+            ChunkMode::Chars => args.embedding_max_tokens * 3,
+            ChunkMode::Sentences => 8,
+            // Roughly 0.75 words per token for English text.
+            ChunkMode::Tokens => (args.embedding_max_tokens * 3) / 4,
+        };
+        let size = args.text_chunk_size.unwrap_or(default_size).max(1);
+        let overlap = args.text_chunk_overlap.unwrap_or(match mode {
+            ChunkMode::Sentences => 1.min(size - 1),
+            _ => size / 10,
+        });
+
+        if mode != ChunkMode::Off && overlap >= size {
+            return Err(
+                format!("--text-chunk-overlap ({}) must be smaller than --text-chunk-size ({})", overlap, size).into()
+            );
+        }
+        Ok(Self { mode, size, overlap })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.mode != ChunkMode::Off
+    }
+
+    /// Splits `text` into chunks. Always returns at least one chunk, so every
+    /// record yields at least one vector.
+    pub fn split(&self, text: &str) -> Vec<String> {
+        let spans = match self.mode {
+            ChunkMode::Off => {
+                return vec![text.to_string()];
+            }
+            ChunkMode::Chars =>
+                text
+                    .char_indices()
+                    .map(|(i, c)| (i, i + c.len_utf8()))
+                    .collect(),
+            ChunkMode::Sentences => sentence_spans(text),
+            ChunkMode::Tokens => word_spans(text),
+        };
+
+        if spans.len() <= self.size {
+            return vec![text.trim().to_string()];
+        }
+
+        let step = self.size - self.overlap;
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        loop {
+            let end = (start + self.size).min(spans.len());
+            chunks.push(text[spans[start].0..spans[end - 1].1].trim().to_string());
+            if end == spans.len() {
+                break;
+            }
+            start += step;
+        }
+        chunks
+    }
+}
+
+fn word_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (start, c.is_whitespace()) {
+            (None, false) => {
+                start = Some(i);
+            }
+            (Some(s), true) => {
+                spans.push((s, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, text.len()));
+    }
+    spans
+}
+
+/// Sentences end at `.`, `!` or `?` followed by whitespace, or at a newline.
+fn sentence_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next_is_space = chars.peek().is_none_or(|(_, n)| n.is_whitespace());
+        let end = match c {
+            '.' | '!' | '?' if next_is_space => i + c.len_utf8(),
+            '\n' => i,
+            _ => {
+                continue;
+            }
+        };
+        if !text[start..end].trim().is_empty() {
+            spans.push((start, end));
+        }
+        start = i + c.len_utf8();
+    }
+    if !text[start..].trim().is_empty() {
+        spans.push((start, text.len()));
+    }
+    spans
+}
@@ -1,15 +1,15 @@
 use log::{ error, info, warn };
 use serde_json::Value;
 use std::error::Error as StdError;
-use std::sync::atomic::{ AtomicUsize, Ordering };
-use std::sync::Arc;
-use tokio::runtime::Runtime;
 use uuid::Uuid;
 use crate::cli::Args;
 use crate::embedding::{
     models::google::GoogleEmbeddingClient,
     models::ollama::OllamaEmbeddingClient, 
+    models::openai::{ OpenAiAuth, OpenAiEmbeddingClient },
     models::tei::TeiEmbeddingClient,
+    chunk::Chunker,
+    template::TextTemplates,
     AsyncEmbeddingGenerator,
 };
 
@@ -21,7 +21,7 @@ pub fn initialize_embedding_generator(
     info!("Selected embedding provider: {}", provider);
 
     let url = override_url
-        .or_else(|| args.embedding_url.as_deref())
+        .or(args.embedding_url.as_deref())
         .map(|s| s.to_string());
 
     match provider.as_str() {
@@ -68,102 +68,172 @@ pub fn initialize_embedding_generator(
             Ok(Box::new(client))
         }
 
+        "openai" => {
+            let auth = OpenAiAuth::from_setting(&args.embedding_auth, url.as_deref().unwrap_or_default())?;
+            let client = OpenAiEmbeddingClient::new(
+                url.as_deref(),
+                args.embedding_api_key.clone(),
+                &args.embedding_model,
+                args.dimension,
+                args.embedding_timeout,
+            )?
+                .with_auth(auth)
+                .with_dimensions_param(args.embedding_send_dimensions);
+            info!("🟢 OpenAI-compatible client");
+            Ok(Box::new(client))
+        }
+
         other => Err(format!("Unsupported embedding provider: {}", other).into()),
     }
 }
 
-pub fn process_records_with_embeddings(
+/// A record ready for storage: `(table, id, vector, metadata)`.
+pub type PreparedRecord = (String, String, Vec<f32>, Value);
+
+/// Derives a stable vector ID so reruns upsert instead of duplicating.
+///
+/// Records with a `source_id` (their primary key) get a UUIDv5 of
+/// `table:source_id`; records without one fall back to a UUIDv5 of their
+/// table and full contents. Those do not depend on the file name or position,
+/// so a renamed or re-exported dump maps to the same vectors; repeated rows
+/// are told apart by the `source_occurrence` they carry.
+pub fn record_id(table: &str, record: &Value) -> String {
+    let name = match record.get("source_id") {
+        Some(Value::String(pk)) => format!("{}:{}", table, pk),
+        Some(pk) => format!("{}:{}", table, pk),
+        None => format!("{}#{}", table, record),
+    };
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, name.as_bytes()).to_string()
+}
+
+/// Turns records into the texts sent to the embedding model: the table's
+/// template (or every field) split into chunks by `chunker`.
+pub struct TextBuilder {
+    pub templates: TextTemplates,
+    pub chunker: Chunker,
+}
+
+impl TextBuilder {
+    pub fn from_args(args: &Args) -> Result<Self, Box<dyn StdError + Send + Sync>> {
+        Ok(Self {
+            templates: TextTemplates::load(&args.templates_file)?,
+            chunker: Chunker::from_args(args)?,
+        })
+    }
+}
+
+/// Builds the text sent to the embedding model for one record.
+fn record_text(record: &Value, templates: &TextTemplates) -> String {
+    templates.render(record).unwrap_or_else(|| {
+        record
+            .as_object()
+            .map(|obj| {
+                obj.iter()
+                    .filter(|(k, _)| !["table", "id", "source_id", "source_occurrence"].contains(&k.as_str()))
+                    .map(|(k, v)| format!("{}: {}", k, v))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .unwrap_or_else(|| record.to_string())
+    })
+}
+
+fn truncate_to_limit(mut text: String, args: &Args) -> String {
+    let approx_char_limit_from_tokens = args.embedding_max_tokens * 3;
+    if text.chars().count() > approx_char_limit_from_tokens {
+        warn!(
+            "Client-side truncation: Input text for a record ({} chars) exceeds approximate limit derived from embedding_max_tokens ({} tokens -> ~{} chars). Truncating. Provider might also truncate based on its own limits.",
+            text.chars().count(),
+            args.embedding_max_tokens,
+            approx_char_limit_from_tokens
+        );
+        text = text.chars().take(approx_char_limit_from_tokens).collect::<String>();
+    }
+    text
+}
+
+/// Vector ID of chunk `index` of the record whose ID is `parent_id`.
+pub fn chunk_id(parent_id: &str, index: usize) -> String {
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, format!("{}#{}", parent_id, index).as_bytes()).to_string()
+}
+
+/// Embeds `records` and pairs each vector with its ID.
+///
+/// With chunking enabled every record yields one vector per chunk, carrying
+/// `parent_id`, `chunk_index` and `chunk_count` in its metadata. Texts are
+/// sent in requests of at most `embedding_batch_size`.
+pub async fn embed_records(
     records: Vec<Value>,
     args: &Args,
-    embedding_counter: Arc<AtomicUsize>,
-    generator: Box<dyn AsyncEmbeddingGenerator + Send + Sync>
-) -> Result<Vec<(String, String, Vec<f32>, Value)>, Box<dyn StdError + Send + Sync>> {
-    let chunk_size = args.embedding_batch_size;
-    let total_records = records.len();
-    let mut prepared_records = Vec::with_capacity(total_records);
-    let rt = Runtime::new()?;
-    let approx_char_limit_from_tokens = args.embedding_max_tokens * 3;
+    text: &TextBuilder,
+    generator: &(dyn AsyncEmbeddingGenerator + Send + Sync)
+) -> Result<Vec<PreparedRecord>, Box<dyn StdError + Send + Sync>> {
+    let chunked: Vec<Vec<String>> = records
+        .iter()
+        .map(|record| {
+            text.chunker
+                .split(&record_text(record, &text.templates))
+                .into_iter()
+                .map(|text| truncate_to_limit(text, args))
+                .collect()
+        })
+        .collect();
+    let texts: Vec<String> = chunked.iter().flatten().cloned().collect();
+
+    let mut embeddings = Vec::with_capacity(texts.len());
+    for request in texts.chunks(args.embedding_batch_size.max(1)) {
+        let batch = generator.generate_embeddings_batch(request).await.map_err(|e| {
+            error!("CRITICAL: Embedding generation failed for batch of {}: {}", request.len(), e);
+            format!("Embedding generation failed: {}", e)
+        })?;
+        embeddings.extend(batch);
+    }
 
-    for (chunk_idx, chunk) in records.chunks(chunk_size).enumerate() {
-        info!(
-            "Processing embedding chunk {}/{}",
-            chunk_idx + 1,
-            (total_records + chunk_size - 1) / chunk_size
+    if embeddings.len() != texts.len() {
+        error!(
+            "CRITICAL: Embedding generator returned {} results for {} inputs",
+            embeddings.len(),
+            texts.len()
+        );
+        return Err(
+            format!(
+                "Embedding generator returned incomplete results: got {}/{}",
+                embeddings.len(),
+                texts.len()
+            ).into()
         );
+    }
 
-        let texts: Vec<String> = chunk
-            .iter()
-            .map(|record| {
-                let mut full_text = record 
-                    .as_object()
-                    .map(|obj| {
-                        obj.iter()
-                            .filter(|(k, _)| *k != "table" && *k != "id")
-                            .map(|(k, v)| format!("{}: {}", k, v))
-                            .collect::<Vec<_>>()
-                            .join(", ")
-                    })
-                    .unwrap_or_else(|| record.to_string());
-
-                if full_text.chars().count() > approx_char_limit_from_tokens {
-                    warn!(
-                        "Client-side truncation: Input text for a record ({} chars) exceeds approximate limit derived from embedding_max_tokens ({} tokens -> ~{} chars). Truncating. Provider might also truncate based on its own limits.",
-                        full_text.chars().count(),
-                        args.embedding_max_tokens,
-                        approx_char_limit_from_tokens
-                    );
-                    full_text = full_text.chars().take(approx_char_limit_from_tokens).collect::<String>();
-                }
-                full_text
-            })
-            .collect();
-
-        let embeddings_result = rt.block_on(generator.generate_embeddings_batch(&texts));
-
-        match embeddings_result {
-            Ok(embeddings) => {
-                if embeddings.len() != chunk.len() {
-                    error!(
-                        "CRITICAL: Embedding generator returned {} results for {} inputs in chunk {}",
-                        embeddings.len(),
-                        chunk.len(),
-                        chunk_idx + 1
-                    );
-                    return Err(
-                        format!(
-                            "Embedding generator returned incomplete results: got {}/{}",
-                            embeddings.len(),
-                            chunk.len()
-                        ).into()
-                    );
-                }
-
-                let chunk_results: Vec<_> = chunk
-                    .iter()
-                    .zip(embeddings.into_iter())
-                    .map(|(record, vec)| {
-                        let id = Uuid::new_v4().to_string();
-                        let mut meta = record.clone();
-                        let table = meta
-                            .get("table")
-                            .and_then(|t| t.as_str())
-                            .unwrap_or("unknown_table")
-                            .to_string();
-                        if let Some(_obj) = meta.as_object_mut() {
-                        }
-                        (table, id, vec, meta)
-                    })
-                    .collect();
-
-                prepared_records.extend(chunk_results);
-                embedding_counter.fetch_add(chunk.len(), Ordering::Relaxed);
-            }
-            Err(e) => {
-                error!("CRITICAL: Embedding generation failed for chunk {}: {}", chunk_idx + 1, e);
-                return Err(format!("Embedding generation failed: {}", e).into());
+    let mut embeddings = embeddings.into_iter();
+    let mut prepared = Vec::with_capacity(texts.len());
+    for (record, chunks) in records.into_iter().zip(chunked) {
+        let table = record
+            .get("table")
+            .and_then(|t| t.as_str())
+            .unwrap_or("unknown_table")
+            .to_string();
+        let id = record_id(&table, &record);
+
+        if !text.chunker.is_enabled() {
+            prepared.push((table, id, embeddings.next().unwrap(), record));
+            continue;
+        }
+
+        let chunk_count = chunks.len();
+        for chunk_index in 0..chunk_count {
+            let mut meta = record.clone();
+            if let Some(obj) = meta.as_object_mut() {
+                obj.insert("parent_id".to_string(), Value::String(id.clone()));
+                obj.insert("chunk_index".to_string(), chunk_index.into());
+                obj.insert("chunk_count".to_string(), chunk_count.into());
             }
+            prepared.push((
+                table.clone(),
+                chunk_id(&id, chunk_index),
+                embeddings.next().unwrap(),
+                meta,
+            ));
         }
     }
-
-    Ok(prepared_records)
-}
\ No newline at end of file
+    Ok(prepared)
+}
@@ -1,5 +1,7 @@
+pub mod chunk;
 pub mod embeding;
 pub mod models;
+pub mod template;
 
 use async_trait::async_trait;
 use std::error::Error as StdError;
@@ -12,6 +14,11 @@ pub trait AsyncEmbeddingGenerator: Send + Sync {
     ) -> Result<Vec<Vec<f32>>, Box<dyn StdError + Send + Sync>>;
 
     fn get_dimension(&self) -> usize;
+
+    /// Tokens billed by the provider so far, when it reports usage.
+    fn tokens_used(&self) -> Option<u64> {
+        None
+    }
 }
 
 pub trait EmbeddingModel {
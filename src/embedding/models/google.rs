@@ -100,7 +100,7 @@ impl AsyncEmbeddingGenerator for GoogleEmbeddingClient {
             });
 
             debug!("Request URL: {}", url);
-            debug!("Request body: {}", request_body.to_string());
+            debug!("Request body: {}", request_body);
 
             let response = self.client
                 .post(&url)
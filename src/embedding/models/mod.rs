@@ -1,3 +1,4 @@
 pub mod google;
 pub mod ollama;
+pub mod openai;
 pub mod tei;
@@ -0,0 +1,240 @@
+use crate::embedding::AsyncEmbeddingGenerator;
+use async_trait::async_trait;
+use log::{ debug, error, info, warn };
+use reqwest::{ Client, StatusCode };
+use serde::{ Deserialize, Serialize };
+use std::error::Error as StdError;
+use std::sync::atomic::{ AtomicU64, Ordering };
+use std::time::Duration;
+
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+const MAX_RETRIES: u32 = 3;
+
+#[derive(Serialize)]
+struct OpenAiRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+    encoding_format: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize, Default)]
+struct OpenAiUsage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    total_tokens: u64,
+}
+
+#[derive(Deserialize)]
+struct OpenAiResponse {
+    data: Vec<OpenAiEmbedding>,
+    #[serde(default)]
+    usage: Option<OpenAiUsage>,
+}
+
+/// How the API key is sent: `Authorization: Bearer` (OpenAI, vLLM, LocalAI,
+/// ...) or an `api-key` header (Azure OpenAI).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenAiAuth {
+    Bearer,
+    ApiKey,
+}
+
+impl OpenAiAuth {
+    /// Parses `bearer`, `api-key` or `auto` (api-key for Azure hosts).
+    pub fn from_setting(setting: &str, url: &str) -> Result<Self, Box<dyn StdError + Send + Sync>> {
+        match setting.to_lowercase().as_str() {
+            "bearer" => Ok(OpenAiAuth::Bearer),
+            "api-key" => Ok(OpenAiAuth::ApiKey),
+            "auto" if url.contains(".azure.com") => Ok(OpenAiAuth::ApiKey),
+            "auto" => Ok(OpenAiAuth::Bearer),
+            other => Err(format!("Unsupported embedding auth mode: {}", other).into()),
+        }
+    }
+}
+
+/// Client for any server implementing the OpenAI `/v1/embeddings` API.
+pub struct OpenAiEmbeddingClient {
+    client: Client,
+    endpoint: String,
+    api_key: Option<String>,
+    auth: OpenAiAuth,
+    model: String,
+    dimension: usize,
+    send_dimensions: bool,
+    total_tokens: AtomicU64,
+}
+
+impl OpenAiEmbeddingClient {
+    pub fn new(
+        base_url: Option<&str>,
+        api_key: Option<String>,
+        model: &str,
+        dimension: usize,
+        timeout_secs: u64
+    ) -> Result<Self, Box<dyn StdError + Send + Sync>> {
+        let endpoint = embeddings_endpoint(base_url.unwrap_or(DEFAULT_BASE_URL));
+        info!("OpenAI-compatible embeddings endpoint: {} (model: {})", endpoint, model);
+
+        Ok(Self {
+            client: Client::builder().timeout(Duration::from_secs(timeout_secs)).build()?,
+            auth: OpenAiAuth::from_setting("auto", &endpoint)?,
+            endpoint,
+            api_key: api_key.filter(|k| !k.is_empty()),
+            model: model.to_string(),
+            dimension,
+            send_dimensions: false,
+            total_tokens: AtomicU64::new(0),
+        })
+    }
+
+    pub fn with_auth(mut self, auth: OpenAiAuth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    /// Sends the configured dimension as the `dimensions` request parameter,
+    /// for models that support shortened embeddings.
+    pub fn with_dimensions_param(mut self, enabled: bool) -> Self {
+        self.send_dimensions = enabled;
+        self
+    }
+
+    async fn send_once(
+        &self,
+        request: &OpenAiRequest<'_>
+    ) -> Result<Result<OpenAiResponse, (StatusCode, String)>, reqwest::Error> {
+        let mut req = self.client.post(&self.endpoint).json(request);
+        if let Some(key) = &self.api_key {
+            req = match self.auth {
+                OpenAiAuth::Bearer => req.bearer_auth(key),
+                OpenAiAuth::ApiKey => req.header("api-key", key),
+            };
+        }
+
+        let response = req.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            Ok(Ok(response.json::<OpenAiResponse>().await?))
+        } else {
+            let body = response
+                .text().await
+                .unwrap_or_else(|_| "Failed to read error body".to_string());
+            Ok(Err((status, body)))
+        }
+    }
+}
+
+/// Accepts a base URL (`http://host:8000`, `.../v1`) or a full embeddings URL.
+fn embeddings_endpoint(url: &str) -> String {
+    let url = url.trim_end_matches('/');
+    if url.contains("/embeddings") {
+        url.to_string()
+    } else if url.ends_with("/v1") || url.contains("/openai/deployments/") {
+        format!("{}/embeddings", url)
+    } else {
+        format!("{}/v1/embeddings", url)
+    }
+}
+
+#[async_trait]
+impl AsyncEmbeddingGenerator for OpenAiEmbeddingClient {
+    async fn generate_embeddings_batch(
+        &self,
+        texts: &[String]
+    ) -> Result<Vec<Vec<f32>>, Box<dyn StdError + Send + Sync>> {
+        if texts.is_empty() {
+            return Ok(vec![]);
+        }
+
+        debug!("OpenAI: Generating embeddings for {} texts via {}", texts.len(), self.endpoint);
+
+        let request = OpenAiRequest {
+            model: &self.model,
+            input: texts,
+            encoding_format: "float",
+            dimensions: self.send_dimensions.then_some(self.dimension),
+        };
+
+        let mut attempt = 0;
+        let response = loop {
+            attempt += 1;
+            let retry_reason = match self.send_once(&request).await {
+                Ok(Ok(response)) => {
+                    break response;
+                }
+                Ok(Err((status, body))) => {
+                    if
+                        (status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()) &&
+                        attempt < MAX_RETRIES
+                    {
+                        format!("server returned {}", status)
+                    } else {
+                        error!("OpenAI-compatible server returned error {}: {}", status, body);
+                        return Err(format!("OpenAI embeddings error {}: {}", status, body).into());
+                    }
+                }
+                Err(e) if attempt < MAX_RETRIES => e.to_string(),
+                Err(e) => {
+                    error!("OpenAI request failed after {} attempts: {}", attempt, e);
+                    return Err(format!("OpenAI embeddings request failed: {}", e).into());
+                }
+            };
+            warn!("OpenAI request failed ({}), retrying ({}/{})", retry_reason, attempt, MAX_RETRIES);
+            tokio::time::sleep(Duration::from_millis(500 * (1 << attempt))).await;
+        };
+
+        if let Some(usage) = &response.usage {
+            self.total_tokens.fetch_add(usage.total_tokens, Ordering::Relaxed);
+            debug!(
+                "OpenAI usage: {} prompt tokens, {} total tokens",
+                usage.prompt_tokens,
+                usage.total_tokens
+            );
+        }
+
+        let mut data = response.data;
+        if data.len() != texts.len() {
+            return Err(
+                format!(
+                    "OpenAI response length mismatch: expected {}, got {}",
+                    texts.len(),
+                    data.len()
+                ).into()
+            );
+        }
+        data.sort_by_key(|d| d.index);
+
+        let embeddings: Vec<Vec<f32>> = data
+            .into_iter()
+            .map(|d| d.embedding)
+            .collect();
+        if let Some(emb) = embeddings.iter().find(|e| e.len() != self.dimension) {
+            return Err(
+                format!(
+                    "OpenAI dimension mismatch: expected {}, got {} (set --dimension or --embedding-send-dimensions)",
+                    self.dimension,
+                    emb.len()
+                ).into()
+            );
+        }
+
+        Ok(embeddings)
+    }
+
+    fn get_dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn tokens_used(&self) -> Option<u64> {
+        Some(self.total_tokens.load(Ordering::Relaxed))
+    }
+}
@@ -123,8 +123,7 @@ impl AsyncEmbeddingGenerator for TeiEmbeddingClient {
             }
         }
 
-        Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Other,
+        Err(Box::new(std::io::Error::other(
             format!("Failed after multiple retries: {}", last_error.unwrap())
         )))
     }
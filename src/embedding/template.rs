@@ -0,0 +1,295 @@
+use log::debug;
+use serde::Deserialize;
+use serde_json::{ Map, Value };
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fs;
+use std::path::Path;
+
+/// One table's entry in the templates file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct TemplateEntry {
+    /// Columns to embed, as `key: value` pairs, when no template is given.
+    pub columns: Vec<String>,
+    /// Text template, e.g. `"{title}\n\n{body}\nTags: {tags|join(', ')}"`.
+    pub template: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Join(String),
+    Default(String),
+    Lower,
+    Upper,
+    Truncate(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Field {
+        name: String,
+        filters: Vec<Filter>,
+    },
+}
+
+#[derive(Debug)]
+enum TableText {
+    Template(Vec<Segment>),
+    Columns(Vec<String>),
+}
+
+/// Per-table rules for building the text that gets embedded.
+///
+/// Tables without an entry use every field except `table`, `id` and
+/// `source_id`. All fields are still stored as metadata either way.
+#[derive(Debug, Default)]
+pub struct TextTemplates {
+    tables: HashMap<String, TableText>,
+}
+
+impl TextTemplates {
+    /// Loads templates from a JSON map of table name to [`TemplateEntry`].
+    ///
+    /// A missing file yields no templates; an unreadable or invalid one is an
+    /// error so a typo does not silently change what gets embedded.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn StdError + Send + Sync>> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path)?;
+        let entries: HashMap<String, TemplateEntry> = serde_json
+            ::from_str(&data)
+            .map_err(|e| format!("Invalid templates file '{}': {}", path.display(), e))?;
+        let templates = Self::from_entries(entries).map_err(|e|
+            format!("Invalid templates file '{}': {}", path.display(), e)
+        )?;
+        debug!("Loaded text templates for {} tables", templates.tables.len());
+        Ok(templates)
+    }
+
+    pub fn from_entries(
+        entries: HashMap<String, TemplateEntry>
+    ) -> Result<Self, Box<dyn StdError + Send + Sync>> {
+        let mut tables = HashMap::new();
+        for (table, entry) in entries {
+            let text = match entry.template {
+                Some(template) =>
+                    TableText::Template(
+                        parse_template(&template).map_err(|e| format!("table '{}': {}", table, e))?
+                    ),
+                None if !entry.columns.is_empty() => TableText::Columns(entry.columns),
+                None => {
+                    return Err(format!("table '{}' needs a template or columns", table).into());
+                }
+            };
+            tables.insert(table, text);
+        }
+        Ok(Self { tables })
+    }
+
+    /// Builds the embedding text for `record`, or `None` when its table has no
+    /// entry.
+    pub fn render(&self, record: &Value) -> Option<String> {
+        let table = record.get("table").and_then(Value::as_str)?;
+        let obj = record.as_object()?;
+        match self.tables.get(table)? {
+            TableText::Template(segments) => {
+                let mut out = String::new();
+                for segment in segments {
+                    match segment {
+                        Segment::Literal(text) => out.push_str(text),
+                        Segment::Field { name, filters } => {
+                            out.push_str(&render_field(field_value(obj, name), filters));
+                        }
+                    }
+                }
+                Some(out.trim().to_string())
+            }
+            TableText::Columns(columns) =>
+                Some(
+                    columns
+                        .iter()
+                        .filter_map(|col| obj.get(col).map(|v| format!("{}: {}", col, v)))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+        }
+    }
+}
+
+/// Looks up `name`, following dots into nested objects (`customer.name`) when
+/// no field has the dotted name itself.
+fn field_value<'a>(obj: &'a Map<String, Value>, name: &str) -> Option<&'a Value> {
+    obj.get(name).or_else(|| {
+        let (first, rest) = name.split_once('.')?;
+        field_value(obj.get(first)?.as_object()?, rest)
+    })
+}
+
+fn render_field(value: Option<&Value>, filters: &[Filter]) -> String {
+    let mut value = value.cloned().unwrap_or(Value::Null);
+    // Array columns often arrive as JSON text, e.g. from MySQL JSON columns.
+    if let Value::String(s) = &value && s.trim_start().starts_with('[') &&
+        let Ok(parsed @ Value::Array(_)) = serde_json::from_str::<Value>(s)
+    {
+        value = parsed;
+    }
+
+    let mut text: Option<String> = None;
+    for filter in filters {
+        if let Filter::Join(sep) = filter && let Value::Array(items) = &value {
+            text = Some(items.iter().map(plain_text).collect::<Vec<_>>().join(sep));
+        }
+    }
+    let mut text = text.unwrap_or_else(|| plain_text(&value));
+
+    for filter in filters {
+        match filter {
+            Filter::Default(fallback) if text.is_empty() => {
+                text = fallback.clone();
+            }
+            Filter::Lower => {
+                text = text.to_lowercase();
+            }
+            Filter::Upper => {
+                text = text.to_uppercase();
+            }
+            Filter::Truncate(n) => {
+                text = text.chars().take(*n).collect();
+            }
+            _ => {}
+        }
+    }
+    text
+}
+
+fn plain_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn parse_template(template: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let mut expr = String::new();
+                let mut quote: Option<char> = None;
+                loop {
+                    match chars.next() {
+                        None => {
+                            return Err(format!("unclosed placeholder '{{{}'", expr));
+                        }
+                        Some(c) if quote == Some(c) => {
+                            quote = None;
+                            expr.push(c);
+                        }
+                        Some(c @ ('\'' | '"')) if quote.is_none() => {
+                            quote = Some(c);
+                            expr.push(c);
+                        }
+                        Some('}') if quote.is_none() => {
+                            break;
+                        }
+                        Some(c) => expr.push(c),
+                    }
+                }
+                if !literal.is_empty() {
+                    segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                }
+                segments.push(parse_placeholder(&expr)?);
+            }
+            '}' => {
+                return Err("unmatched '}' (use '}}' for a literal brace)".to_string());
+            }
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+    Ok(segments)
+}
+
+fn parse_placeholder(expr: &str) -> Result<Segment, String> {
+    let mut parts = split_filters(expr).into_iter();
+    let name = parts.next().unwrap_or_default().trim().to_string();
+    if name.is_empty() {
+        return Err(format!("empty placeholder '{{{}}}'", expr));
+    }
+    let filters = parts.map(|f| parse_filter(f.trim())).collect::<Result<_, _>>()?;
+    Ok(Segment::Field { name, filters })
+}
+
+/// Splits `name|f1|f2('a|b')` on `|` outside quotes.
+fn split_filters(expr: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+    for (i, c) in expr.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => {
+                quote = None;
+            }
+            (Some(_), _) => {}
+            (None, '\'' | '"') => {
+                quote = Some(c);
+            }
+            (None, '|') => {
+                parts.push(&expr[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&expr[start..]);
+    parts
+}
+
+fn parse_filter(filter: &str) -> Result<Filter, String> {
+    let (name, arg) = match filter.split_once('(') {
+        Some((name, rest)) => {
+            let arg = rest
+                .strip_suffix(')')
+                .ok_or_else(|| format!("missing ')' in filter '{}'", filter))?
+                .trim();
+            let unquoted = arg
+                .strip_prefix('\'')
+                .and_then(|a| a.strip_suffix('\''))
+                .or_else(|| arg.strip_prefix('"').and_then(|a| a.strip_suffix('"')))
+                .unwrap_or(arg);
+            (name.trim(), Some(unquoted.to_string()))
+        }
+        None => (filter, None),
+    };
+
+    match (name, arg) {
+        ("join", arg) => Ok(Filter::Join(arg.unwrap_or_else(|| ", ".to_string()))),
+        ("default", Some(arg)) => Ok(Filter::Default(arg)),
+        ("lower", None) => Ok(Filter::Lower),
+        ("upper", None) => Ok(Filter::Upper),
+        ("truncate", Some(arg)) =>
+            arg
+                .parse()
+                .map(Filter::Truncate)
+                .map_err(|_| format!("truncate expects a number, got '{}'", arg)),
+        _ => Err(format!("unknown filter '{}'", filter)),
+    }
+}
@@ -3,4 +3,5 @@ pub mod parser;
 pub mod embedding;
 pub mod cli;
 pub mod util;
-pub mod workflow;
\ No newline at end of file
+pub mod workflow;
+pub mod search;
\ No newline at end of file
@@ -2,24 +2,45 @@
 use db2vec::util;
 
 use clap::Parser;
-use db2vec::cli::Args;
+use db2vec::cli::{ Args, Command };
 use db2vec::db::select_database;
 use dotenvy::dotenv;
+use std::sync::Arc;
 
 use log::{ info, error };
-use db2vec::util::{ read_file_and_detect_format, logo };
-use db2vec::parser::parse_database_export;
+use db2vec::util::logo;
+use db2vec::parser::{
+    join_records,
+    open_dump_files,
+    open_reject_file,
+    parse_joins,
+    print_join_suggestions,
+};
 use db2vec::workflow::execute_migration_workflow;
+use db2vec::search::{ execute_search, print_search_results };
 
 fn main() -> Result<(), db2vec::db::DbError> {
     logo();
     dotenv().ok();
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("off")).init();
     let args = Args::parse();
+
+    if let Some(Command::Search(search)) = &args.command {
+        let database = select_database(&args, Arc::default())?;
+        let hits = execute_search(&args, search, &*database)?;
+        print_search_results(&hits);
+        return Ok(());
+    }
+
     let file_path = args.dump_file.clone();
     util::init_thread_pool(args.num_threads);
+    let joins = parse_joins(&args.joins)?;
+    if let Some(path) = &args.reject_file {
+        open_reject_file(path)?;
+    }
 
-    let (content, format) = match read_file_and_detect_format(&file_path) {
+    let schema = Arc::default();
+    let (records, summary) = match open_dump_files(&file_path, &args, &schema) {
         Ok(result) => result,
         Err(e) => {
             let err_msg = format!("Error reading file '{}': {}", file_path, e);
@@ -28,16 +49,16 @@ fn main() -> Result<(), db2vec::db::DbError> {
         }
     };
 
-    let records = match parse_database_export(&content, &format, &args) {
-        Ok(recs) => recs,
-        Err(e) => {
-            let err_msg = format!("Error parsing database export: {}", e);
-            error!("{}", err_msg);
-            return Err(err_msg.into());
-        }
-    };
+    if let Some(Command::Inspect) = &args.command {
+        let result = records.into_iter().try_for_each(|record| record.map(drop));
+        summary.lock().unwrap().print();
+        print_join_suggestions(&schema, &joins);
+        return result.map_err(|e| format!("Error reading file '{}': {}", file_path, e).into());
+    }
+
+    let records = join_records(records, joins.clone());
 
-    let database = select_database(&args)?;
+    let database = select_database(&args, schema.clone())?;
     match execute_migration_workflow(records, &*database, &args) {
         Ok(stats) => {
             info!(
@@ -45,9 +66,12 @@ fn main() -> Result<(), db2vec::db::DbError> {
                 stats.processed_records,
                 stats.elapsed_seconds
             );
+            summary.lock().unwrap().print();
+            print_join_suggestions(&schema, &joins);
             Ok(())
         }
         Err(e) => {
+            summary.lock().unwrap().print();
             error!("Migration failed: {}", e);
             Err(e)
         }
@@ -0,0 +1,197 @@
+use crate::cli::Args;
+use crate::parser::parse_regex::json_text;
+use crate::parser::diagnostics::{ report_rejection, Rejection };
+use crate::parser::{ table_name_from_path, ExportParser, ParseContext, RecordIter };
+use crate::util::strip_compression_extension;
+use csv::{ ReaderBuilder, StringRecord };
+use log::info;
+use serde_json::{ Map, Number, Value };
+use std::io::{ self, BufRead };
+
+/// Delimiter for `--csv-delimiter`, or guessed from the file extension.
+pub fn csv_delimiter(setting: Option<&str>, path: &str) -> Result<u8, String> {
+    match setting.map(str::trim) {
+        None | Some("") | Some("auto") => {
+            let lower = strip_compression_extension(path).to_lowercase();
+            Ok(if lower.ends_with(".tsv") || lower.ends_with(".tab") { b'\t' } else { b',' })
+        }
+        Some("\\t" | "tab" | "\t") => Ok(b'\t'),
+        Some(d) if d.len() == 1 && d.is_ascii() => Ok(d.as_bytes()[0]),
+        Some(d) => Err(format!("--csv-delimiter must be a single ASCII character or 'tab', got '{}'", d)),
+    }
+}
+
+/// Infers a typed value from a CSV cell: empty, `NULL` and `\N` become null,
+/// `true`/`false` booleans, JSON arrays and objects are parsed, and numbers
+/// are converted unless they carry a leading zero (zip codes, phone numbers).
+pub fn infer_cell(cell: &str) -> Value {
+    let trimmed = cell.trim();
+    if trimmed.is_empty() || trimmed == "NULL" || trimmed == "\\N" {
+        return Value::Null;
+    }
+    if trimmed.eq_ignore_ascii_case("true") {
+        return Value::Bool(true);
+    }
+    if trimmed.eq_ignore_ascii_case("false") {
+        return Value::Bool(false);
+    }
+    if let json @ (Value::Array(_) | Value::Object(_)) = json_text(trimmed.to_string()) {
+        return json;
+    }
+
+    let digits = trimmed.trim_start_matches(['-', '+']);
+    let leading_zero = digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.");
+    if !leading_zero && digits.starts_with(|c: char| c.is_ascii_digit()) {
+        if let Ok(n) = trimmed.parse::<i64>() {
+            return Value::Number(n.into());
+        }
+        if let Ok(f) = trimmed.parse::<f64>() && let Some(n) = Number::from_f64(f) {
+            return Value::Number(n);
+        }
+    }
+    Value::String(cell.to_string())
+}
+
+/// Column names from the header row. Blank names become `column<N>` and
+/// repeated names get a `_<N>` suffix so no cell is lost.
+fn column_names(headers: &StringRecord) -> Vec<String> {
+    let mut names: Vec<String> = Vec::with_capacity(headers.len());
+    for (i, header) in headers.iter().enumerate() {
+        let base = header.trim_start_matches('\u{feff}').trim();
+        let base = if base.is_empty() { format!("column{}", i) } else { base.to_string() };
+        let mut name = base.clone();
+        let mut n = 2;
+        while names.contains(&name) {
+            name = format!("{}_{}", base, n);
+            n += 1;
+        }
+        names.push(name);
+    }
+    names
+}
+
+/// Streams one record per CSV/TSV row, using the header row for field names
+/// and the file name for the table. Quoted fields may span lines.
+pub struct CsvRecords<R: BufRead> {
+    reader: csv::Reader<R>,
+    table: String,
+    columns: Option<Vec<String>>,
+    ctx: ParseContext,
+    row: StringRecord,
+    skip: bool,
+    delimiter: u8,
+    /// Input named in rejection reports.
+    source: String,
+}
+
+impl<R: BufRead> CsvRecords<R> {
+    /// Names the input in rejection reports, e.g. with its file path.
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source = source.to_string();
+        self
+    }
+}
+
+/// The CSV reader as a registered parser. CSV files are recognized by their
+/// extension during detection, so `detect` claims nothing on its own.
+pub struct CsvParser;
+
+impl ExportParser for CsvParser {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    fn detect(&self, _path: &str, _head: &[u8]) -> u32 {
+        0
+    }
+
+    fn parse(&self, path: &str, reader: Box<dyn BufRead + Send>, args: &Args) -> RecordIter {
+        match csv_delimiter(args.csv_delimiter.as_deref(), path) {
+            Ok(delimiter) =>
+                Box::new(stream_csv_export(reader, &table_name_from_path(path), delimiter, args).with_source(path)),
+            Err(e) => Box::new(std::iter::once(Err(io::Error::new(io::ErrorKind::InvalidInput, e)))),
+        }
+    }
+}
+
+pub fn stream_csv_export<R: BufRead>(
+    reader: R,
+    table: &str,
+    delimiter: u8,
+    args: &Args
+) -> CsvRecords<R> {
+    info!("Streaming CSV records into table '{}'", table);
+    // Cells are typed by `infer_cell`.
+    let ctx = ParseContext { guess_json: false, ..ParseContext::new(args) };
+    let skip = ctx.ignore_table(table);
+    if skip {
+        info!("Skipping excluded CSV table: {}", table);
+    }
+    CsvRecords {
+        reader: ReaderBuilder::new()
+            .delimiter(delimiter)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(reader),
+        table: table.to_string(),
+        columns: None,
+        ctx,
+        row: StringRecord::new(),
+        skip,
+        delimiter,
+        source: String::new(),
+    }
+}
+
+impl<R: BufRead> Iterator for CsvRecords<R> {
+    type Item = io::Result<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.skip {
+                return None;
+            }
+            match self.reader.read_record(&mut self.row) {
+                Ok(true) => {}
+                Ok(false) => {
+                    return None;
+                }
+                Err(e) => {
+                    return Some(Err(io::Error::new(io::ErrorKind::InvalidData, e)));
+                }
+            }
+
+            let Some(columns) = &self.columns else {
+                self.columns = Some(column_names(&self.row));
+                continue;
+            };
+            if self.row.iter().all(|cell| cell.trim().is_empty()) {
+                continue;
+            }
+            if self.row.len() > columns.len() {
+                let rejection = Rejection {
+                    file: self.source.clone(),
+                    line: self.row.position().map_or(0, |p| p.line() as usize),
+                    table: self.table.clone(),
+                    reason: format!("{} fields for {} columns", self.row.len(), columns.len()),
+                    raw: self.row
+                        .iter()
+                        .collect::<Vec<_>>()
+                        .join(&char::from(self.delimiter).to_string()),
+                };
+                report_rejection(&rejection);
+                continue;
+            }
+
+            let mut obj = Map::new();
+            obj.insert("table".to_string(), Value::String(self.table.clone()));
+            for (name, cell) in columns.iter().zip(self.row.iter()) {
+                obj.insert(name.clone(), infer_cell(cell));
+            }
+
+            let mut record = Value::Object(obj);
+            self.ctx.finish_record(&mut record);
+            return Some(Ok(record));
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use crate::parser::registry::registered_parsers;
+use crate::util::strip_compression_extension;
+use log::info;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// Formats read by dedicated readers rather than a registered `ExportParser`.
+const READER_FORMATS: &[&str] = &["sqlite-db"];
+
+/// Points the best format must lead the runner-up by before detection is trusted.
+const MIN_LEAD: u32 = 2;
+
+/// Score at which a parser's claim beats the file-name and JSON shortcuts.
+const CLAIM_SCORE: u32 = 3;
+
+static GO_LINE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?m)^[ \t]*GO[ \t]*;?[ \t]*\r?$").unwrap());
+static COPY_FROM_STDIN: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?m)^COPY .+ FROM stdin\b").unwrap());
+static BACKTICK_INSERT: Lazy<Regex> = Lazy::new(|| Regex::new(r"INSERT INTO `[^`]+`").unwrap());
+
+/// A piece of dump content that points at one source database.
+struct Signature {
+    format: &'static str,
+    name: &'static str,
+    /// 3 for output only that database's dump tool produces, 1 for hints
+    /// other dumps can contain too.
+    weight: u32,
+    matches: fn(&str) -> bool,
+}
+
+static SIGNATURES: &[Signature] = &[
+    Signature { format: "oracle", name: "REM INSERTING into", weight: 3, matches: |c| c.contains("REM INSERTING into") },
+    Signature { format: "oracle", name: "SET DEFINE OFF;", weight: 3, matches: |c| c.contains("SET DEFINE OFF;") },
+    Signature { format: "oracle", name: "'Insert into' statements", weight: 1, matches: |c| c.contains("Insert into ") },
+    Signature {
+        format: "oracle",
+        name: "PCTFREE/TABLESPACE storage clauses",
+        weight: 3,
+        matches: |c| c.contains("CREATE TABLE \"") && c.contains("PCTFREE") && c.contains("TABLESPACE"),
+    },
+    Signature {
+        format: "oracle",
+        name: "BUFFER_POOL storage clause",
+        weight: 3,
+        matches: |c| c.contains("BUFFER_POOL DEFAULT FLASH_CACHE DEFAULT CELL_FLASH_CACHE DEFAULT"),
+    },
+    Signature { format: "oracle", name: "USING INDEX PCTFREE", weight: 3, matches: |c| c.contains("USING INDEX PCTFREE") },
+    Signature { format: "oracle", name: "ALTER SESSION SET EVENTS", weight: 2, matches: |c| c.contains("ALTER SESSION SET EVENTS") },
+    Signature { format: "oracle", name: "DBMS_LOGREP_IMP", weight: 3, matches: |c| c.contains("DBMS_LOGREP_IMP") },
+    Signature { format: "postgres", name: "COPY ... FROM stdin", weight: 3, matches: |c| COPY_FROM_STDIN.is_match(c) },
+    Signature { format: "postgres", name: "pg_dump header", weight: 3, matches: |c| c.contains("PostgreSQL database dump") },
+    Signature {
+        format: "postgres",
+        name: "SET standard_conforming_strings",
+        weight: 3,
+        matches: |c| c.contains("SET standard_conforming_strings"),
+    },
+    Signature { format: "postgres", name: "ALTER TABLE ONLY", weight: 2, matches: |c| c.contains("ALTER TABLE ONLY") },
+    Signature {
+        format: "postgres",
+        name: "CREATE TYPE ... AS ENUM",
+        weight: 1,
+        matches: |c| c.contains("CREATE TYPE") && c.contains("AS ENUM"),
+    },
+    Signature {
+        format: "postgres",
+        name: "CREATE SEQUENCE ... OWNED BY",
+        weight: 2,
+        matches: |c| c.contains("CREATE SEQUENCE") && c.contains("OWNED BY"),
+    },
+    Signature {
+        format: "sqlite",
+        name: "PRAGMA foreign_keys=OFF header",
+        weight: 3,
+        matches: |c| c.starts_with("PRAGMA foreign_keys=OFF;"),
+    },
+    Signature {
+        format: "sqlite",
+        name: "BEGIN TRANSACTION ... COMMIT wrapper",
+        weight: 1,
+        matches: |c| {
+            c.contains("BEGIN TRANSACTION;") &&
+                c.contains("COMMIT;") &&
+                c.contains("CREATE TABLE") &&
+                c.contains("INSERT INTO ")
+        },
+    },
+    Signature { format: "sqlite", name: "sqlite_sequence", weight: 3, matches: |c| c.contains("sqlite_sequence") },
+    Signature { format: "mssql", name: "SET ANSI_NULLS ON", weight: 3, matches: |c| c.contains("SET ANSI_NULLS ON") },
+    Signature { format: "mssql", name: "SET QUOTED_IDENTIFIER ON", weight: 3, matches: |c| c.contains("SET QUOTED_IDENTIFIER ON") },
+    Signature { format: "mssql", name: "CREATE TABLE [dbo].", weight: 3, matches: |c| c.contains("CREATE TABLE [dbo].") },
+    Signature { format: "mssql", name: "INSERT [dbo].", weight: 3, matches: |c| c.contains("INSERT [dbo].") },
+    Signature { format: "mssql", name: "WITH (PAD_INDEX = OFF", weight: 3, matches: |c| c.contains("WITH (PAD_INDEX = OFF") },
+    Signature { format: "mssql", name: "GO batch separator lines", weight: 2, matches: |c| GO_LINE.is_match(c) },
+    Signature { format: "mysql", name: "ENGINE=InnoDB", weight: 3, matches: |c| c.contains("ENGINE=InnoDB") },
+    Signature { format: "mysql", name: "LOCK TABLES", weight: 2, matches: |c| c.contains("LOCK TABLES") },
+    Signature { format: "mysql", name: "/*!40 version comments", weight: 3, matches: |c| c.contains("/*!40") },
+    Signature { format: "mysql", name: "AUTO_INCREMENT", weight: 1, matches: |c| c.contains("AUTO_INCREMENT") },
+    Signature { format: "mysql", name: "COLLATE=utf8mb4", weight: 2, matches: |c| c.contains("COLLATE=utf8mb4") },
+    Signature { format: "mysql", name: "backtick-quoted INSERT INTO", weight: 2, matches: |c| BACKTICK_INSERT.is_match(c) },
+    Signature { format: "surreal", name: "OPTION IMPORT;", weight: 3, matches: |c| c.contains("OPTION IMPORT;") },
+    Signature { format: "surreal", name: "DEFINE TABLE", weight: 3, matches: |c| c.contains("DEFINE TABLE ") },
+];
+
+/// How strongly a sample of a dump points at one format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatScore {
+    pub format: &'static str,
+    pub score: u32,
+    /// Names of the signatures found in the sample.
+    pub matched: Vec<&'static str>,
+}
+
+/// Formats accepted by `--format`: every registered parser, then the
+/// SQLite database reader.
+pub fn formats() -> Vec<&'static str> {
+    registered_parsers()
+        .iter()
+        .map(|p| p.name())
+        .chain(READER_FORMATS.iter().copied())
+        .collect()
+}
+
+/// Total weight and names of the signatures of `format` found in `content`.
+pub(crate) fn signature_matches(format: &str, content: &str) -> (u32, Vec<&'static str>) {
+    SIGNATURES.iter()
+        .filter(|sig| sig.format == format && (sig.matches)(content))
+        .fold((0, Vec::new()), |(score, mut names), sig| {
+            names.push(sig.name);
+            (score + sig.weight, names)
+        })
+}
+
+/// Asks every registered parser how likely `content`, read from `file_path`,
+/// is in its format. Returns the formats with any confidence, best first.
+fn score_parsers(file_path: &str, content: &str) -> Vec<FormatScore> {
+    let mut scores: Vec<FormatScore> = registered_parsers()
+        .iter()
+        .filter_map(|parser| {
+            let score = parser.detect(file_path, content.as_bytes());
+            (score > 0).then(|| FormatScore {
+                format: parser.name(),
+                score,
+                matched: signature_matches(parser.name(), content).1,
+            })
+        })
+        .collect();
+    scores.sort_by_key(|s| std::cmp::Reverse(s.score));
+    scores
+}
+
+/// Scores every format that recognizes `content`, best first.
+pub fn score_formats(content: &str) -> Vec<FormatScore> {
+    score_parsers("", content)
+}
+
+/// One line per candidate, e.g. `mysql 5 (ENGINE=InnoDB, LOCK TABLES)`.
+pub fn format_report(scores: &[FormatScore]) -> String {
+    scores
+        .iter()
+        .map(|s| format!("{} {} ({})", s.format, s.score, s.matched.join(", ")))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Formats decided by the file name, or by content that can only be JSON,
+/// unless a registered parser already claims the file with `scores`.
+fn format_from_name(file_path: &str, content: &str, scores: &[FormatScore]) -> Option<&'static str> {
+    if scores.first().is_some_and(|best| best.score >= CLAIM_SCORE) {
+        return None;
+    }
+    // `dump.sql.gz` is detected as `dump.sql`.
+    let lower_path = strip_compression_extension(file_path).to_lowercase();
+    if lower_path.ends_with(".surql") {
+        return Some("surreal");
+    }
+    if [".csv", ".tsv", ".tab"].iter().any(|ext| lower_path.ends_with(ext)) {
+        return Some("csv");
+    }
+    if
+        [".json", ".jsonl", ".ndjson"].iter().any(|ext| lower_path.ends_with(ext)) ||
+        content.trim_start().starts_with(['{', '['])
+    {
+        return Some("json");
+    }
+    None
+}
+
+/// Best guess at the format of a dump, falling back to `json` when nothing
+/// matches. See `detect_format_checked` for a guess that can be refused.
+pub fn detect_format(file_path: &str, content: &str) -> String {
+    let scores = score_parsers(file_path, content);
+    if let Some(format) = format_from_name(file_path, content, &scores) {
+        return format.to_string();
+    }
+    scores
+        .first()
+        .map_or("json", |s| s.format)
+        .to_string()
+}
+
+/// Detects the format of a dump from its name and a sample of its content.
+///
+/// Fails when no signature matches, or when the two best formats score within
+/// `MIN_LEAD` of each other; `--format` settles both cases.
+pub fn detect_format_checked(file_path: &str, content: &str) -> Result<String, String> {
+    let scores = score_parsers(file_path, content);
+    if let Some(format) = format_from_name(file_path, content, &scores) {
+        return Ok(format.to_string());
+    }
+
+    let report = format_report(&scores);
+    match scores.as_slice() {
+        [] =>
+            Err(
+                format!(
+                    "Could not detect the format of '{}': no known dump signatures found; pass --format ({})",
+                    file_path,
+                    formats().join("|")
+                )
+            ),
+        [best, second, ..] if best.score < second.score + MIN_LEAD =>
+            Err(
+                format!(
+                    "Format of '{}' is ambiguous between {} and {} (signatures: {}); pass --format to choose",
+                    file_path,
+                    best.format,
+                    second.format,
+                    report
+                )
+            ),
+        [best, ..] => {
+            info!("Format signatures in {}: {}", file_path, report);
+            Ok(best.format.to_string())
+        }
+    }
+}
+
+/// Checks a `--format` value against `formats()`, returning the format's own name.
+pub fn validate_format(format: &str) -> Result<String, String> {
+    let format = format.trim();
+    let known = formats();
+    match known.iter().find(|f| f.eq_ignore_ascii_case(format)) {
+        Some(name) => Ok(name.to_string()),
+        None => Err(format!("Unknown --format '{}'; expected one of {}", format, known.join("|"))),
+    }
+}
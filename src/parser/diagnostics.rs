@@ -0,0 +1,71 @@
+use log::warn;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{ self, LineWriter, Write };
+use std::sync::Mutex;
+
+/// Characters of a rejected row shown in log messages.
+const SNIPPET_CHARS: usize = 200;
+
+/// Rejections of every dump read so far, shared with the input summary.
+static REJECTS: Lazy<Mutex<RejectLog>> = Lazy::new(|| Mutex::new(RejectLog::default()));
+
+/// A source row that a parser could not turn into a record.
+#[derive(Debug, Clone, Serialize)]
+pub struct Rejection {
+    /// Input file, empty when reading from memory.
+    pub file: String,
+    /// Line the row starts on.
+    pub line: usize,
+    pub table: String,
+    pub reason: String,
+    /// The row as written in the dump.
+    pub raw: String,
+}
+
+#[derive(Default)]
+struct RejectLog {
+    counts: BTreeMap<String, usize>,
+    file: Option<LineWriter<File>>,
+}
+
+/// Writes every rejection from now on to `path` as one JSON object per line.
+pub fn open_reject_file(path: &str) -> io::Result<()> {
+    let file = File::create(path).map_err(|e|
+        io::Error::new(e.kind(), format!("cannot create reject file '{}': {}", path, e))
+    )?;
+    REJECTS.lock().unwrap().file = Some(LineWriter::new(file));
+    Ok(())
+}
+
+/// Counts `rejection`, logs it and appends it to the reject file if one is open.
+pub fn report_rejection(rejection: &Rejection) {
+    let snippet: String = rejection.raw.chars().take(SNIPPET_CHARS).collect();
+    warn!(
+        "Rejected row of table '{}' at {}:{}: {}. Row: '{}'",
+        rejection.table,
+        if rejection.file.is_empty() { "<input>" } else { &rejection.file },
+        rejection.line,
+        rejection.reason,
+        snippet
+    );
+
+    let mut log = REJECTS.lock().unwrap();
+    *log.counts.entry(rejection.table.clone()).or_default() += 1;
+    if let Some(file) = &mut log.file {
+        let written = serde_json
+            ::to_string(rejection)
+            .map_err(io::Error::other)
+            .and_then(|line| writeln!(file, "{}", line));
+        if let Err(e) = written {
+            warn!("Failed to write to reject file: {}", e);
+        }
+    }
+}
+
+/// Rejected rows per table so far.
+pub fn rejected_counts() -> BTreeMap<String, usize> {
+    REJECTS.lock().unwrap().counts.clone()
+}
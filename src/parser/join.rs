@@ -0,0 +1,314 @@
+use crate::parser::schema::{ source_id_part, DumpSchema };
+use crate::parser::RecordIter;
+use log::{ info, warn };
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::{ Map, Value };
+use std::collections::{ HashMap, HashSet };
+use std::io;
+
+/// Buffered rows of joined tables at which `JoinedRecords` first warns about
+/// memory use; it warns again each time the buffer doubles.
+const JOIN_BUFFER_WARN_ROWS: usize = 1_000_000;
+
+/// `child.column -> parent.column [as field[]]`
+static JOIN_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?i)^\s*([\w$]+)\.([\w$]+)\s*->\s*([\w$]+)\.([\w$]+)(?:\s+as\s+([\w$]+)(\[\])?)?\s*$"
+    ).unwrap()
+});
+
+/// One `--join` rule.
+///
+/// `orders.customer_id -> customers.id` adds the matching `customers` row to
+/// every `orders` record under `customer`. With `as items[]`, as in
+/// `order_items.order_id -> orders.id as items[]`, the rows of the left table
+/// are instead collected into an `items` array on each matching `orders`
+/// record and are no longer stored on their own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JoinSpec {
+    /// Table holding the foreign key.
+    pub table: String,
+    pub column: String,
+    /// Table the foreign key points to.
+    pub ref_table: String,
+    pub ref_column: String,
+    /// Field the related row (or rows) are stored under.
+    pub field: String,
+    /// Nest rows of `table` into `ref_table` instead of looking up `ref_table`.
+    pub nested: bool,
+}
+
+impl JoinSpec {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let cap = JOIN_RE.captures(spec).ok_or_else(|| {
+            format!(
+                "invalid join '{}': expected 'table.column -> table.column [as field[]]'",
+                spec
+            )
+        })?;
+        let nested = cap.get(6).is_some();
+        let field = match cap.get(5) {
+            Some(field) => field.as_str().to_string(),
+            None if nested => cap[1].to_string(),
+            None => default_lookup_field(&cap[2], &cap[3]),
+        };
+        Ok(JoinSpec {
+            table: cap[1].to_string(),
+            column: cap[2].to_string(),
+            ref_table: cap[3].to_string(),
+            ref_column: cap[4].to_string(),
+            field,
+            nested,
+        })
+    }
+
+    fn tables(&self) -> [&str; 2] {
+        [&self.table, &self.ref_table]
+    }
+}
+
+/// `customer_id` becomes `customer`; other columns use the referenced table.
+fn default_lookup_field(column: &str, ref_table: &str) -> String {
+    match column.len().checked_sub(3) {
+        Some(stem) if stem > 0 && column[stem..].eq_ignore_ascii_case("_id") => column[..stem].to_string(),
+        _ => ref_table.to_string(),
+    }
+}
+
+/// Parses every `--join` spec, failing on the first invalid one.
+pub fn parse_joins(specs: &[String]) -> io::Result<Vec<JoinSpec>> {
+    specs
+        .iter()
+        .filter(|spec| !spec.trim().is_empty())
+        .map(|spec| JoinSpec::parse(spec).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e)))
+        .collect()
+}
+
+/// Value of `column` in `record` as a join key. Key columns named `id` have
+/// been moved to `source_id` by the parser, so that is used in their place.
+fn join_key(record: &Value, column: &str) -> Option<String> {
+    let obj = record.as_object()?;
+    let value = obj
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(column))
+        .map(|(_, v)| v)
+        .or_else(|| if column.eq_ignore_ascii_case("id") { obj.get("source_id") } else { None })?;
+    if value.is_null() {
+        return None;
+    }
+    Some(source_id_part(value))
+}
+
+/// A record as it is embedded inside another one, without its `table` field.
+fn related_row(record: &Value) -> Value {
+    let mut row = record.as_object().cloned().unwrap_or_else(Map::new);
+    row.remove("table");
+    Value::Object(row)
+}
+
+/// Records of the joined tables, grouped per table in order of first appearance.
+#[derive(Default)]
+struct Tables {
+    groups: Vec<(String, Vec<Value>)>,
+    /// Rows held across all groups.
+    len: usize,
+}
+
+impl Tables {
+    fn position(&self, table: &str) -> Option<usize> {
+        self.groups.iter().position(|(t, _)| t.eq_ignore_ascii_case(table))
+    }
+
+    fn push(&mut self, table: &str, record: Value) {
+        self.len += 1;
+        match self.position(table) {
+            Some(i) => self.groups[i].1.push(record),
+            None => self.groups.push((table.to_string(), vec![record])),
+        }
+    }
+
+    fn rows(&self, table: &str) -> &[Value] {
+        self.position(table).map_or(&[], |i| &self.groups[i].1)
+    }
+
+    fn rows_mut(&mut self, table: &str) -> &mut [Value] {
+        match self.position(table) {
+            Some(i) => &mut self.groups[i].1,
+            None => &mut [],
+        }
+    }
+
+    /// Applies `join` to the buffered records.
+    fn apply(&mut self, join: &JoinSpec) {
+        let total = self.rows(if join.nested { &join.ref_table } else { &join.table }).len();
+        let mut matched = 0;
+
+        if join.nested {
+            let mut children: HashMap<String, Vec<Value>> = HashMap::new();
+            for row in self.rows(&join.table) {
+                if let Some(key) = join_key(row, &join.column) {
+                    children.entry(key).or_default().push(related_row(row));
+                }
+            }
+            for row in self.rows_mut(&join.ref_table) {
+                // Parents without children get no field rather than an empty array.
+                let Some(items) = join_key(row, &join.ref_column).and_then(|key| children.get(&key)) else {
+                    continue;
+                };
+                matched += 1;
+                if let Some(obj) = row.as_object_mut() {
+                    obj.insert(join.field.clone(), Value::Array(items.clone()));
+                }
+            }
+        } else {
+            let mut parents: HashMap<String, Value> = HashMap::new();
+            for row in self.rows(&join.ref_table) {
+                if let Some(key) = join_key(row, &join.ref_column) {
+                    parents.entry(key).or_insert_with(|| related_row(row));
+                }
+            }
+            for row in self.rows_mut(&join.table) {
+                let Some(parent) = join_key(row, &join.column).and_then(|key| parents.get(&key)) else {
+                    continue;
+                };
+                matched += 1;
+                if let Some(obj) = row.as_object_mut() {
+                    obj.insert(join.field.clone(), parent.clone());
+                }
+            }
+        }
+
+        let target = if join.nested { &join.ref_table } else { &join.table };
+        if total > 0 && matched == 0 {
+            warn!(
+                "Join '{}.{} -> {}.{}' matched no {} records",
+                join.table,
+                join.column,
+                join.ref_table,
+                join.ref_column,
+                target
+            );
+        } else {
+            info!("Joined '{}' into {} of {} {} records", join.field, matched, total, target);
+        }
+    }
+}
+
+/// Records with the configured joins applied.
+///
+/// Records of tables that take part in no join pass straight through. Those
+/// of joined tables are held in memory until the input is exhausted, since a
+/// related row may appear anywhere in the dump, and are then emitted table by
+/// table, in order of first appearance, with the joins applied in the order
+/// they were given. The output order is therefore fixed for a given input.
+pub struct JoinedRecords {
+    records: RecordIter,
+    joins: Vec<JoinSpec>,
+    buffered: Tables,
+    output: Option<std::vec::IntoIter<Value>>,
+    /// Buffered row count at which to warn next.
+    warn_at: usize,
+}
+
+impl JoinedRecords {
+    fn joined_table(&self, record: &Value) -> Option<String> {
+        let table = record.get("table").and_then(Value::as_str)?;
+        self.joins
+            .iter()
+            .flat_map(JoinSpec::tables)
+            .any(|t| t.eq_ignore_ascii_case(table))
+            .then(|| table.to_string())
+    }
+
+    fn resolve(&mut self) -> Vec<Value> {
+        let mut buffered = std::mem::take(&mut self.buffered);
+        for join in &self.joins {
+            buffered.apply(join);
+        }
+        let nested: HashSet<String> = self.joins
+            .iter()
+            .filter(|j| j.nested)
+            .map(|j| j.table.to_lowercase())
+            .collect();
+        buffered.groups
+            .into_iter()
+            .filter(|(table, _)| !nested.contains(&table.to_lowercase()))
+            .flat_map(|(_, rows)| rows)
+            .collect()
+    }
+}
+
+impl Iterator for JoinedRecords {
+    type Item = io::Result<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(output) = &mut self.output {
+                return output.next().map(Ok);
+            }
+            match self.records.next() {
+                Some(Ok(record)) => {
+                    match self.joined_table(&record) {
+                        Some(table) => {
+                            self.buffered.push(&table, record);
+                            if self.buffered.len >= self.warn_at {
+                                warn!(
+                                    "--join is holding {} rows of joined tables in memory until the input ends",
+                                    self.buffered.len
+                                );
+                                self.warn_at *= 2;
+                            }
+                        }
+                        None => {
+                            return Some(Ok(record));
+                        }
+                    }
+                }
+                Some(Err(e)) => {
+                    return Some(Err(e));
+                }
+                None => {
+                    self.output = Some(self.resolve().into_iter());
+                }
+            }
+        }
+    }
+}
+
+/// Applies `joins` to `records`; without joins the records are returned as is.
+pub fn join_records(records: RecordIter, joins: Vec<JoinSpec>) -> RecordIter {
+    if joins.is_empty() {
+        return records;
+    }
+    Box::new(JoinedRecords {
+        records,
+        joins,
+        buffered: Tables::default(),
+        output: None,
+        warn_at: JOIN_BUFFER_WARN_ROWS,
+    })
+}
+
+/// Prints the foreign keys declared in the dump that no `--join` covers yet.
+pub fn print_join_suggestions(schema: &DumpSchema, joins: &[JoinSpec]) {
+    let suggestions: Vec<String> = schema
+        .foreign_keys()
+        .into_iter()
+        .filter(|key| {
+            !joins.iter().any(|j| {
+                j.table.eq_ignore_ascii_case(&key.table) &&
+                    j.column.eq_ignore_ascii_case(&key.column) &&
+                    j.ref_table.eq_ignore_ascii_case(&key.ref_table)
+            })
+        })
+        .map(|key| key.to_string())
+        .collect();
+    if suggestions.is_empty() {
+        return;
+    }
+    println!("Foreign keys found in the dump; embed related rows together with:");
+    for spec in suggestions {
+        println!("  --join \"{}\"", spec);
+    }
+}
@@ -0,0 +1,502 @@
+use crate::cli::Args;
+use crate::parser::diagnostics::{ report_rejection, Rejection };
+use crate::parser::{ table_name_from_path, ExportParser, ParseContext, RecordIter };
+use log::{ debug, info };
+use serde_json::{ Map, Number, Value };
+use std::collections::VecDeque;
+use std::io::{ self, BufRead };
+
+/// Bytes up to which an unfinished value spanning several lines is checked
+/// for having been cut short, so that a broken JSON line does not swallow
+/// the lines after it.
+const RECHECK_BYTES: usize = 64 * 1024;
+
+enum Source<R: BufRead> {
+    /// Nothing read yet; the first byte decides between the other two.
+    Start(R),
+    /// Elements of a top-level JSON array, read one at a time.
+    Array(R),
+    /// One or more whitespace-separated values (JSON Lines / NDJSON).
+    Values(R),
+    Done,
+}
+
+/// Streams records from a JSON array, a single JSON document or JSON Lines.
+///
+/// Each object becomes one record. The table name comes from `--json-table`,
+/// else the `--json-table-field` field of the object (removed from it), else
+/// the key of a `{"table": [...]}` wrapper that makes up the whole input,
+/// else the file name.
+/// MongoDB Extended JSON values such as `{"$oid": ...}` are flattened.
+/// Malformed array elements and lines are rejected and reading goes on.
+pub struct JsonRecords<R: BufRead> {
+    source: Source<R>,
+    default_table: String,
+    table_override: Option<String>,
+    table_field: String,
+    ctx: ParseContext,
+    pending: VecDeque<Value>,
+    /// Values read but not yet turned into records.
+    values: VecDeque<Value>,
+    /// The value being read is the entire input rather than an array
+    /// element or one of several JSON Lines.
+    whole_document: bool,
+    /// Line the reader is on.
+    line: usize,
+    /// A line read ahead that starts the next value.
+    carry: Vec<u8>,
+    /// Input named in rejection reports.
+    source_name: String,
+}
+
+/// The JSON reader as a registered parser. JSON files are recognized by
+/// their extension or leading `{`/`[` during detection, so `detect` claims
+/// nothing on its own.
+pub struct JsonParser;
+
+impl ExportParser for JsonParser {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn detect(&self, _path: &str, _head: &[u8]) -> u32 {
+        0
+    }
+
+    fn parse(&self, path: &str, reader: Box<dyn BufRead + Send>, args: &Args) -> RecordIter {
+        Box::new(stream_json_export(reader, &table_name_from_path(path), args).with_source(path))
+    }
+}
+
+pub fn stream_json_export<R: BufRead>(reader: R, default_table: &str, args: &Args) -> JsonRecords<R> {
+    info!("Streaming JSON records (default table '{}')", default_table);
+    JsonRecords {
+        source: Source::Start(reader),
+        default_table: default_table.to_string(),
+        table_override: args.json_table.clone().filter(|t| !t.is_empty()),
+        table_field: args.json_table_field.clone(),
+        ctx: ParseContext { guess_json: false, ..ParseContext::new(args) },
+        pending: VecDeque::new(),
+        values: VecDeque::new(),
+        whole_document: false,
+        line: 1,
+        carry: Vec::new(),
+        source_name: String::new(),
+    }
+}
+
+impl<R: BufRead> JsonRecords<R> {
+    /// Names the input in rejection reports, e.g. with its file path.
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source_name = source.to_string();
+        self
+    }
+
+    fn next_value(&mut self) -> Option<io::Result<Value>> {
+        loop {
+            if let Some(value) = self.values.pop_front() {
+                return Some(Ok(value));
+            }
+            match std::mem::replace(&mut self.source, Source::Done) {
+                Source::Start(mut reader) => {
+                    match skip_whitespace(&mut reader, &mut self.line) {
+                        Ok(Some(b'[')) => {
+                            reader.consume(1);
+                            self.source = Source::Array(reader);
+                        }
+                        Ok(Some(_)) => {
+                            let (line, bytes) = match read_value(&mut reader, &mut self.line, &mut self.carry) {
+                                Ok(value) => value?,
+                                Err(e) => {
+                                    return Some(Err(e));
+                                }
+                            };
+                            let parsed = self.queue_values(line, &bytes);
+                            match skip_whitespace(&mut reader, &mut self.line) {
+                                Ok(None) if self.carry.is_empty() => {
+                                    self.whole_document = parsed && self.values.len() == 1;
+                                }
+                                Ok(_) => {
+                                    self.source = Source::Values(reader);
+                                }
+                                Err(e) => {
+                                    return Some(Err(e));
+                                }
+                            }
+                        }
+                        Ok(None) => {
+                            return None;
+                        }
+                        Err(e) => {
+                            return Some(Err(e));
+                        }
+                    }
+                }
+                Source::Array(mut reader) =>
+                    match read_array_element(&mut reader, &mut self.line) {
+                        Ok(Some((line, bytes, last))) => {
+                            if !last {
+                                self.source = Source::Array(reader);
+                            }
+                            self.queue_values(line, &bytes);
+                        }
+                        Ok(None) => {
+                            return None;
+                        }
+                        Err(e) => {
+                            return Some(Err(e));
+                        }
+                    }
+                Source::Values(mut reader) =>
+                    match read_value(&mut reader, &mut self.line, &mut self.carry) {
+                        Ok(Some((line, bytes))) => {
+                            self.source = Source::Values(reader);
+                            self.queue_values(line, &bytes);
+                        }
+                        Ok(None) => {
+                            return None;
+                        }
+                        Err(e) => {
+                            return Some(Err(e));
+                        }
+                    }
+                Source::Done => {
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Queues the values in `bytes`, which start on `line`, and rejects the
+    /// text from the first malformed one on. Returns whether all of it parsed.
+    fn queue_values(&mut self, line: usize, bytes: &[u8]) -> bool {
+        let mut values = serde_json::Deserializer::from_slice(bytes).into_iter::<Value>();
+        loop {
+            let offset = values.byte_offset();
+            match values.next() {
+                Some(Ok(value)) => self.values.push_back(value),
+                Some(Err(e)) => {
+                    let rest = &bytes[offset..];
+                    let skipped = rest.len() - rest.trim_ascii_start().len();
+                    let newlines = count_lines(&bytes[..offset + skipped]);
+                    let rejection = Rejection {
+                        file: self.source_name.clone(),
+                        line: line + newlines,
+                        table: self.table_override.clone().unwrap_or_else(|| self.default_table.clone()),
+                        reason: format!("invalid JSON: {}", e),
+                        raw: String::from_utf8_lossy(rest).trim().to_string(),
+                    };
+                    report_rejection(&rejection);
+                    return false;
+                }
+                None => {
+                    return true;
+                }
+            }
+        }
+    }
+
+    fn push_record(&mut self, value: Value, table: Option<&str>) {
+        let mut obj = match value {
+            Value::Object(obj) => obj,
+            other => {
+                let mut obj = Map::new();
+                obj.insert("value".to_string(), other);
+                obj
+            }
+        };
+        let field_table = match obj.remove(&self.table_field) {
+            Some(Value::String(t)) if !t.is_empty() => Some(t),
+            _ => None,
+        };
+        let table = self.table_override
+            .clone()
+            .or(field_table)
+            .or_else(|| table.map(str::to_string))
+            .unwrap_or_else(|| self.default_table.clone());
+        if self.ctx.ignore_table(&table) {
+            return;
+        }
+
+        if obj.contains_key("_id") && self.ctx.key_columns(&table).is_none() {
+            self.ctx.primary_keys.insert(table.clone(), vec!["_id".to_string()]);
+        }
+        obj.insert("table".to_string(), Value::String(table));
+
+        let mut record = Value::Object(obj);
+        normalize_extended_json(&mut record);
+        self.ctx.finish_record(&mut record);
+        self.pending.push_back(record);
+    }
+}
+
+impl<R: BufRead> Iterator for JsonRecords<R> {
+    type Item = io::Result<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.pending.pop_front() {
+                return Some(Ok(record));
+            }
+            let value = match self.next_value()? {
+                Ok(value) => value,
+                Err(e) => {
+                    return Some(Err(e));
+                }
+            };
+
+            match value {
+                // `{"users": [{...}], "orders": [{...}]}`: one table per key.
+                Value::Object(obj) if self.whole_document && is_table_wrapper(&obj) => {
+                    for (table, rows) in obj {
+                        debug!("Reading JSON table '{}'", table);
+                        if let Value::Array(rows) = rows {
+                            for row in rows {
+                                self.push_record(row, Some(&table));
+                            }
+                        }
+                    }
+                }
+                value => self.push_record(value, None),
+            }
+        }
+    }
+}
+
+fn is_table_wrapper(obj: &Map<String, Value>) -> bool {
+    !obj.is_empty() &&
+        obj.values().all(|v| {
+            v.as_array().is_some_and(|rows| !rows.is_empty() && rows.iter().all(Value::is_object))
+        })
+}
+
+/// Returns the next non-whitespace byte without consuming it, counting the
+/// lines skipped in `line`.
+fn skip_whitespace<R: BufRead>(reader: &mut R, line: &mut usize) -> io::Result<Option<u8>> {
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(None);
+        }
+        let end = buf.iter().position(|b| !b.is_ascii_whitespace());
+        *line += count_lines(&buf[..end.unwrap_or(buf.len())]);
+        match end {
+            Some(i) => {
+                let byte = buf[i];
+                reader.consume(i);
+                return Ok(Some(byte));
+            }
+            None => {
+                let len = buf.len();
+                reader.consume(len);
+            }
+        }
+    }
+}
+
+/// Tracks the brackets and strings open while JSON text is scanned.
+#[derive(Default)]
+struct Nesting {
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl Nesting {
+    /// Accounts for byte `b`. Returns whether `b` lies outside any string
+    /// and bracket pair, like the `,` between two array elements.
+    fn push(&mut self, b: u8) -> bool {
+        if self.in_string {
+            match b {
+                _ if self.escaped => {
+                    self.escaped = false;
+                }
+                b'\\' => {
+                    self.escaped = true;
+                }
+                // Strings cannot span lines; a line break ends a broken one.
+                b'"' | b'\n' => {
+                    self.in_string = false;
+                }
+                _ => {}
+            }
+            return false;
+        }
+        match b {
+            b'"' => {
+                self.in_string = true;
+                false
+            }
+            b'{' | b'[' => {
+                self.depth += 1;
+                false
+            }
+            b'}' | b']' if self.depth > 0 => {
+                self.depth -= 1;
+                false
+            }
+            _ => self.depth == 0,
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.depth == 0 && !self.in_string
+    }
+}
+
+fn count_lines(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .filter(|&&b| b == b'\n')
+        .count()
+}
+
+/// Reads the bytes of the next value of JSON Lines or concatenated JSON, with
+/// the line it starts on. A value ends with the first line that closes all
+/// its brackets. A complete value on the next line that cannot continue an
+/// unfinished one ends it as well, and is left in `carry` for the next call.
+fn read_value<R: BufRead>(
+    reader: &mut R,
+    line: &mut usize,
+    carry: &mut Vec<u8>
+) -> io::Result<Option<(usize, Vec<u8>)>> {
+    let mut value = std::mem::take(carry);
+    if value.is_empty() && skip_whitespace(reader, line)?.is_none() {
+        return Ok(None);
+    }
+    let start = *line - count_lines(&value);
+    let mut nesting = Nesting::default();
+    for &b in &value {
+        nesting.push(b);
+    }
+    let mut next = Vec::new();
+    while value.is_empty() || !nesting.is_closed() {
+        next.clear();
+        if reader.read_until(b'\n', &mut next)? == 0 {
+            break;
+        }
+        *line += count_lines(&next);
+        if !value.is_empty() && value.len() <= RECHECK_BYTES && starts_new_value(&value, &next) {
+            *carry = next;
+            break;
+        }
+        for &b in &next {
+            nesting.push(b);
+        }
+        value.extend_from_slice(&next);
+    }
+    Ok(Some((start, value)))
+}
+
+/// Whether `next` is a complete value that cannot continue the unfinished
+/// `value`, as after a JSON line that was cut short.
+fn starts_new_value(value: &[u8], next: &[u8]) -> bool {
+    serde_json::from_slice::<Value>(next).is_ok() &&
+        serde_json::from_slice::<Value>(&[value, next].concat()).is_err_and(|e| e.is_syntax())
+}
+
+/// Reads the bytes of the next array element, after the opening `[` or a
+/// `,`, with the line it starts on. Returns `None` at the closing `]`, and
+/// `true` alongside the element when it was the last one.
+fn read_array_element<R: BufRead>(reader: &mut R, line: &mut usize) -> io::Result<Option<(usize, Vec<u8>, bool)>> {
+    if skip_whitespace(reader, line)? == Some(b']') {
+        reader.consume(1);
+        return Ok(None);
+    }
+
+    let start = *line;
+    let mut element = Vec::new();
+    let mut nesting = Nesting::default();
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Unterminated JSON array"));
+        }
+        for (i, &b) in buf.iter().enumerate() {
+            if nesting.push(b) && (b == b',' || b == b']') {
+                element.extend_from_slice(&buf[..i]);
+                reader.consume(i + 1);
+                *line += count_lines(&element);
+                return Ok(Some((start, element, b == b']')));
+            }
+        }
+        element.extend_from_slice(buf);
+        let len = buf.len();
+        reader.consume(len);
+    }
+}
+
+/// Replaces MongoDB Extended JSON wrappers (`$oid`, `$date`, `$numberLong`,
+/// ...) with plain values so they embed and index like ordinary fields.
+pub fn normalize_extended_json(value: &mut Value) {
+    match value {
+        Value::Array(items) => items.iter_mut().for_each(normalize_extended_json),
+        Value::Object(obj) => {
+            if obj.len() == 1 && let Some(plain) = extended_json_value(obj) {
+                *value = plain;
+                return;
+            }
+            obj.values_mut().for_each(normalize_extended_json);
+        }
+        _ => {}
+    }
+}
+
+fn extended_json_value(obj: &Map<String, Value>) -> Option<Value> {
+    let (key, inner) = obj.iter().next()?;
+    match (key.as_str(), inner) {
+        ("$oid" | "$uuid" | "$symbol", Value::String(s)) => Some(Value::String(s.clone())),
+        ("$numberInt" | "$numberLong", Value::String(s)) =>
+            s.parse::<i64>().ok().map(|n| Value::Number(n.into())),
+        ("$numberDouble" | "$numberDecimal", Value::String(s)) =>
+            Some(
+                s
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(Number::from_f64)
+                    .map(Value::Number)
+                    .unwrap_or_else(|| Value::String(s.clone()))
+            ),
+        ("$date", Value::String(s)) => Some(Value::String(s.clone())),
+        ("$date", Value::Number(n)) => n.as_i64().map(|ms| Value::String(iso_from_millis(ms))),
+        ("$date", Value::Object(inner)) =>
+            match inner.get("$numberLong") {
+                Some(Value::String(s)) => s.parse().ok().map(|ms| Value::String(iso_from_millis(ms))),
+                _ => None,
+            }
+        ("$timestamp", Value::Object(inner)) => inner.get("t").cloned(),
+        // Binary payloads carry no text worth embedding.
+        ("$binary", _) => Some(Value::Null),
+        _ => None,
+    }
+}
+
+/// Formats milliseconds since the Unix epoch as an RFC 3339 UTC timestamp.
+fn iso_from_millis(ms: i64) -> String {
+    let secs = ms.div_euclid(1000);
+    let millis = ms.rem_euclid(1000);
+    let days = secs.div_euclid(86_400);
+    let rem = secs.rem_euclid(86_400);
+
+    // Civil-from-days, after Howard Hinnant's date algorithms.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + ((month <= 2) as i64);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60,
+        millis
+    )
+}
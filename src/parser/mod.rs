@@ -1,185 +1,397 @@
 use crate::cli::Args;
+use crate::util::exclude::{ Excluder, EXCLUDE_FILE };
+use crate::parser::parse_regex::clean_html_in_value;
+use crate::util::{
+    open_and_detect_format,
+    open_decompressed,
+    resolve_dump_files,
+    Compression,
+};
 
-use log::{ info, warn, debug };
-use parse_regex::mssql::parse_mssql;
-use parse_regex::mysql::parse_mysql;
-use parse_regex::oracle::parse_oracle;
-use parse_regex::postgres::parse_postgres;
-use parse_regex::sqlite::parse_sqlite;
-use parse_regex::surreal::parse_surreal;
+use log::{ debug, info };
 use serde_json::Value;
-use std::error::Error;
+use std::collections::{ BTreeMap, HashMap };
+use std::hash::{ Hash, Hasher };
+use std::io::Read;
+use std::sync::{ Arc, Mutex };
 
+pub mod csv_file;
+pub mod detect;
+pub mod diagnostics;
+pub mod join;
+pub mod json_file;
 pub mod parse_regex;
-pub trait ExportParser {
-    fn parse(&self, content: &str) -> Result<Vec<Value>, Box<dyn Error>>;
-}
+pub mod registry;
+pub mod schema;
+pub mod sqlite_file;
+pub mod stream;
+pub use csv_file::{ stream_csv_export, CsvParser, CsvRecords };
+pub use diagnostics::{ open_reject_file, rejected_counts, Rejection };
+pub use detect::{
+    detect_format,
+    detect_format_checked,
+    formats,
+    score_formats,
+    validate_format,
+    FormatScore,
+};
+pub use join::{ join_records, parse_joins, print_join_suggestions, JoinSpec };
+pub use json_file::{ stream_json_export, JsonParser, JsonRecords };
+pub use registry::{
+    find_parser,
+    register_parser,
+    registered_parsers,
+    ExportParser,
+    SqlDialect,
+};
+pub use sqlite_file::{ is_sqlite_database, stream_sqlite_database, SqliteRecords };
+pub use stream::{ stream_database_export, RecordStream, StatementReader };
 
-pub fn parse_database_export(
-    content: &str,
-    format: &str,
-    args: &Args
-) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
-    let mut all_records = Vec::new();
-    
-    let excluder = if args.use_exclude {
-        Some(crate::util::exclude::Excluder::load("config/exclude.json"))
-    } else {
-        None
-    };
+/// Records streamed from any supported input, in input order.
+pub type RecordIter = Box<dyn Iterator<Item = std::io::Result<Value>> + Send>;
 
-    let chunks: Vec<String> = match format {
-        "mssql" | "postgres" | "mysql" | "surreal" | "sqlite" => {
-            info!("Processing {} file without chunking", format);
-            vec![content.to_string()]
+/// State carried between statements while a dump is being streamed.
+pub struct ParseContext {
+    pub excluder: Option<Excluder>,
+    /// Column names per table, taken from CREATE TABLE statements.
+    pub table_columns: HashMap<String, Vec<String>>,
+    /// Table named by the most recent section marker (SurrealDB exports).
+    pub current_table: Option<String>,
+    /// Primary key columns per table, taken from DDL statements.
+    pub primary_keys: HashMap<String, Vec<String>>,
+    /// Primary key columns configured per table; these win over the DDL.
+    pub key_overrides: HashMap<String, Vec<String>>,
+    /// Declared column types per table, taken from CREATE TABLE statements.
+    pub column_types: HashMap<String, schema::TableSchema>,
+    /// Rows rejected by the parser, waiting to be located and reported by the stream.
+    pub rejections: Vec<Rejection>,
+    /// Turn text holding a JSON array or object into that value for columns
+    /// without a declared type. Off for inputs whose values are already typed.
+    pub guess_json: bool,
+    /// Declared column types and foreign keys shared with the rest of the run.
+    pub schema: Arc<schema::DumpSchema>,
+}
+
+impl ParseContext {
+    pub fn new(args: &Args) -> Self {
+        let excluder = if args.use_exclude {
+            Some(Excluder::load(EXCLUDE_FILE))
+        } else {
+            None
+        };
+        ParseContext {
+            excluder,
+            table_columns: HashMap::new(),
+            current_table: None,
+            primary_keys: HashMap::new(),
+            key_overrides: schema::load_primary_key_config(&args.primary_keys_file),
+            column_types: HashMap::new(),
+            rejections: Vec::new(),
+            guess_json: true,
+            schema: Arc::default(),
         }
-        "oracle" => {
-            content
-                .split("Insert into")
-                .filter(|s| !s.trim().is_empty())
-                .enumerate()
-                .map(|(i, s)| {
-                    if i > 0 { format!("Insert into{}", s) } else { s.to_string() }
-                })
-                .collect()
+    }
+
+    /// Key columns for `table`: configured first, then declared in the dump.
+    pub fn key_columns(&self, table: &str) -> Option<&[String]> {
+        lookup_table(&self.key_overrides, table).or_else(|| lookup_table(&self.primary_keys, table))
+    }
+
+    pub fn ignore_table(&self, table: &str) -> bool {
+        self.excluder.as_ref().is_some_and(|excl| excl.ignore_table(table))
+    }
+
+    /// Records a row of `table` that could not be parsed, keeping its source text.
+    pub fn reject(&mut self, table: &str, reason: String, raw: &str) {
+        self.rejections.push(Rejection {
+            file: String::new(),
+            line: 0,
+            table: table.to_string(),
+            reason,
+            raw: raw.trim().to_string(),
+        });
+    }
+
+    /// Records the declared column types of `table` for value coercion and
+    /// makes them available to sinks.
+    pub fn register_column_types(&mut self, table: &str, columns: schema::TableSchema) {
+        debug!("Column types for table '{}': {:?}", table, columns);
+        self.schema.register_table(table, columns.clone());
+        self.column_types.insert(table.to_string(), columns);
+    }
+
+    /// Coerces values to their declared column types, strips HTML, sets
+    /// `source_id` and applies field exclusions to a parsed record.
+    pub fn finish_record(&self, record: &mut Value) {
+        let table = record.get("table").and_then(Value::as_str).unwrap_or_default().to_string();
+        let columns = lookup_table(&self.column_types, &table).unwrap_or_default();
+        schema::coerce_record(record, columns, self.guess_json);
+        clean_html_in_value(record);
+        schema::assign_source_id(record, self.key_columns(&table));
+        if let Some(ref excl) = self.excluder {
+            excl.filter_record(record);
         }
-        _ => {
-            warn!("Using default (single chunk) processing for unknown format: {}", format);
-            vec![content.to_string()]
+    }
+}
+
+fn lookup_table<'a, T>(map: &'a HashMap<String, Vec<T>>, table: &str) -> Option<&'a [T]> {
+    map.get(table)
+        .or_else(|| {
+            map.iter()
+                .find(|(t, _)| t.eq_ignore_ascii_case(table))
+                .map(|(_, cols)| cols)
+        })
+        .map(Vec::as_slice)
+}
+
+/// Opens `path` and streams its records, reading SQLite database files
+/// directly and detecting the format of text dumps. Declared column types and
+/// foreign keys are added to `schema`. Returns the format name.
+pub fn open_export(
+    path: &str,
+    args: &Args,
+    schema: &Arc<schema::DumpSchema>
+) -> std::io::Result<(RecordIter, String)> {
+    let format_override = args.format
+        .as_deref()
+        .map(validate_format)
+        .transpose()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let native_sqlite = match format_override.as_deref() {
+        Some(format) => format == "sqlite-db",
+        None => is_sqlite_database(path)?,
+    };
+    if native_sqlite {
+        info!("Detected SQLite database file: {}", path);
+        return Ok((Box::new(stream_sqlite_database(path, args, schema.clone())?), "sqlite-db".to_string()));
+    }
+    if Compression::detect(path)? != Compression::None {
+        let mut header = Vec::new();
+        open_decompressed(path)?.take(16).read_to_end(&mut header)?;
+        if header.starts_with(b"SQLite format 3\0") {
+            return Err(
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("'{}' is a compressed SQLite database; decompress it before migrating", path)
+                )
+            );
         }
+    }
+    let (reader, format) = open_and_detect_format(path, format_override.as_deref())?;
+    let Some(parser) = find_parser(&format) else {
+        return Err(
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("No parser registered for format '{}'", format)
+            )
+        );
     };
+    Ok((parser.parse_with_schema(path, reader, args, schema.clone()), format))
+}
 
-    info!("Found {} chunks to process for format '{}'", chunks.len(), format);
+/// Records read from each input file and for each table.
+#[derive(Debug, Default)]
+pub struct InputSummary {
+    /// Path, detected format and record count of each file, in read order.
+    pub files: Vec<(String, String, usize)>,
+    pub tables: BTreeMap<String, usize>,
+}
 
-    for (i, chunk) in chunks.iter().enumerate() {
-        if chunk.trim().is_empty() {
-            debug!("Skipping empty chunk {}", i);
-            continue;
+impl InputSummary {
+    fn count(&mut self, record: &Value) {
+        if let Some((_, _, n)) = self.files.last_mut() {
+            *n += 1;
         }
+        let table = record.get("table").and_then(Value::as_str).unwrap_or("unknown");
+        *self.tables.entry(table.to_string()).or_default() += 1;
+    }
 
-        match parse_with_regex(&chunk, format, args) {
-            Some(mut records) => {
-                if !records.is_empty() {
-                    if let Some(ref excl) = excluder {
-                        debug!("Filtering fields for {} records from chunk {}", records.len(), i);
-                        for record in &mut records {
-                            excl.filter_record(record);
-                        }
-                    }
-                    
-                    info!("Parsed {} records from chunk {}", records.len(), i);
-                    if args.debug {
-                        for (j, rec) in records.iter().enumerate() {
-                            debug!("Debug: Record {} in chunk {}: {}", j, i, rec);
-                        }
-                    }
-                    all_records.extend(records);
-                } else {
-                    debug!("Regex parsing yielded 0 records for chunk {}", i);
-                }
-            }
-            None => {
-           
-                if args.debug && chunk.len() < 1000 {
-                    debug!("Content of failed chunk {}:\n{}", i, chunk);
-                } else if args.debug {
-                    debug!(
-                        "Content of failed chunk {} (truncated):\n{}...",
-                        i,
-                        &chunk[..std::cmp::min(chunk.len(), 1000)]
-                    );
-                }
+    pub fn print(&self) {
+        if self.files.len() > 1 {
+            println!("Records per file:");
+            for (path, format, n) in &self.files {
+                println!("  {:>8}  {} ({})", n, path, format);
             }
         }
+        let rejected = rejected_counts();
+        let mut tables: Vec<&String> = self.tables.keys().chain(rejected.keys()).collect();
+        tables.sort();
+        tables.dedup();
+        println!("Records per table:");
+        println!("  {:>8}  {:>8}  table", "parsed", "rejected");
+        for table in tables {
+            println!(
+                "  {:>8}  {:>8}  {}",
+                self.tables.get(table).copied().unwrap_or(0),
+                rejected.get(table).copied().unwrap_or(0),
+                table
+            );
+        }
+        let total_rejected: usize = rejected.values().sum();
+        if total_rejected > 0 {
+            println!(
+                "Rejected {} rows ({:.2}% of the input); see the log or --reject-file for details",
+                total_rejected,
+                self.reject_ratio() * 100.0
+            );
+        }
+    }
+
+    /// Share of input rows that were rejected rather than parsed.
+    pub fn reject_ratio(&self) -> f64 {
+        let (parsed, rejected) = self.row_counts();
+        if rejected == 0 { 0.0 } else { (rejected as f64) / ((parsed + rejected) as f64) }
+    }
+
+    /// Input rows parsed and rejected so far.
+    fn row_counts(&self) -> (usize, usize) {
+        (self.tables.values().sum(), rejected_counts().values().sum())
     }
+}
+
+/// Input rows read before `--max-reject-ratio` is enforced mid-run, so that a
+/// rejected row near the start of the dump does not end the run on its own.
+const REJECT_RATIO_MIN_ROWS: usize = 1000;
 
-    info!("Total records parsed: {}", all_records.len());
-    Ok(all_records)
+/// Ends `records` with an error once the share of rejected input rows passes
+/// `max`: while reading after `REJECT_RATIO_MIN_ROWS` rows, and at the end of
+/// the input. The records of the batch being read are then not stored.
+fn limit_reject_ratio(records: RecordIter, summary: Arc<Mutex<InputSummary>>, max: f64) -> RecordIter {
+    let mut records = records.fuse();
+    let mut exceeded = false;
+    Box::new(
+        std::iter::from_fn(move || {
+            if exceeded {
+                return None;
+            }
+            let next = records.next();
+            let summary = summary.lock().unwrap();
+            let (parsed, rejected) = summary.row_counts();
+            let ratio = summary.reject_ratio();
+            if ratio > max && (next.is_none() || parsed + rejected >= REJECT_RATIO_MIN_ROWS) {
+                exceeded = true;
+                return Some(
+                    Err(
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            format!(
+                                "{:.2}% of the input rows were rejected, more than --max-reject-ratio {}",
+                                ratio * 100.0,
+                                max
+                            )
+                        )
+                    )
+                );
+            }
+            next
+        })
+    )
 }
 
-pub fn detect_format(file_path: &str, content: &str) -> String {
-    let _content_lower = content.to_lowercase();
-
-    if file_path.ends_with(".surql") {
-        return "surreal".to_string();
-    }
-
-    // Oracle distinctive patterns
-    if
-        content.contains("REM INSERTING into") ||
-        content.contains("SET DEFINE OFF;") ||
-        content.contains("Insert into ") ||
-        (content.contains("CREATE TABLE \"") &&
-            content.contains("PCTFREE") &&
-            content.contains("TABLESPACE")) ||
-        content.contains("BUFFER_POOL DEFAULT FLASH_CACHE DEFAULT CELL_FLASH_CACHE DEFAULT") ||
-        content.contains("USING INDEX PCTFREE") ||
-        content.contains("ALTER SESSION SET EVENTS") ||
-        content.contains("DBMS_LOGREP_IMP")
-    {
-        return "oracle".to_string();
-    }
-
-    // PostgreSQL distinctive patterns
-    if
-        (content.contains("COPY ") && content.contains(" FROM stdin;")) ||
-        content.contains("PostgreSQL database dump") ||
-        (content.contains("SET ") && content.contains("standard_conforming_strings")) ||
-        content.contains("ALTER TABLE ONLY") ||
-        (content.contains("CREATE TYPE") && content.contains("AS ENUM")) ||
-        (content.contains("CREATE SEQUENCE") && content.contains("OWNED BY"))
-    {
-        return "postgres".to_string();
-    }
-
-    // SQLite distinctive patterns
-    if
-        content.starts_with("PRAGMA foreign_keys=OFF;") ||
-        (content.contains("BEGIN TRANSACTION;") &&
-            content.contains("COMMIT;") &&
-            content.contains("CREATE TABLE") &&
-            !content.contains("ENGINE=InnoDB") &&
-            !content.contains("TABLESPACE") &&
-            content.contains("INSERT INTO ")) ||
-        content.contains("sqlite_sequence")
-    {
-        return "sqlite".to_string();
-    }
-
-    // MSSQL distinctive patterns
-    if
-        content.contains("SET ANSI_NULLS ON") ||
-        content.contains("SET QUOTED_IDENTIFIER ON") ||
-        content.contains("CREATE TABLE [dbo].") ||
-        content.contains("INSERT [dbo].") ||
-        content.contains("WITH (PAD_INDEX = OFF") ||
-        content.contains("GO")
-    {
-        return "mssql".to_string();
-    }
-
-    // MySQL distinctive patterns
-    if
-        content.contains("ENGINE=InnoDB") ||
-        content.contains("LOCK TABLES") ||
-        content.contains("/*!40") ||
-        content.contains("AUTO_INCREMENT") ||
-        content.contains("COLLATE=utf8mb4")
-    {
-        return "mysql".to_string();
-    }
-
-    "json".to_string()
+/// Gives a record without a primary key that repeats an earlier one field for
+/// field a `source_occurrence` of 2, 3, ..., so that its vector ID, which is
+/// derived from its contents, differs. `seen` counts the records per content
+/// hash across all input files.
+fn number_duplicate(record: &mut Value, seen: &mut HashMap<u64, usize>) {
+    if record.get("source_id").is_some() {
+        return;
+    }
+    let mut hasher = std::hash::DefaultHasher::new();
+    record.to_string().hash(&mut hasher);
+    let occurrence = seen.entry(hasher.finish()).or_default();
+    *occurrence += 1;
+    if *occurrence > 1 && let Some(obj) = record.as_object_mut() {
+        obj.insert("source_occurrence".to_string(), (*occurrence).into());
+    }
 }
 
-pub fn parse_with_regex(chunk: &str, format: &str, args: &Args) -> Option<Vec<Value>> {
-    match format {
-        "surreal" => parse_surreal(chunk, args),
-        "mysql" => parse_mysql(chunk, args),
-        "postgres" => parse_postgres(chunk, args),
-        "oracle" => parse_oracle(chunk, args),
-        "sqlite" => parse_sqlite(chunk, args),
-        "mssql" => parse_mssql(chunk, args),
-        _ => None,
+/// Streams every file named by `--dump-file` (a file, directory or glob) one
+/// after another, detecting the format of each. Records of the same table in
+/// different files end up in the same collection. Repeated records without a
+/// primary key are numbered by `number_duplicate`. The schemas declared in
+/// all files go into `schema`. The returned summary fills in as records are
+/// read.
+pub fn open_dump_files(
+    spec: &str,
+    args: &Args,
+    schema: &Arc<schema::DumpSchema>
+) -> std::io::Result<(RecordIter, Arc<Mutex<InputSummary>>)> {
+    let files = resolve_dump_files(spec)?;
+    if files.len() > 1 {
+        info!("Reading {} dump files from {}", files.len(), spec);
     }
+    let summary = Arc::new(Mutex::new(InputSummary::default()));
+
+    // A single file is opened up front so that errors surface before the
+    // migration starts; later files of a set are opened as they are reached.
+    let mut first = None;
+    if let [file] = files.as_slice() {
+        let path = file.to_string_lossy().to_string();
+        let (records, format) = open_export(&path, args, schema)?;
+        first = Some((path, format, records));
+    }
+
+    let paths: Vec<String> = if first.is_some() {
+        Vec::new()
+    } else {
+        files
+            .iter()
+            .map(|f| f.to_string_lossy().to_string())
+            .collect()
+    };
+    let max_reject_ratio = args.max_reject_ratio;
+    let args = args.clone();
+    let schema = schema.clone();
+    let opened = first
+        .map(Ok)
+        .into_iter()
+        .chain(
+            paths.into_iter().map(move |path| {
+                match open_export(&path, &args, &schema) {
+                    Ok((records, format)) => Ok((path, format, records)),
+                    Err(e) => Err(std::io::Error::new(e.kind(), format!("{}: {}", path, e))),
+                }
+            })
+        );
+
+    let counter = summary.clone();
+    let seen = Arc::new(Mutex::new(HashMap::new()));
+    let records = opened.flat_map(move |opened| -> RecordIter {
+        match opened {
+            Ok((path, format, records)) => {
+                info!("Reading {} ({})", path, format);
+                counter.lock().unwrap().files.push((path, format, 0));
+                let counter = counter.clone();
+                let seen = seen.clone();
+                Box::new(
+                    records.map(move |record| {
+                        let mut record = record?;
+                        number_duplicate(&mut record, &mut seen.lock().unwrap());
+                        counter.lock().unwrap().count(&record);
+                        Ok(record)
+                    })
+                )
+            }
+            Err(e) => Box::new(std::iter::once(Err(e))),
+        }
+    });
+    let records: RecordIter = match max_reject_ratio {
+        Some(max) => limit_reject_ratio(Box::new(records), summary.clone(), max),
+        None => Box::new(records),
+    };
+    Ok((records, summary))
+}
+
+/// File name without directories or extensions, e.g. `users` for `exports/users.jsonl`.
+pub fn table_name_from_path(path: &str) -> String {
+    let name = std::path::Path::new(path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(path);
+    name.split('.').next().filter(|n| !n.is_empty()).unwrap_or(name).to_string()
+}
+
+/// Parses one statement with the statement parser of the built-in SQL dialect `format`.
+pub fn parse_with_regex(chunk: &str, format: &str, ctx: &mut ParseContext) -> Option<Vec<Value>> {
+    (registry::sql_dialect(format)?.parse_statement)(chunk, ctx)
 }
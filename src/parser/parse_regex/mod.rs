@@ -6,20 +6,32 @@ pub mod sqlite;
 pub mod mssql;
 use serde_json::Value;
 
+/// Text holding a JSON array or object, as JSON columns are dumped, becomes
+/// that JSON value; any other text stays a string.
+pub fn json_text(text: String) -> Value {
+    let trimmed = text.trim();
+    if
+        ((trimmed.starts_with('[') && trimmed.ends_with(']')) ||
+            (trimmed.starts_with('{') && trimmed.ends_with('}'))) &&
+        let Ok(json) = serde_json::from_str::<Value>(trimmed)
+    {
+        return json;
+    }
+    Value::String(text)
+}
+
 pub fn clean_html_in_value(val: &mut Value) {
     match val {
-        Value::String(s) => {
-            if s.contains('<') && s.contains('>') {
-                *s = html2text
-                    ::from_read(s.as_bytes(), usize::MAX)
-                    .unwrap_or_else(|_| s.clone())
-                    .replace('\n', " ")
-                    .split_whitespace()
-                    .collect::<Vec<_>>()
-                    .join(" ")
-                    .trim()
-                    .to_string();
-            }
+        Value::String(s) if s.contains('<') && s.contains('>') => {
+            *s = html2text
+                ::from_read(s.as_bytes(), usize::MAX)
+                .unwrap_or_else(|_| s.clone())
+                .replace('\n', " ")
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .trim()
+                .to_string();
         }
         Value::Array(arr) => {
             for v in arr {
@@ -73,11 +85,10 @@ pub fn parse_array(array_str: &str) -> Option<Value> {
     }
     let mut elements = Vec::new();
     let mut current_element = String::new();
-    let mut chars = content.chars().peekable();
     let mut in_quotes = false;
     let mut escape_next = false;
 
-    while let Some(c) = chars.next() {
+    for c in content.chars() {
         if escape_next {
             current_element.push(c);
             escape_next = false;
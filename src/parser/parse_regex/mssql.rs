@@ -1,104 +1,312 @@
+use base64::{ engine::general_purpose::STANDARD, Engine as _ };
 use log::{ debug, info };
+use once_cell::sync::Lazy;
 use regex::Regex;
-use serde_json::Value;
-use crate::parser::parse_regex::clean_html_in_value;
-use crate::cli::Args;
-use crate::util::exclude::Excluder;
+use serde_json::{ Map, Number, Value };
+use std::ops::Range;
+use crate::parser::schema::{ coerce_value, column_kind, decode_hex };
+use crate::parser::ParseContext;
 
-pub fn parse_mssql(chunk: &str, args: &Args) -> Option<Vec<Value>> {
-    info!("Using parse method: MSSQL");
-    let mut records = Vec::new();
-    
-    let excluder = if args.use_exclude {
-        Some(Excluder::load("config/exclude.json"))
-    } else {
-        None
-    };
+/// One part of a possibly qualified name: `[Order Items]`, `"Orders"` or `Orders`.
+const NAME: &str = r#"(?:\[(?:[^\]]|\]\])+\]|"[^"]+"|[\w@$#]+)"#;
+
+/// `INSERT [INTO] name [(columns)] VALUES (` up to the first row's parenthesis;
+/// the rows are read by `parse_value_rows`.
+static INSERT_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        &format!(r"(?is)\bINSERT\s+(?:INTO\s+)?({0}(?:\s*\.\s*{0}){{0,3}})\s*(?:\(([^)]*)\))?\s*VALUES\s*\(", NAME)
+    ).unwrap()
+});
+/// `CAST(`, `TRY_CAST(`, `CONVERT(` and `TRY_CONVERT(`.
+static CONVERSION_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)^(?:TRY_)?(CAST|CONVERT)\s*\(").unwrap()
+});
+
+static CAST_AS_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)\s+AS\s").unwrap());
+
+/// Strips `[...]` or `"..."` from an identifier.
+fn unquote(ident: &str) -> String {
+    let ident = ident.trim();
+    if let Some(inner) = ident.strip_prefix('[').and_then(|i| i.strip_suffix(']')) {
+        return inner.replace("]]", "]");
+    }
+    ident.trim_matches('"').to_string()
+}
+
+/// Record table name for a qualified name. Tables in `dbo` keep their bare
+/// name; other schemas become a prefix, so `[sales].[Orders]` is `sales_Orders`.
+/// A leading database name is ignored.
+pub(crate) fn table_name(qualified: &str) -> String {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in qualified.char_indices() {
+        match (quote, c) {
+            (None, '[') => {
+                quote = Some(']');
+            }
+            (None, '"') => {
+                quote = Some('"');
+            }
+            (Some(close), c) if c == close => {
+                quote = None;
+            }
+            (None, '.') => {
+                parts.push(unquote(&qualified[start..i]));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(unquote(&qualified[start..]));
+
+    match parts.as_slice() {
+        [.., schema, table] if !schema.is_empty() && !is_default_schema(schema) =>
+            format!("{}_{}", schema, table),
+        [.., table] => table.clone(),
+        [] => String::new(),
+    }
+}
+
+fn is_default_schema(schema: &str) -> bool {
+    schema.eq_ignore_ascii_case("dbo") || schema.eq_ignore_ascii_case("DB_OWNER")
+}
 
-    let insert_re = Regex::new(
-        r"(?is)INSERT\s+\[(?:dbo|DB_OWNER)\]\.\[(\w+)\]\s*(?:\((.*?)\))?\s*VALUES\s*"
-    ).ok()?;
-    let values_re = Regex::new(r"\(((?:[^()]*|\((?:[^()]*|\([^()]*\))*\))*)\)").ok()?;
-
-    for cap in insert_re.captures_iter(chunk) {
-        let table = cap.get(1)?.as_str();
-        
-        if let Some(ref excl) = excluder {
-            if excl.ignore_table(table) {
-                info!("Skipping excluded MSSQL table: {}", table);
+fn skip_whitespace(bytes: &[u8], i: &mut usize) {
+    while bytes.get(*i).is_some_and(|b| b.is_ascii_whitespace()) {
+        *i += 1;
+    }
+}
+
+/// Byte offset just past the `'...'` string opening at `start`. T-SQL has no
+/// backslash escapes; quotes are doubled.
+fn quoted_end(bytes: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        if bytes[i] == b'\'' {
+            if bytes.get(i + 1) == Some(&b'\'') {
+                i += 2;
                 continue;
             }
+            return i + 1;
         }
+        i += 1;
+    }
+    bytes.len()
+}
 
-        info!("Processing INSERT for MSSQL table: {}", table);
-
-        let column_names: Vec<String> = if let Some(cols_match) = cap.get(2) {
-            cols_match
-                .as_str()
-                .split(',')
-                .map(|c|
-                    c
-                        .trim()
-                        .trim_matches(&['[', ']'][..])
-                        .to_string()
-                )
-                .collect()
+/// Byte offset of the `,` or `)` ending the expression at `*i`, skipping
+/// nested parentheses and strings.
+fn expression_end(bytes: &[u8], mut i: usize) -> usize {
+    let mut depth = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\'' => {
+                i = quoted_end(bytes, i);
+                continue;
+            }
+            b'(' => {
+                depth += 1;
+            }
+            b')' if depth == 0 => {
+                break;
+            }
+            b')' => {
+                depth -= 1;
+            }
+            b',' if depth == 0 => {
+                break;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    i
+}
+
+fn word_value(word: &str) -> Value {
+    if word.eq_ignore_ascii_case("NULL") {
+        return Value::Null;
+    }
+    if let Ok(i) = word.parse::<i64>() {
+        return Value::Number(i.into());
+    }
+    if let Ok(f) = word.parse::<f64>() && let Some(n) = Number::from_f64(f) {
+        return Value::Number(n);
+    }
+    Value::String(word.to_string())
+}
+
+/// Converts the operand of a `CAST`/`CONVERT` to the target SQL type, e.g.
+/// `CAST(N'2021-01-01 00:00:00' AS DateTime)` to an ISO timestamp.
+fn converted(mut value: Value, sql_type: &str) -> Value {
+    coerce_value(&mut value, column_kind(sql_type));
+    value
+}
+
+/// Reads `CAST(expr AS type)` or `CONVERT(type, expr[, style])` after the
+/// opening parenthesis at `*i`, leaving `*i` past the closing one.
+fn read_conversion(text: &str, i: &mut usize, convert: bool) -> Value {
+    let bytes = text.as_bytes();
+    skip_whitespace(bytes, i);
+    let value = if convert {
+        let type_end = expression_end(bytes, *i);
+        let sql_type = text[*i..type_end].trim().to_string();
+        *i = (type_end + 1).min(bytes.len());
+        skip_whitespace(bytes, i);
+        let value = read_literal(text, i);
+        converted(value, &sql_type)
+    } else {
+        let rest = &text[*i..];
+        let quoted = rest.starts_with('\'') || rest.starts_with("N'") || rest.starts_with("n'");
+        let value = if quoted || CONVERSION_RE.is_match(rest) {
+            read_literal(text, i)
         } else {
-            Vec::new()
+            // A bare operand such as `12.50` ends at ` AS `.
+            let limit = expression_end(bytes, *i);
+            let end = CAST_AS_RE.find(&text[*i..limit]).map_or(limit, |m| *i + m.start());
+            let value = operand_value(text[*i..end].trim());
+            *i = end;
+            value
         };
+        skip_whitespace(bytes, i);
+        if text.get(*i..*i + 2).is_some_and(|kw| kw.eq_ignore_ascii_case("AS")) {
+            *i += 2;
+        }
+        let type_end = expression_end(bytes, *i);
+        let sql_type = text[*i..type_end].trim().to_string();
+        *i = type_end;
+        converted(value, &sql_type)
+    };
+    // Skip a CONVERT style argument and the closing parenthesis.
+    *i = expression_end(bytes, *i);
+    while bytes.get(*i) == Some(&b',') {
+        *i = expression_end(bytes, *i + 1);
+    }
+    if bytes.get(*i) == Some(&b')') {
+        *i += 1;
+    }
+    value
+}
 
-        let insert_statement_end = cap.get(0)?.end();
-        let search_area = &chunk[insert_statement_end..];
-
-        for values_cap in values_re.captures_iter(search_area) {
-            let values_str = values_cap.get(1)?.as_str();
-            let mut fields = Vec::new();
-            let mut current = String::new();
-            let mut in_string = false;
-            let mut in_cast = 0;
-            let mut escape_next = false;
-
-            for c in values_str.chars() {
-                if escape_next {
-                    current.push(c);
-                    escape_next = false;
-                } else if c == '\\' {
-                    current.push(c);
-                    escape_next = true;
-                } else if c == '\'' {
-                    current.push(c);
-                    if !in_string && current.ends_with("N'") {
-                        in_string = true;
-                    } else if in_string {
-                        if let Some(next_char) = search_area.chars().nth(current.len()) {
-                            if next_char == '\'' {
-                                continue;
-                            }
-                        }
-                        in_string = false;
-                    }
-                } else if c == '(' {
-                    current.push(c);
-                    if current.contains("CAST") {
-                        in_cast += 1;
-                    }
-                } else if c == ')' {
-                    current.push(c);
-                    if in_cast > 0 {
-                        in_cast -= 1;
-                    }
-                } else if c == ',' && !in_string && in_cast == 0 {
-                    fields.push(current.trim().to_string());
-                    current.clear();
-                } else {
-                    current.push(c);
+/// Reads one T-SQL literal at `*i`: `N'...'`/`'...'` strings, `0x...` binary
+/// (as base64), `CAST`/`CONVERT` expressions, numbers and NULL. Other
+/// expressions such as `GETDATE()` are kept as text.
+fn read_literal(text: &str, i: &mut usize) -> Value {
+    let bytes = text.as_bytes();
+    if matches!(bytes.get(*i), Some(b'N' | b'n')) && bytes.get(*i + 1) == Some(&b'\'') {
+        *i += 1;
+    }
+
+    if bytes.get(*i) == Some(&b'\'') {
+        let end = quoted_end(bytes, *i);
+        let close = if end > *i + 1 && bytes[end - 1] == b'\'' { end - 1 } else { end };
+        let inner = text.get(*i + 1..close).unwrap_or("").replace("''", "'");
+        *i = end;
+        return Value::String(inner);
+    }
+
+    if let Some(cap) = CONVERSION_RE.captures(&text[*i..]) {
+        let convert = cap[1].eq_ignore_ascii_case("CONVERT");
+        *i += cap.get(0).unwrap().end();
+        return read_conversion(text, i, convert);
+    }
+
+    let start = *i;
+    *i = expression_end(bytes, *i);
+    operand_value(text[start..*i].trim())
+}
+
+/// Value of an unquoted literal: `0x...` binary as base64, a number or NULL.
+fn operand_value(word: &str) -> Value {
+    if let Some(hex) = word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+        return decode_hex(hex).map_or_else(
+            || Value::String(word.to_string()),
+            |bytes| Value::String(STANDARD.encode(bytes))
+        );
+    }
+    word_value(word)
+}
+
+/// Splits `(v1, v2), (v3, v4)` starting at `*i` into rows of values with the
+/// byte range of each row, leaving `*i` after the last row.
+fn parse_value_rows(text: &str, i: &mut usize) -> Vec<(Range<usize>, Vec<Value>)> {
+    let bytes = text.as_bytes();
+    let mut rows = Vec::new();
+    loop {
+        skip_whitespace(bytes, i);
+        if bytes.get(*i) != Some(&b'(') {
+            break;
+        }
+        let start = *i;
+        *i += 1;
+        let mut row = Vec::new();
+        loop {
+            skip_whitespace(bytes, i);
+            row.push(read_literal(text, i));
+            skip_whitespace(bytes, i);
+            match bytes.get(*i) {
+                Some(b',') => {
+                    *i += 1;
+                }
+                Some(b')') => {
+                    *i += 1;
+                    break;
+                }
+                _ => {
+                    return rows;
                 }
             }
+        }
+        rows.push((start..*i, row));
+        skip_whitespace(bytes, i);
+        if bytes.get(*i) != Some(&b',') {
+            break;
+        }
+        *i += 1;
+    }
+    rows
+}
 
-            if !current.is_empty() {
-                fields.push(current.trim().to_string());
-            }
+pub fn parse_mssql(chunk: &str, ctx: &mut ParseContext) -> Option<Vec<Value>> {
+    debug!("Using parse method: MSSQL");
+    let mut records = Vec::new();
+    let mut pos = 0;
+
+    while let Some(cap) = INSERT_RE.captures_at(chunk, pos) {
+        let whole = cap.get(0)?;
+        let table = table_name(&cap[1]);
+        let mut end = whole.end() - 1;
+        let rows = parse_value_rows(chunk, &mut end);
+        pos = end.max(whole.end());
+
+        if ctx.ignore_table(&table) {
+            info!("Skipping excluded MSSQL table: {}", table);
+            continue;
+        }
+
+        debug!("Processing INSERT for MSSQL table: {}", table);
 
+        let column_names: Vec<String> = match cap.get(2) {
+            Some(list) =>
+                list
+                    .as_str()
+                    .split(',')
+                    .map(unquote)
+                    .filter(|c| !c.is_empty())
+                    .collect(),
+            None =>
+                ctx.column_types
+                    .get(&table)
+                    .map(|cols| {
+                        cols.iter()
+                            .map(|(name, _)| name.clone())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+        };
+
+        for (span, fields) in rows {
             let col_names = if !column_names.is_empty() {
                 column_names.clone()
             } else {
@@ -115,34 +323,19 @@ pub fn parse_mssql(chunk: &str, args: &Args) -> Option<Vec<Value>> {
             };
 
             if fields.len() != col_names.len() {
+                let reason = format!("{} values for {} columns", fields.len(), col_names.len());
+                ctx.reject(&table, reason, &chunk[span]);
                 continue;
             }
 
-            let mut obj = serde_json::Map::new();
-            obj.insert("table".to_string(), Value::String(table.to_string()));
-
-            for (i, val_str) in fields.iter().enumerate() {
-                if i >= col_names.len() {
-                    continue;
-                }
-
-                let value = parse_mssql_value(val_str);
-                obj.insert(col_names[i].clone(), value);
-            }
-
-            let id_key = obj
-                .keys()
-                .find(|k| k.eq_ignore_ascii_case("id"))
-                .cloned();
-            if let Some(key) = id_key {
-                obj.remove(&key);
-                debug!("Removed 'id' field (key: {}) from MSSQL record", key);
+            let mut obj = Map::new();
+            obj.insert("table".to_string(), Value::String(table.clone()));
+            for (name, value) in col_names.into_iter().zip(fields) {
+                obj.insert(name, value);
             }
 
             if obj.len() > 1 {
-                let mut final_value = Value::Object(obj);
-                clean_html_in_value(&mut final_value);
-                records.push(final_value);
+                records.push(Value::Object(obj));
             }
         }
     }
@@ -153,54 +346,3 @@ pub fn parse_mssql(chunk: &str, args: &Args) -> Option<Vec<Value>> {
         Some(records)
     }
 }
-
-fn parse_mssql_value(val_str: &str) -> Value {
-    if val_str == "NULL" {
-        return Value::Null;
-    }
-    if val_str.starts_with("N'") && val_str.ends_with("'") {
-        let inner_str = &val_str[2..val_str.len() - 1].replace("''", "'");
-
-        if
-            (inner_str.starts_with("[") && inner_str.ends_with("]")) ||
-            (inner_str.starts_with("{") && inner_str.ends_with("}"))
-        {
-            if let Ok(json_val) = serde_json::from_str(inner_str) {
-                return json_val;
-            }
-        }
-
-        return Value::String(inner_str.to_string());
-    }
-
-    if val_str.starts_with("CAST(") {
-        let re = Regex::new(r"CAST\(\s*N?'?(.*?)'?\s+AS").ok();
-        if let Some(re) = re {
-            if let Some(cap) = re.captures(val_str) {
-                if let Some(m) = cap.get(1) {
-                    return parse_mssql_value(m.as_str());
-                }
-            }
-        }
-
-        return Value::String(val_str.to_string());
-    }
-
-    if val_str == "0" || val_str == "1" {
-        if let Ok(b) = val_str.parse::<i8>() {
-            return Value::Bool(b != 0);
-        }
-    }
-
-    if let Ok(i) = val_str.parse::<i64>() {
-        return Value::Number(i.into());
-    }
-
-    if let Ok(f) = val_str.parse::<f64>() {
-        if let Some(n) = serde_json::Number::from_f64(f) {
-            return Value::Number(n);
-        }
-    }
-
-    Value::String(val_str.to_string())
-}
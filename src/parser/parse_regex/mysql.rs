@@ -1,198 +1,356 @@
-use log::{ info, warn, debug };
+use base64::{ engine::general_purpose::STANDARD, Engine as _ };
+use log::{ info, debug };
+use once_cell::sync::Lazy;
 use regex::Regex;
-use serde_json::Value;
-use crate::parser::parse_regex::{ clean_html_in_value, parse_array };
-use crate::cli::Args;
-use crate::util::exclude::Excluder;
+use serde_json::{ Map, Number, Value };
+use std::ops::Range;
+use crate::parser::parse_regex::parse_array;
+use crate::parser::schema::decode_hex;
+use crate::parser::ParseContext;
 
-pub fn parse_mysql(chunk: &str, args: &Args) -> Option<Vec<Value>> {
-    info!("Using parse method: MySQL");
-    let mut records = Vec::new();
-    
-    let excluder = if args.use_exclude {
-        Some(Excluder::load("config/exclude.json"))
-    } else {
-        None
-    };
+/// `INSERT [IGNORE] INTO`, `REPLACE INTO` and friends up to the first row's
+/// opening parenthesis. The rows themselves are read by `parse_value_rows`.
+static INSERT_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?is)\b(?:INSERT(?:\s+(?:LOW_PRIORITY|DELAYED|HIGH_PRIORITY|IGNORE))*|REPLACE(?:\s+(?:LOW_PRIORITY|DELAYED))*)(?:\s+INTO)?\s+((?:`(?:[^`]|``)+`|[\w$]+)(?:\s*\.\s*(?:`(?:[^`]|``)+`|[\w$]+))?)\s*(?:\(([^)]*)\))?\s*VALUES?\s*\("
+    ).unwrap()
+});
 
-    let insert_re = Regex::new(
-        r#"(?is)INSERT INTO\s+[`'\"]?(\w+)[`'\"]?\s*(?:\(([^)]+)\))?\s*VALUES\s*(.*?);"#
-    ).ok()?;
+/// Strips backticks from an identifier, undoubling embedded backticks.
+fn unquote(ident: &str) -> String {
+    let ident = ident.trim();
+    match ident.strip_prefix('`').and_then(|i| i.strip_suffix('`')) {
+        Some(inner) => inner.replace("``", "`"),
+        None => ident.trim_matches(&['\'', '"'][..]).to_string(),
+    }
+}
 
-    let row_re = Regex::new(r"\((.*?)\)").ok()?;
+/// Table of a possibly database-qualified name: `` `shop`.`items` `` is `items`.
+fn table_name(qualified: &str) -> String {
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in qualified.char_indices() {
+        match c {
+            '`' => {
+                quoted = !quoted;
+            }
+            '.' if !quoted => {
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    unquote(&qualified[start..])
+}
 
-    for cap in insert_re.captures_iter(chunk) {
-        let table = cap.get(1)?.as_str();
-        
-        if let Some(ref excl) = excluder {
-            if excl.ignore_table(table) {
-                info!("Skipping excluded MySQL table: {}", table);
-                continue;
+/// Column names used when an INSERT has no column list and no `CREATE TABLE`
+/// was seen for its table.
+fn default_columns(count: usize) -> Vec<String> {
+    (0..count)
+        .map(|i| {
+            match i {
+                0 => "id".to_string(),
+                1 => "name".to_string(),
+                2 => "description".to_string(),
+                _ => format!("column{}", i),
             }
+        })
+        .collect()
+}
+
+/// Undoes MySQL string escapes. `\%` and `\_` keep their backslash, as in MySQL.
+fn unescape_mysql(text: &str, quote: char) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == quote && chars.peek() == Some(&quote) {
+            chars.next();
+            out.push(quote);
+            continue;
+        }
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('0') => out.push('\0'),
+            Some('b') => out.push('\u{8}'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('Z') => out.push('\u{1a}'),
+            Some(c @ ('%' | '_')) => {
+                out.push('\\');
+                out.push(c);
+            }
+            Some(c) => out.push(c),
+            None => out.push('\\'),
         }
+    }
+    out
+}
 
-        let column_names: Vec<String> = if let Some(cols_match) = cap.get(2) {
-            cols_match
-                .as_str()
-                .split(',')
-                .map(|c|
-                    c
-                        .trim()
-                        .trim_matches(&['`', '\'', '"'][..])
-                        .to_string()
-                )
-                .collect()
-        } else {
-            Vec::new()
-        };
+/// Binary literals that decode to printable UTF-8 are kept as text; anything
+/// else (images, hashes) is stored as base64, like bytea and varbinary values.
+fn binary_value(bytes: Vec<u8>) -> Value {
+    match String::from_utf8(bytes) {
+        Ok(text) if !text.chars().any(|c| c.is_control() && !c.is_whitespace()) => Value::String(text),
+        Ok(text) => Value::String(STANDARD.encode(text)),
+        Err(e) => Value::String(STANDARD.encode(e.into_bytes())),
+    }
+}
 
-        let values_str = cap.get(3)?.as_str();
-        let mut inferred_column_count = 0;
-        let mut first_row_processed = false;
-
-        for row_cap in row_re.captures_iter(values_str) {
-            let row = row_cap.get(1)?.as_str();
-            let mut fields = Vec::new();
-            let mut current = String::new();
-            let mut in_string = false;
-            let mut escape_next = false;
-
-            for c in row.chars() {
-                if escape_next {
-                    current.push(c);
-                    escape_next = false;
-                } else if c == '\\' {
-                    current.push(c);
-                    escape_next = true;
-                } else if c == '\'' {
-                    current.push(c);
-                    in_string = !in_string;
-                } else if c == ',' && !in_string {
-                    fields.push(current.trim().to_string());
-                    current.clear();
+fn word_value(word: &str) -> Value {
+    if word.eq_ignore_ascii_case("NULL") {
+        return Value::Null;
+    }
+    if word.eq_ignore_ascii_case("TRUE") {
+        return Value::Bool(true);
+    }
+    if word.eq_ignore_ascii_case("FALSE") {
+        return Value::Bool(false);
+    }
+    if let Some(hex) = word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+        return decode_hex(hex).map_or_else(|| Value::String(word.to_string()), binary_value);
+    }
+    if let Some(bits) = word.strip_prefix("0b") && let Ok(n) = i64::from_str_radix(bits, 2) {
+        return Value::Number(n.into());
+    }
+    if let Ok(n) = word.parse::<i64>() {
+        return Value::Number(n.into());
+    }
+    if let Ok(f) = word.parse::<f64>() && let Some(n) = Number::from_f64(f) {
+        return Value::Number(n);
+    }
+    if word.starts_with('{') && word.ends_with('}') {
+        return serde_json
+            ::from_str::<Value>(word)
+            .ok()
+            .or_else(|| parse_array(word))
+            .unwrap_or_else(|| Value::String(word.to_string()));
+    }
+    Value::String(word.to_string())
+}
+
+/// Byte offset just past the quoted string opening at `start`.
+fn quoted_end(bytes: &[u8], start: usize) -> usize {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => {
+                i += 2;
+            }
+            b if b == quote => {
+                if bytes.get(i + 1) == Some(&quote) {
+                    i += 2;
                 } else {
-                    current.push(c);
+                    return i + 1;
                 }
             }
-            if !current.is_empty() {
-                fields.push(current.trim().to_string());
+            _ => {
+                i += 1;
             }
+        }
+    }
+    bytes.len()
+}
 
-            let col_names = if !column_names.is_empty() {
-                column_names.clone()
-            } else if !first_row_processed {
-                first_row_processed = true;
-                inferred_column_count = fields.len();
-
-                let mut default_cols = Vec::with_capacity(fields.len());
-                for i in 0..fields.len() {
-                    let col_name = match i {
-                        0 => "id".to_string(),
-                        1 => "name".to_string(),
-                        2 => "description".to_string(),
-                        _ => format!("column{}", i),
-                    };
-                    default_cols.push(col_name);
-                }
-                default_cols
-            } else {
-                (0..inferred_column_count)
-                    .map(|i| {
-                        match i {
-                            0 => "id".to_string(),
-                            1 => "name".to_string(),
-                            2 => "description".to_string(),
-                            _ => format!("column{}", i),
-                        }
-                    })
-                    .collect()
-            };
+/// Text between the quote at `open` and the closing quote just before `end`.
+fn quoted_inner(text: &str, open: usize, end: usize) -> &str {
+    let quote = text.as_bytes()[open];
+    let close = if end > open + 1 && text.as_bytes().get(end - 1) == Some(&quote) { end - 1 } else { end };
+    text.get(open + 1..close.min(text.len())).unwrap_or("")
+}
 
-            let mut obj = serde_json::Map::new();
-            obj.insert("table".to_string(), Value::String(table.to_string()));
+/// Reads one MySQL literal starting at `*i`: a `'...'` or `"..."` string with
+/// an optional `_charset` introducer, `X'..'`/`0x..` hex, `b'..'`/`0b..` bits,
+/// a number, NULL, or an unquoted expression such as `NOW()`.
+fn read_literal(text: &str, i: &mut usize) -> Value {
+    let bytes = text.as_bytes();
 
-            for (i, val_str) in fields.iter().enumerate() {
-                if i >= col_names.len() {
-                    warn!("More values than columns for table '{}', value: '{}'", table, val_str);
-                    continue;
-                }
+    // `_binary '...'`, `_utf8mb4'...'`
+    let mut binary = false;
+    if bytes.get(*i) == Some(&b'_') {
+        let mut j = *i + 1;
+        while bytes.get(j).is_some_and(|b| b.is_ascii_alphanumeric()) {
+            j += 1;
+        }
+        let charset = &text[*i + 1..j];
+        while bytes.get(j).is_some_and(|b| b.is_ascii_whitespace()) {
+            j += 1;
+        }
+        if !charset.is_empty() && matches!(bytes.get(j), Some(b'\'' | b'"' | b'0' | b'X' | b'x')) {
+            binary = charset.eq_ignore_ascii_case("binary");
+            *i = j;
+        }
+    }
 
-                let mut value = Value::Null;
-
-                if val_str == "NULL" {
-                } else if val_str.starts_with('\'') && val_str.ends_with('\'') {
-                    let inner_str = val_str.trim_matches('\'');
-                    let unescaped_mysql_str = inner_str
-                        .replace("''", "'")
-                        .replace("\\\\", "\\")
-                        .replace("\\'", "'");
-
-                    if
-                        (unescaped_mysql_str.starts_with('[') &&
-                            unescaped_mysql_str.ends_with(']')) ||
-                        (unescaped_mysql_str.starts_with('{') && unescaped_mysql_str.ends_with('}'))
-                    {
-                        let potential_json_str = unescaped_mysql_str.replace("\\\"", "\"");
-                        match serde_json::from_str::<Value>(&potential_json_str) {
-                            Ok(json_value) => {
-                                value = json_value;
-                            }
-                            Err(_) => {
-                                value = Value::String(unescaped_mysql_str);
-                            }
-                        }
-                    } else {
-                        value = Value::String(unescaped_mysql_str);
+    match bytes.get(*i) {
+        Some(&q @ (b'\'' | b'"')) => {
+            let end = quoted_end(bytes, *i);
+            let inner = quoted_inner(text, *i, end);
+            *i = end;
+            let value = unescape_mysql(inner, q as char);
+            if binary {
+                binary_value(value.into_bytes())
+            } else {
+                Value::String(value)
+            }
+        }
+        Some(b'X' | b'x' | b'B' | b'b') if bytes.get(*i + 1) == Some(&b'\'') => {
+            let hex = matches!(bytes[*i], b'X' | b'x');
+            let end = quoted_end(bytes, *i + 1);
+            let digits = quoted_inner(text, *i + 1, end);
+            *i = end;
+            if hex {
+                decode_hex(digits).map_or(Value::Null, binary_value)
+            } else {
+                i64::from_str_radix(digits, 2).map_or(Value::Null, |n| Value::Number(n.into()))
+            }
+        }
+        _ => {
+            let start = *i;
+            let mut depth = 0;
+            while *i < bytes.len() {
+                match bytes[*i] {
+                    b'\'' | b'"' => {
+                        *i = quoted_end(bytes, *i);
+                        continue;
                     }
-                } else if val_str.starts_with('{') && val_str.ends_with('}') {
-                    match serde_json::from_str::<Value>(val_str) {
-                        Ok(json_val) => {
-                            value = json_val;
-                        }
-                        Err(_) => {
-                            value = parse_array(val_str).unwrap_or(
-                                Value::String(val_str.to_string())
-                            );
-                        }
+                    b'(' => {
+                        depth += 1;
                     }
-                } else if val_str.starts_with('[') && val_str.ends_with(']') {
-                    match serde_json::from_str::<Value>(val_str) {
-                        Ok(json_val) => {
-                            value = json_val;
-                        }
-                        Err(_) => {
-                            value = Value::String(val_str.to_string());
-                        }
+                    b')' if depth == 0 => {
+                        break;
                     }
-                } else if let Ok(n) = val_str.parse::<i64>() {
-                    value = Value::Number(n.into());
-                } else if let Ok(f) = val_str.parse::<f64>() {
-                    value = Value::Number(
-                        serde_json::Number::from_f64(f).unwrap_or_else(|| (0).into())
-                    );
-                } else {
-                    value = Value::String(val_str.to_string());
+                    b')' => {
+                        depth -= 1;
+                    }
+                    b',' if depth == 0 => {
+                        break;
+                    }
+                    _ => {}
                 }
-
-                obj.insert(col_names[i].clone(), value);
+                *i += 1;
             }
+            word_value(text[start..*i].trim())
+        }
+    }
+}
+
+fn skip_whitespace(bytes: &[u8], i: &mut usize) {
+    while bytes.get(*i).is_some_and(|b| b.is_ascii_whitespace()) {
+        *i += 1;
+    }
+}
 
-            let id_key = obj
-                .keys()
-                .find(|k| k.eq_ignore_ascii_case("id"))
-                .cloned();
-            if let Some(key) = id_key {
-                obj.remove(&key);
-                debug!("Removed 'id' field (key: {}) from MySQL record", key);
+/// Splits `(v1, v2),(v3, v4)` starting at `*i` into rows of values with the
+/// byte range of each row, leaving `*i` after the last row. Parentheses
+/// inside strings never end a row.
+fn parse_value_rows(text: &str, i: &mut usize) -> Vec<(Range<usize>, Vec<Value>)> {
+    let bytes = text.as_bytes();
+    let mut rows = Vec::new();
+    loop {
+        skip_whitespace(bytes, i);
+        if bytes.get(*i) != Some(&b'(') {
+            break;
+        }
+        let start = *i;
+        *i += 1;
+        let mut row = Vec::new();
+        skip_whitespace(bytes, i);
+        if bytes.get(*i) == Some(&b')') {
+            *i += 1;
+        } else {
+            loop {
+                skip_whitespace(bytes, i);
+                row.push(read_literal(text, i));
+                skip_whitespace(bytes, i);
+                match bytes.get(*i) {
+                    Some(b',') => {
+                        *i += 1;
+                    }
+                    Some(b')') => {
+                        *i += 1;
+                        break;
+                    }
+                    _ => {
+                        return rows;
+                    }
+                }
             }
+        }
+        rows.push((start..*i, row));
+        skip_whitespace(bytes, i);
+        if bytes.get(*i) != Some(&b',') {
+            break;
+        }
+        *i += 1;
+    }
+    rows
+}
+
+pub fn parse_mysql(chunk: &str, ctx: &mut ParseContext) -> Option<Vec<Value>> {
+    debug!("Using parse method: MySQL");
+    let mut records = Vec::new();
+    let mut pos = 0;
+
+    while let Some(cap) = INSERT_RE.captures_at(chunk, pos) {
+        let whole = cap.get(0)?;
+        let table = table_name(&cap[1]);
+        let mut end = whole.end() - 1;
+        let rows = parse_value_rows(chunk, &mut end);
+        pos = end.max(whole.end());
+
+        if ctx.ignore_table(&table) {
+            info!("Skipping excluded MySQL table: {}", table);
+            continue;
+        }
+
+        let column_names: Vec<String> = match cap.get(2) {
+            Some(list) =>
+                list
+                    .as_str()
+                    .split(',')
+                    .map(unquote)
+                    .filter(|c| !c.is_empty())
+                    .collect(),
+            None =>
+                ctx.column_types
+                    .get(&table)
+                    .map(|cols| {
+                        cols.iter()
+                            .map(|(name, _)| name.clone())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+        };
 
-            if obj.len() > 1 {
-                let mut final_value = Value::Object(obj);
-                clean_html_in_value(&mut final_value);
-                records.push(final_value);
+        for (span, fields) in rows {
+            let col_names = if column_names.is_empty() {
+                default_columns(fields.len())
             } else {
-                warn!("Skipping MySQL record for table '{}', too few fields after processing", table);
+                column_names.clone()
+            };
+
+            if fields.is_empty() {
+                ctx.reject(&table, "row has no values".to_string(), &chunk[span]);
+                continue;
             }
+            if fields.len() != col_names.len() {
+                let reason = format!("{} values for {} columns", fields.len(), col_names.len());
+                ctx.reject(&table, reason, &chunk[span]);
+                continue;
+            }
+
+            let mut obj = Map::new();
+            obj.insert("table".to_string(), Value::String(table.clone()));
+            for (name, value) in col_names.iter().zip(fields) {
+                obj.insert(name.clone(), value);
+            }
+
+            records.push(Value::Object(obj));
         }
     }
 
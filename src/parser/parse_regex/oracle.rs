@@ -1,182 +1,505 @@
-use log::{ info, warn, debug };
+use base64::{ engine::general_purpose::STANDARD, Engine as _ };
+use log::{ info, debug };
+use once_cell::sync::Lazy;
 use regex::Regex;
-use serde_json::Value;
-use crate::parser::parse_regex::clean_html_in_value;
-use crate::cli::Args;
-use crate::util::exclude::Excluder;
+use serde_json::{ Map, Number, Value };
+use crate::parser::schema::decode_hex;
+use crate::parser::ParseContext;
 
-pub fn parse_oracle(content: &str, args: &Args) -> Option<Vec<Value>> {
-    info!("Using parse method: Oracle");
-    let mut records = Vec::new();
+/// `Insert into OWNER.TABLE (COLS) values (` up to the row's parenthesis; the
+/// values are read by `read_expression`.
+static INSERT_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?is)\bINSERT\s+INTO\s+((?:"[^"]+"|[\w$#]+)(?:\s*\.\s*(?:"[^"]+"|[\w$#]+))*)\s*(?:\(([^)]*)\))?\s*VALUES\s*\("#
+    ).unwrap()
+});
+/// A function name, possibly package-qualified, followed by its `(`.
+static FUNCTION_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^([A-Za-z][\w$#]*(?:\.[A-Za-z][\w$#]*)?)\s*\(").unwrap()
+});
 
-    let excluder = if args.use_exclude {
-        Some(Excluder::load("config/exclude.json"))
-    } else {
-        None
-    };
+const MONTHS: [&str; 12] = ["JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"];
+/// Format elements understood by `oracle_datetime`, longest first.
+const MASK_ELEMENTS: &[&str] = &[
+    "YYYY",
+    "RRRR",
+    "HH24",
+    "HH12",
+    "MONTH",
+    "MON",
+    "A.M.",
+    "P.M.",
+    "TZH",
+    "TZM",
+    "TZR",
+    "YY",
+    "RR",
+    "MM",
+    "DD",
+    "HH",
+    "MI",
+    "SS",
+    "FF",
+    "AM",
+    "PM",
+    "FX",
+    "FM",
+    "X",
+];
+/// Masks tried for `TO_DATE`/`TO_TIMESTAMP` calls without one.
+const DEFAULT_MASKS: &[&str] = &[
+    "YYYY-MM-DD HH24:MI:SSXFF",
+    "YYYY-MM-DD HH24:MI:SS",
+    "YYYY-MM-DD",
+    "DD-MON-RR HH.MI.SSXFF AM",
+    "DD-MON-RR",
+];
 
-    let insert_re = Regex::new(
-        r#"(?is)Insert\s+into\s+([\w\.\"]+)\s+\(([^)]+)\)\s+values\s+\(([^;]+)\);"#
-    ).ok()?;
+/// Closing delimiter of a `q'<open>...<close>'` literal.
+pub fn q_quote_close(open: char) -> char {
+    match open {
+        '[' => ']',
+        '{' => '}',
+        '(' => ')',
+        '<' => '>',
+        other => other,
+    }
+}
 
-    for cap in insert_re.captures_iter(content) {
-        let full_table = cap.get(1)?.as_str();
+fn unquote(ident: &str) -> String {
+    ident.trim().trim_matches('"').to_string()
+}
 
-        let table = (
-            if full_table.contains('.') {
-                full_table.split('.').last().unwrap_or(full_table)
-            } else {
-                full_table
-            }
-        ).trim_matches('"');
+fn read_number(chars: &[char], i: &mut usize, max: usize) -> Option<u32> {
+    let start = *i;
+    while *i < chars.len() && *i - start < max && chars[*i].is_ascii_digit() {
+        *i += 1;
+    }
+    if *i == start {
+        return None;
+    }
+    chars[start..*i].iter().collect::<String>().parse().ok()
+}
 
-        if let Some(ref excl) = excluder {
-            if excl.ignore_table(table) {
-                info!("Skipping excluded Oracle table: {}", table);
-                continue;
-            }
-        }
+/// Converts `value` written with the Oracle format `mask` (e.g. `DD-MON-RR
+/// HH.MI.SSXFF AM`) to ISO 8601: a date, or a timestamp when the mask has a
+/// time part. Returns `None` when the value does not match the mask.
+pub fn oracle_datetime(value: &str, mask: &str) -> Option<String> {
+    let chars: Vec<char> = value.trim().chars().collect();
+    let mask = mask.trim().to_uppercase();
+    let (mut year, mut month, mut day) = (None, 1, 1);
+    let (mut hour, mut minute, mut second) = (0, 0, 0);
+    let mut fraction = String::new();
+    let mut pm = None;
+    let mut zone = String::new();
+    let mut has_time = false;
 
-        debug!("Processing Oracle INSERT for table: {}", table);
-
-        let columns: Vec<&str> = cap
-            .get(2)?
-            .as_str()
-            .split(',')
-            .map(|s| s.trim().trim_matches('"'))
-            .collect();
-
-        let values_str = cap.get(3)?.as_str();
-        let mut fields = Vec::new();
-        let mut current = String::new();
-        let mut in_string = false;
-        let mut function_depth = 0;
-        let mut chars = values_str.chars().peekable();
-
-        while let Some(c) = chars.next() {
-            match c {
-                '\'' if !in_string => {
-                    current.push('\'');
-                    in_string = true;
+    let mut i = 0;
+    let mut m = 0;
+    while m < mask.len() {
+        let rest = &mask[m..];
+        if let Some(literal) = rest.strip_prefix('"') {
+            let len = literal.find('"').unwrap_or(literal.len());
+            i = (i + literal[..len].chars().count()).min(chars.len());
+            m += len + 2;
+            continue;
+        }
+        let Some(element) = MASK_ELEMENTS.iter().find(|e| rest.starts_with(**e)) else {
+            // Separators match any punctuation or space in the value.
+            m += rest.chars().next().map_or(1, char::len_utf8);
+            if chars.get(i).is_some_and(|c| !c.is_alphanumeric()) {
+                i += 1;
+            }
+            continue;
+        };
+        m += element.len();
+        match *element {
+            "YYYY" | "RRRR" => {
+                let start = i;
+                let y = read_number(&chars, &mut i, 4)?;
+                year = Some(if i - start <= 2 { rr_year(y) } else { y as i32 });
+            }
+            "YY" => {
+                year = Some(2000 + (read_number(&chars, &mut i, 2)? as i32));
+            }
+            "RR" => {
+                year = Some(rr_year(read_number(&chars, &mut i, 2)?));
+            }
+            "MM" => {
+                month = read_number(&chars, &mut i, 2)?;
+            }
+            "MON" | "MONTH" => {
+                let start = i;
+                while chars.get(i).is_some_and(|c| c.is_alphabetic()) {
+                    i += 1;
                 }
-                '\'' if in_string => {
-                    if chars.peek() == Some(&'\'') {
-                        current.push('\'');
-                        current.push('\'');
-                        chars.next();
-                    } else {
-                        current.push('\'');
-                        in_string = false;
-                    }
+                let name: String = chars[start..i].iter().collect::<String>().to_uppercase();
+                let index = MONTHS.iter().position(|mon| name.starts_with(mon))?;
+                month = (index as u32) + 1;
+            }
+            "DD" => {
+                day = read_number(&chars, &mut i, 2)?;
+            }
+            "HH" | "HH12" | "HH24" => {
+                hour = read_number(&chars, &mut i, 2)?;
+                has_time = true;
+            }
+            "MI" => {
+                minute = read_number(&chars, &mut i, 2)?;
+                has_time = true;
+            }
+            "SS" => {
+                second = read_number(&chars, &mut i, 2)?;
+                has_time = true;
+            }
+            "FF" => {
+                if mask[m..].starts_with(|c: char| c.is_ascii_digit()) {
+                    m += 1;
                 }
-                '(' => {
-                    current.push('(');
-                    if !in_string {
-                        function_depth += 1;
-                    }
+                while chars.get(i).is_some_and(|c| c.is_ascii_digit()) {
+                    fraction.push(chars[i]);
+                    i += 1;
+                }
+            }
+            "X" => {
+                if matches!(chars.get(i), Some('.' | ',')) {
+                    i += 1;
+                }
+            }
+            "AM" | "PM" | "A.M." | "P.M." => {
+                pm = Some(matches!(chars.get(i), Some('P' | 'p')));
+                while chars.get(i).is_some_and(|c| c.is_alphabetic() || *c == '.') {
+                    i += 1;
                 }
-                ')' => {
-                    current.push(')');
-                    if !in_string && function_depth > 0 {
-                        function_depth -= 1;
+            }
+            "TZH" => {
+                let sign = match chars.get(i) {
+                    Some(&s @ ('+' | '-')) => {
+                        i += 1;
+                        s
                     }
+                    _ => '+',
+                };
+                zone = format!("{}{:02}", sign, read_number(&chars, &mut i, 2)?);
+            }
+            "TZM" => {
+                zone = format!("{}:{:02}", zone, read_number(&chars, &mut i, 2)?);
+            }
+            "TZR" => {
+                let start = i;
+                while chars.get(i).is_some_and(|c| !c.is_whitespace()) {
+                    i += 1;
                 }
-                ',' if !in_string && function_depth == 0 => {
-                    fields.push(current.trim().to_string());
-                    current.clear();
+                let region: String = chars[start..i].iter().collect();
+                if region.starts_with(['+', '-']) {
+                    zone = region;
+                } else if ["UTC", "GMT", "Z"].contains(&region.to_uppercase().as_str()) {
+                    zone = "Z".to_string();
                 }
-                _ => current.push(c),
             }
+            _ => {}
         }
+    }
+    if i < chars.len() {
+        return None;
+    }
 
-        if !current.is_empty() || fields.len() < columns.len() {
-            fields.push(current.trim().to_string());
+    match pm {
+        Some(true) if hour < 12 => {
+            hour += 12;
         }
-
-        if fields.len() != columns.len() {
-            warn!(
-                "Mismatched number of columns ({}) and values ({}) for table '{}'. Row values: '{}'",
-                columns.len(),
-                fields.len(),
-                table,
-                values_str
-            );
-            continue;
+        Some(false) if hour == 12 => {
+            hour = 0;
         }
+        _ => {}
+    }
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
 
-        let mut obj = serde_json::Map::new();
-        obj.insert("table".to_string(), Value::String(table.to_string()));
+    let date = format!("{:04}-{:02}-{:02}", year?, month, day);
+    if !has_time {
+        return Some(date);
+    }
+    let fraction = fraction.trim_end_matches('0');
+    let fraction = if fraction.is_empty() { String::new() } else { format!(".{}", fraction) };
+    Some(format!("{}T{:02}:{:02}:{:02}{}{}", date, hour, minute, second, fraction, zone))
+}
 
-        for (col, val) in columns.iter().zip(fields.iter()) {
-            let parsed_value = parse_oracle_value(val);
-            obj.insert(col.to_string(), parsed_value);
-        }
+/// Oracle's `RR` rule: 00-49 are in the 2000s, 50-99 in the 1900s.
+fn rr_year(yy: u32) -> i32 {
+    if yy < 50 { 2000 + (yy as i32) } else { 1900 + (yy as i32) }
+}
 
-        let id_key = obj
-            .keys()
-            .find(|k| k.eq_ignore_ascii_case("id"))
-            .cloned();
-        if let Some(key) = id_key {
-            obj.remove(&key);
-            debug!("Removed 'id' field (key: {}) from Oracle record", key);
+fn skip_whitespace(chars: &[char], i: &mut usize) {
+    while chars.get(*i).is_some_and(|c| c.is_whitespace()) {
+        *i += 1;
+    }
+}
+
+/// Reads `'...'` (quotes doubled) or `q'[...]'` starting at the quote or `q`.
+fn read_string(chars: &[char], i: &mut usize) -> String {
+    let mut text = String::new();
+    if matches!(chars.get(*i), Some('q' | 'Q')) {
+        let Some(&open) = chars.get(*i + 2) else {
+            *i = chars.len();
+            return text;
+        };
+        let close = q_quote_close(open);
+        *i += 3;
+        while *i < chars.len() {
+            if chars[*i] == close && chars.get(*i + 1) == Some(&'\'') {
+                *i += 2;
+                return text;
+            }
+            text.push(chars[*i]);
+            *i += 1;
         }
+        return text;
+    }
 
-        if obj.len() > 1 {
-            let mut final_value = Value::Object(obj);
-            clean_html_in_value(&mut final_value);
-            records.push(final_value);
-        } else {
-            warn!("Skipping Oracle record for table '{}', too few fields after processing", table);
+    *i += 1;
+    while *i < chars.len() {
+        let c = chars[*i];
+        *i += 1;
+        if c == '\'' {
+            if chars.get(*i) == Some(&'\'') {
+                text.push('\'');
+                *i += 1;
+                continue;
+            }
+            break;
         }
+        text.push(c);
     }
+    text
+}
 
-    if records.is_empty() {
-        None
-    } else {
-        Some(records)
+fn text_of(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
     }
 }
 
-fn parse_oracle_value(val_str: &str) -> Value {
-    if val_str.eq_ignore_ascii_case("NULL") {
+fn word_value(word: &str) -> Value {
+    if word.eq_ignore_ascii_case("NULL") {
         return Value::Null;
     }
-    if val_str.starts_with('\'') && val_str.ends_with('\'') {
-        let inner_str = &val_str[1..val_str.len() - 1].replace("''", "'");
+    if let Ok(i) = word.parse::<i64>() {
+        return Value::Number(i.into());
+    }
+    if let Ok(f) = word.parse::<f64>() && let Some(n) = Number::from_f64(f) {
+        return Value::Number(n);
+    }
+    Value::String(word.to_string())
+}
 
-        if
-            (inner_str.starts_with('{') && inner_str.ends_with('}')) ||
-            (inner_str.starts_with('[') && inner_str.ends_with(']'))
-        {
-            if let Ok(json_val) = serde_json::from_str(inner_str) {
-                return json_val;
-            }
+/// Evaluates the functions SQL Developer and `exp` put in INSERTs. Unknown
+/// functions are kept as their SQL text.
+fn call_function(name: &str, args: Vec<Value>, sql: String) -> Value {
+    let first = args.first().cloned().unwrap_or(Value::Null);
+    match name.to_uppercase().as_str() {
+        "TO_DATE" | "TO_TIMESTAMP" | "TO_TIMESTAMP_TZ" => {
+            let text = text_of(&first);
+            let parsed = match args.get(1) {
+                Some(mask) => oracle_datetime(&text, &text_of(mask)),
+                None => DEFAULT_MASKS.iter().find_map(|mask| oracle_datetime(&text, mask)),
+            };
+            if first.is_null() { Value::Null } else { Value::String(parsed.unwrap_or(text)) }
         }
+        "HEXTORAW" =>
+            match decode_hex(text_of(&first).trim()) {
+                Some(bytes) => Value::String(STANDARD.encode(bytes)),
+                None => first,
+            }
+        "EMPTY_CLOB" | "EMPTY_BLOB" => Value::Null,
+        "TO_CLOB" | "TO_NCLOB" | "TO_CHAR" | "TO_NCHAR" =>
+            if first.is_null() {
+                Value::Null
+            } else {
+                Value::String(text_of(&first))
+            }
+        "TO_NUMBER" => word_value(text_of(&first).trim()),
+        "CHR" | "NCHR" =>
+            first
+                .as_u64()
+                .and_then(|code| char::from_u32(code as u32))
+                .map_or(Value::Null, |c| Value::String(c.to_string())),
+        _ => Value::String(sql),
+    }
+}
+
+/// Reads one operand: a string literal, a function call or a bare word.
+fn read_term(text: &[char], i: &mut usize) -> Value {
+    skip_whitespace(text, i);
+    let start = *i;
+    let at = |k: usize| text.get(*i + k).copied();
 
-        return Value::String(inner_str.to_string());
+    let national = matches!(at(0), Some('N' | 'n'));
+    let offset = if national { 1 } else { 0 };
+    if at(offset) == Some('\'') {
+        *i += offset;
+        return Value::String(read_string(text, i));
+    }
+    if matches!(at(offset), Some('q' | 'Q')) && at(offset + 1) == Some('\'') {
+        *i += offset;
+        return Value::String(read_string(text, i));
     }
 
-    if val_str.starts_with("to_timestamp(") {
-        let timestamp_re = Regex::new(r"to_timestamp\('([^']+)'").ok();
-        if let Some(re) = timestamp_re {
-            if let Some(cap) = re.captures(val_str) {
-                if let Some(date_match) = cap.get(1) {
-                    return Value::String(date_match.as_str().to_string());
+    let rest: String = text[*i..(*i + 64).min(text.len())].iter().collect();
+    if let Some(cap) = FUNCTION_RE.captures(&rest) {
+        let name = cap[1].to_string();
+        *i += cap[0].chars().count();
+        let mut args = Vec::new();
+        skip_whitespace(text, i);
+        if text.get(*i) == Some(&')') {
+            *i += 1;
+        } else {
+            loop {
+                args.push(read_expression(text, i));
+                skip_whitespace(text, i);
+                match text.get(*i) {
+                    Some(',') => {
+                        *i += 1;
+                    }
+                    Some(')') => {
+                        *i += 1;
+                        break;
+                    }
+                    _ => {
+                        break;
+                    }
                 }
             }
         }
-        return Value::String("timestamp_parse_error".to_string());
+        let sql: String = text[start..*i].iter().collect();
+        return call_function(&name, args, sql);
     }
 
-    if let Ok(i) = val_str.parse::<i64>() {
-        return Value::Number(i.into());
+    while let Some(c) = text.get(*i) {
+        if *c == ',' || *c == ')' || (*c == '|' && text.get(*i + 1) == Some(&'|')) {
+            break;
+        }
+        *i += 1;
+    }
+    let word: String = text[start..*i].iter().collect();
+    word_value(word.trim())
+}
+
+/// Reads one value, joining `||` concatenations (NULL counts as empty text).
+fn read_expression(text: &[char], i: &mut usize) -> Value {
+    let mut value = read_term(text, i);
+    loop {
+        skip_whitespace(text, i);
+        if text.get(*i) != Some(&'|') || text.get(*i + 1) != Some(&'|') {
+            return value;
+        }
+        *i += 2;
+        let next = read_term(text, i);
+        value = Value::String(text_of(&value) + &text_of(&next));
+    }
+}
+
+/// Reads the values of one row after its opening parenthesis, leaving `*i`
+/// past the closing one.
+fn parse_row(text: &[char], i: &mut usize) -> Vec<Value> {
+    let mut row = Vec::new();
+    skip_whitespace(text, i);
+    if text.get(*i) == Some(&')') {
+        *i += 1;
+        return row;
+    }
+    loop {
+        row.push(read_expression(text, i));
+        skip_whitespace(text, i);
+        match text.get(*i) {
+            Some(',') => {
+                *i += 1;
+            }
+            Some(')') => {
+                *i += 1;
+                return row;
+            }
+            _ => {
+                return row;
+            }
+        }
     }
+}
+
+pub fn parse_oracle(content: &str, ctx: &mut ParseContext) -> Option<Vec<Value>> {
+    debug!("Using parse method: Oracle");
+    let mut records = Vec::new();
+    // The statement is decoded once; `pos` (a byte offset for the regex) and
+    // `index` (into `chars` for the value reader) move forward together.
+    let chars: Vec<char> = content.chars().collect();
+    let mut pos = 0;
+    let mut index = 0;
+
+    while let Some(cap) = INSERT_RE.captures_at(content, pos) {
+        let whole = cap.get(0)?;
+        let full_table = cap.get(1)?.as_str();
+        let table = unquote(full_table.rsplit('.').next().unwrap_or(full_table));
+
+        index += content[pos..whole.end()].chars().count();
+        let start = index;
+        let fields = parse_row(&chars, &mut index);
+        pos = whole.end() + chars[start..index].iter().map(|c| c.len_utf8()).sum::<usize>();
+
+        if ctx.ignore_table(&table) {
+            info!("Skipping excluded Oracle table: {}", table);
+            continue;
+        }
+
+        debug!("Processing Oracle INSERT for table: {}", table);
+
+        let columns: Vec<String> = match cap.get(2) {
+            Some(list) =>
+                list
+                    .as_str()
+                    .split(',')
+                    .map(unquote)
+                    .collect(),
+            None =>
+                ctx.column_types
+                    .get(&table)
+                    .map(|cols| {
+                        cols.iter()
+                            .map(|(name, _)| name.clone())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+        };
 
-    if let Ok(f) = val_str.parse::<f64>() {
-        if let Some(n) = serde_json::Number::from_f64(f) {
-            return Value::Number(n);
+        if fields.len() != columns.len() {
+            let reason = if columns.is_empty() {
+                "columns unknown (no column list or CREATE TABLE)".to_string()
+            } else {
+                format!("{} values for {} columns", fields.len(), columns.len())
+            };
+            ctx.reject(&table, reason, &content[whole.start()..pos]);
+            continue;
+        }
+
+        let mut obj = Map::new();
+        obj.insert("table".to_string(), Value::String(table.clone()));
+
+        for (col, value) in columns.into_iter().zip(fields) {
+            obj.insert(col, value);
+        }
+
+        if obj.len() > 1 {
+            records.push(Value::Object(obj));
+        } else {
+            ctx.reject(&table, "row has no values".to_string(), &content[whole.start()..pos]);
         }
     }
 
-    Value::String(val_str.to_string())
+    if records.is_empty() {
+        None
+    } else {
+        Some(records)
+    }
 }
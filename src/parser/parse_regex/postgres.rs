@@ -1,110 +1,482 @@
 use log::{ info, warn, debug };
+use once_cell::sync::Lazy;
 use regex::Regex;
-use serde_json::Value;
-use crate::parser::parse_regex::{ clean_html_in_value, parse_array };
-use crate::cli::Args;
-use crate::util::exclude::Excluder;
+use serde_json::{ Map, Number, Value };
+use std::ops::Range;
+use crate::parser::schema::{ balanced_body, column_definitions, split_top_level };
+use crate::parser::ParseContext;
 
-pub fn parse_postgres(content: &str, args: &Args) -> Option<Vec<Value>> {
-    info!("Using parse method: Postgres");
-    let mut records = Vec::new();
-    
-    let excluder = if args.use_exclude {
-        Some(Excluder::load("config/exclude.json"))
-    } else {
-        None
-    };
-    
-    let copy_re = Regex::new(
-        r"COPY\s+public\.([a-zA-Z0-9_]+)\s*\(([^)]+)\)\s+FROM stdin;\n((?s:.*?))\n\\\."
-    ).ok()?;
-
-    for cap in copy_re.captures_iter(content) {
-        let table = cap.get(1)?.as_str();
-        
-        if let Some(ref excl) = excluder {
-            if excl.ignore_table(table) {
-                info!("Skipping excluded Postgres table: {}", table);
-                continue;
+/// A possibly schema-qualified, possibly double-quoted name.
+const QUALIFIED_NAME: &str = r#"(?:"(?:[^"]|"")+"|[\w$]+)(?:\s*\.\s*(?:"(?:[^"]|"")+"|[\w$]+))?"#;
+
+static COPY_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        &format!(
+            r"(?is)COPY\s+({})\s*(?:\(([^)]*)\))?\s*FROM\s+stdin\b([^\n]*)\n(.*?)\n\\\.",
+            QUALIFIED_NAME
+        )
+    ).unwrap()
+});
+static INSERT_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        &format!(
+            r"(?is)\bINSERT\s+INTO\s+({})\s*(?:\(([^)]*)\))?\s*(?:OVERRIDING\s+\w+\s+VALUE\s+)?VALUES\s*\(",
+            QUALIFIED_NAME
+        )
+    ).unwrap()
+});
+static CREATE_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        &format!(
+            r"(?is)\bCREATE\s+(?:UNLOGGED\s+)?TABLE\s+(?:IF\s+NOT\s+EXISTS\s+)?({})\s*\(",
+            QUALIFIED_NAME
+        )
+    ).unwrap()
+});
+static COPY_OPTION_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\b(DELIMITER|NULL)\b(?:\s+AS)?\s*('(?:[^']|'')*'|\w+)").unwrap()
+});
+static COPY_HEADER_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\bHEADER\b(?:\s+(true|false|on|off|0|1|match)\b)?").unwrap()
+});
+static COPY_CSV_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)\bCSV\b").unwrap());
+
+/// Strips double quotes from an identifier, undoubling embedded quotes.
+fn unquote(ident: &str) -> String {
+    let ident = ident.trim();
+    match ident.strip_prefix('"').and_then(|i| i.strip_suffix('"')) {
+        Some(inner) => inner.replace("\"\"", "\""),
+        None => ident.to_string(),
+    }
+}
+
+/// Record table name for a qualified name. Tables in `public` keep their bare
+/// name; other schemas become a prefix, so `sales.orders` is `sales_orders`.
+pub(crate) fn table_name(qualified: &str) -> String {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in qualified.char_indices() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+            }
+            '.' if !quoted => {
+                parts.push(unquote(&qualified[start..i]));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(unquote(&qualified[start..]));
+
+    match parts.as_slice() {
+        [schema, table] if !schema.eq_ignore_ascii_case("public") => format!("{}_{}", schema, table),
+        [.., table] => table.clone(),
+        [] => String::new(),
+    }
+}
+
+fn column_list(list: &str) -> Vec<String> {
+    split_top_level(list)
+        .into_iter()
+        .map(unquote)
+        .filter(|c| !c.is_empty())
+        .collect()
+}
+
+/// Remembers the column order of `CREATE TABLE` statements for dumps made
+/// with `--inserts`, whose INSERTs carry no column list.
+fn record_table_columns(chunk: &str, ctx: &mut ParseContext) {
+    for cap in CREATE_RE.captures_iter(chunk) {
+        let table = table_name(&cap[1]);
+        let open = cap.get(0).unwrap().end() - 1;
+        let Some(body) = balanced_body(chunk, open) else {
+            continue;
+        };
+        let columns: Vec<String> = column_definitions(body)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        if !columns.is_empty() {
+            debug!("Found columns for table '{}': {:?}", table, columns);
+            ctx.table_columns.insert(table, columns);
+        }
+    }
+}
+
+/// Decodes the backslash escapes of COPY's text format: `\b \f \n \r \t \v`,
+/// octal `\NNN`, hex `\xHH`, and any other escaped character as itself.
+pub fn unescape_copy_text(field: &str) -> String {
+    if !field.contains('\\') {
+        return field.to_string();
+    }
+    let mut out: Vec<u8> = Vec::with_capacity(field.len());
+    let bytes = field.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' || i + 1 == bytes.len() {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        let c = bytes[i + 1];
+        i += 2;
+        match c {
+            b'b' => out.push(0x08),
+            b'f' => out.push(0x0c),
+            b'n' => out.push(b'\n'),
+            b'r' => out.push(b'\r'),
+            b't' => out.push(b'\t'),
+            b'v' => out.push(0x0b),
+            b'0'..=b'7' => {
+                let mut n = (c - b'0') as u32;
+                let mut digits = 1;
+                while digits < 3 && i < bytes.len() && (b'0'..=b'7').contains(&bytes[i]) {
+                    n = n * 8 + ((bytes[i] - b'0') as u32);
+                    i += 1;
+                    digits += 1;
+                }
+                out.push(n as u8);
+            }
+            b'x' if i < bytes.len() && bytes[i].is_ascii_hexdigit() => {
+                let mut n = 0u32;
+                let mut digits = 0;
+                while digits < 2 && i < bytes.len() && bytes[i].is_ascii_hexdigit() {
+                    n = n * 16 + (bytes[i] as char).to_digit(16).unwrap();
+                    i += 1;
+                    digits += 1;
+                }
+                out.push(n as u8);
             }
+            other => out.push(other),
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Options of a `COPY ... FROM stdin [WITH] (...)` statement that change how
+/// rows are read.
+struct CopyOptions {
+    delimiter: char,
+    null: String,
+    csv: bool,
+    header: bool,
+}
+
+fn copy_options(options: &str) -> CopyOptions {
+    let mut opts = CopyOptions { delimiter: '\t', null: "\\N".to_string(), csv: false, header: false };
+    let mut delimiter = None;
+    let mut null = None;
+    for cap in COPY_OPTION_RE.captures_iter(options) {
+        let value = cap[2]
+            .strip_prefix('\'')
+            .and_then(|v| v.strip_suffix('\''))
+            .map_or_else(|| cap[2].to_string(), |v| v.replace("''", "'"));
+        match cap[1].to_uppercase().as_str() {
+            "DELIMITER" => {
+                delimiter = value.chars().next();
+            }
+            "NULL" => {
+                null = Some(value);
+            }
+            _ => {}
+        }
+    }
+    // Matches both `FORMAT csv` and the pre-9.0 bare `CSV` keyword.
+    opts.csv = COPY_CSV_RE.is_match(options);
+    if let Some(cap) = COPY_HEADER_RE.captures(options) {
+        opts.header = cap.get(1).is_none_or(|v| !matches!(v.as_str().to_lowercase().as_str(), "false" | "off" | "0"));
+    }
+    if opts.csv {
+        opts.delimiter = ',';
+        opts.null = String::new();
+    }
+    if let Some(d) = delimiter {
+        opts.delimiter = d;
+    }
+    if let Some(n) = null {
+        opts.null = n;
+    }
+    opts
+}
+
+fn copy_row_values(line: &str, opts: &CopyOptions) -> Vec<Value> {
+    if opts.csv {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .delimiter(opts.delimiter as u8)
+            .from_reader(line.as_bytes());
+        let mut record = csv::StringRecord::new();
+        if !reader.read_record(&mut record).unwrap_or(false) {
+            return Vec::new();
         }
-        
-        let columns: Vec<&str> = cap
-            .get(2)?
-            .as_str()
-            .split(',')
-            .map(|s| s.trim())
+        // CSV COPY only distinguishes NULL from '' by quoting, which the csv
+        // reader hides; unquoted empty fields are NULL by default.
+        return record
+            .iter()
+            .map(|f| if f == opts.null { Value::Null } else { Value::String(f.to_string()) })
             .collect();
-        let rows = cap.get(3)?.as_str();
+    }
+    line.split(opts.delimiter)
+        .map(|f| if f == opts.null { Value::Null } else { Value::String(unescape_copy_text(f)) })
+        .collect()
+}
+
+fn parse_copy(chunk: &str, ctx: &mut ParseContext, records: &mut Vec<Value>) {
+    for cap in COPY_RE.captures_iter(chunk) {
+        let table = table_name(&cap[1]);
+
+        if ctx.ignore_table(&table) {
+            info!("Skipping excluded Postgres table: {}", table);
+            continue;
+        }
+
+        let columns = match cap.get(2) {
+            Some(list) => column_list(list.as_str()),
+            None =>
+                match ctx.table_columns.get(&table) {
+                    Some(cols) => cols.clone(),
+                    None => {
+                        for line in cap[4].lines().filter(|l| !l.trim().is_empty()) {
+                            ctx.reject(&table, "columns unknown (no column list or CREATE TABLE)".to_string(), line);
+                        }
+                        continue;
+                    }
+                }
+        };
+        let opts = copy_options(&cap[3]);
+        let rows = &cap[4];
 
         for line in rows.lines() {
             if line.trim().is_empty() {
                 continue;
             }
 
-            let fields: Vec<&str> = line.split('\t').collect();
-            if fields.len() != columns.len() {
-                warn!(
-                    "Warning: Mismatched number of columns ({}) and values ({}) for table '{}' in COPY data. Line: '{}'",
-                    columns.len(),
-                    fields.len(),
-                    table,
-                    line
-                );
+            let values = copy_row_values(line, &opts);
+            if opts.header && values.iter().zip(&columns).all(|(v, c)| v.as_str() == Some(c)) {
                 continue;
             }
-            let mut obj = serde_json::Map::new();
-            obj.insert("table".to_string(), Value::String(table.to_string()));
-
-            for (col, val_str) in columns.iter().zip(fields.iter()) {
-                let value = if *val_str == r"\N" {
-                    Value::Null
-                } else if
-                    (val_str.starts_with('{') && val_str.ends_with('}')) ||
-                    (val_str.starts_with('[') && val_str.ends_with(']'))
-                {
-                    match serde_json::from_str::<Value>(val_str) {
-                        Ok(json_val) => json_val,
-                        Err(_) => {
-                            if val_str.starts_with('{') && val_str.ends_with('}') {
-                                parse_array(val_str).unwrap_or(Value::String(val_str.to_string()))
-                            } else {
-                                Value::String(val_str.to_string())
-                            }
-                        }
-                    }
-                } else {
-                    let unescaped_val = val_str
-                        .replace("\\\\", "\\")
-                        .replace("\\t", "\t")
-                        .replace("\\n", "\n");
-                    Value::String(unescaped_val)
-                };
-                obj.insert(col.trim().to_string(), value);
+            if values.len() != columns.len() {
+                let reason = format!("{} values for {} columns", values.len(), columns.len());
+                ctx.reject(&table, reason, line);
+                continue;
+            }
+            push_record(&table, &columns, values, records);
+        }
+    }
+}
+
+/// Reads one SQL literal: a string (optionally `E'...'`), number, NULL,
+/// boolean or bare word, followed by an optional `::type` cast.
+fn read_literal(chars: &[char], i: &mut usize) -> Value {
+    let start = *i;
+    let escaped = matches!(chars.get(*i), Some('E' | 'e')) && chars.get(*i + 1) == Some(&'\'');
+    if escaped {
+        *i += 1;
+    }
+
+    let value = if chars.get(*i) == Some(&'\'') {
+        *i += 1;
+        let mut text = String::new();
+        while *i < chars.len() {
+            let c = chars[*i];
+            if escaped && c == '\\' && *i + 1 < chars.len() {
+                text.push('\\');
+                text.push(chars[*i + 1]);
+                *i += 2;
+                continue;
             }
+            *i += 1;
+            if c == '\'' {
+                if chars.get(*i) == Some(&'\'') {
+                    text.push('\'');
+                    *i += 1;
+                    continue;
+                }
+                break;
+            }
+            text.push(c);
+        }
+        let text = if escaped { unescape_copy_text(&text) } else { text };
+        Value::String(text)
+    } else {
+        let mut depth = 0;
+        while *i < chars.len() {
+            match chars[*i] {
+                '(' => {
+                    depth += 1;
+                }
+                ')' if depth == 0 => {
+                    break;
+                }
+                ')' => {
+                    depth -= 1;
+                }
+                ',' if depth == 0 => {
+                    break;
+                }
+                ':' if depth == 0 && chars.get(*i + 1) == Some(&':') => {
+                    break;
+                }
+                _ => {}
+            }
+            *i += 1;
+        }
+        let word: String = chars[start..*i].iter().collect();
+        let word = word.trim();
+        if word.eq_ignore_ascii_case("NULL") {
+            Value::Null
+        } else if word.eq_ignore_ascii_case("true") {
+            Value::Bool(true)
+        } else if word.eq_ignore_ascii_case("false") {
+            Value::Bool(false)
+        } else if let Ok(n) = word.parse::<i64>() {
+            Value::Number(n.into())
+        } else if let Ok(f) = word.parse::<f64>() && let Some(n) = Number::from_f64(f) {
+            Value::Number(n)
+        } else {
+            Value::String(word.to_string())
+        }
+    };
 
-            let id_key = obj
-                .keys()
-                .find(|k| k.eq_ignore_ascii_case("id"))
-                .cloned();
-            if let Some(key) = id_key {
-                obj.remove(&key);
-                debug!("Removed 'id' field (key: {}) from Postgres record", key);
+    // Skip casts such as `::jsonb` or `::character varying(20)[]`.
+    while chars.get(*i) == Some(&':') && chars.get(*i + 1) == Some(&':') {
+        *i += 2;
+        let mut depth = 0;
+        while *i < chars.len() {
+            match chars[*i] {
+                '(' | '[' => {
+                    depth += 1;
+                }
+                ')' | ']' if depth > 0 => {
+                    depth -= 1;
+                }
+                ',' | ')' if depth == 0 => {
+                    break;
+                }
+                ':' if depth == 0 => {
+                    break;
+                }
+                _ => {}
+            }
+            *i += 1;
+        }
+    }
+    value
+}
+
+/// Splits `(v1, v2), (v3, v4)` into rows of values with the character range
+/// of each row, stopping at whatever follows the last row (`;`, `ON CONFLICT ...`).
+fn parse_value_rows(values: &str) -> Vec<(Range<usize>, Vec<Value>)> {
+    let chars: Vec<char> = values.chars().collect();
+    let mut rows = Vec::new();
+    let mut i = 0;
+    loop {
+        while chars.get(i).is_some_and(|c| c.is_whitespace()) {
+            i += 1;
+        }
+        if chars.get(i) != Some(&'(') {
+            break;
+        }
+        let start = i;
+        i += 1;
+        let mut row = Vec::new();
+        loop {
+            while chars.get(i).is_some_and(|c| c.is_whitespace()) {
+                i += 1;
+            }
+            row.push(read_literal(&chars, &mut i));
+            while chars.get(i).is_some_and(|c| c.is_whitespace()) {
+                i += 1;
             }
+            match chars.get(i) {
+                Some(',') => {
+                    i += 1;
+                }
+                Some(')') => {
+                    i += 1;
+                    break;
+                }
+                _ => {
+                    return rows;
+                }
+            }
+        }
+        rows.push((start..i, row));
+        while chars.get(i).is_some_and(|c| c.is_whitespace()) {
+            i += 1;
+        }
+        if chars.get(i) != Some(&',') {
+            break;
+        }
+        i += 1;
+    }
+    rows
+}
+
+/// Parses the INSERT statements of `pg_dump --inserts` / `--column-inserts`.
+fn parse_inserts(chunk: &str, ctx: &mut ParseContext, records: &mut Vec<Value>) {
+    for cap in INSERT_RE.captures_iter(chunk) {
+        let table = table_name(&cap[1]);
+
+        if ctx.ignore_table(&table) {
+            info!("Skipping excluded Postgres table: {}", table);
+            continue;
+        }
+
+        let columns = match cap.get(2) {
+            Some(list) => column_list(list.as_str()),
+            None =>
+                match ctx.table_columns.get(&table) {
+                    Some(cols) => cols.clone(),
+                    None => Vec::new(),
+                }
+        };
 
-            if obj.len() > 1 {
-                let mut final_value = Value::Object(obj);
-                clean_html_in_value(&mut final_value);
-                records.push(final_value);
-            } else {
-                warn!(
-                    "Skipping Postgres record for table '{}', became empty after removing ID. Original line: '{}'",
-                    table,
-                    line
-                );
+        let text = &chunk[cap.get(0).unwrap().end() - 1..];
+        for (span, values) in parse_value_rows(text) {
+            if values.len() != columns.len() {
+                let reason = if columns.is_empty() {
+                    "columns unknown (CREATE TABLE missing or unparsed)".to_string()
+                } else {
+                    format!("{} values for {} columns", values.len(), columns.len())
+                };
+                let raw: String = text
+                    .chars()
+                    .skip(span.start)
+                    .take(span.len())
+                    .collect();
+                ctx.reject(&table, reason, &raw);
+                continue;
             }
+            push_record(&table, &columns, values, records);
         }
     }
+}
+
+fn push_record(table: &str, columns: &[String], values: Vec<Value>, records: &mut Vec<Value>) {
+    let mut obj = Map::new();
+    obj.insert("table".to_string(), Value::String(table.to_string()));
+    for (col, value) in columns.iter().zip(values) {
+        obj.insert(col.clone(), value);
+    }
+    if obj.len() > 1 {
+        records.push(Value::Object(obj));
+    } else {
+        warn!("Skipping Postgres record for table '{}', has no fields.", table);
+    }
+}
+
+pub fn parse_postgres(content: &str, ctx: &mut ParseContext) -> Option<Vec<Value>> {
+    debug!("Using parse method: Postgres");
+    let mut records = Vec::new();
+
+    record_table_columns(content, ctx);
+    parse_copy(content, ctx, &mut records);
+    if records.is_empty() {
+        parse_inserts(content, ctx, &mut records);
+    }
 
     if records.is_empty() {
         None
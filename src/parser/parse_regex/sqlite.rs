@@ -1,28 +1,24 @@
 use log::{ info, warn, debug };
+use once_cell::sync::Lazy;
 use regex::Regex;
 use serde_json::Value;
-use crate::parser::parse_regex::clean_html_in_value;
-use crate::cli::Args;
-use crate::util::exclude::Excluder;
-
-pub fn parse_sqlite(chunk: &str, args: &Args) -> Option<Vec<Value>> {
-    info!("Using parse method: SQLite");
+use crate::parser::ParseContext;
+
+static CREATE_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?is)CREATE TABLE\s+(?:IF NOT EXISTS\s+)?(?:`?(\w+)`?|(\w+))\s*\((.*?)\);").unwrap()
+});
+static COLUMN_DEF_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s*(?:`?(\w+)`?|(\w+))\s+").unwrap()
+});
+static INSERT_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?is)INSERT INTO\s+(?:`?(\w+)`?|(\w+))\s+VALUES\s*\((.*?)\);").unwrap()
+});
+
+pub fn parse_sqlite(chunk: &str, ctx: &mut ParseContext) -> Option<Vec<Value>> {
+    debug!("Using parse method: SQLite");
     let mut records = Vec::new();
-    
-    let excluder = if args.use_exclude {
-        Some(Excluder::load("config/exclude.json"))
-    } else {
-        None
-    };
-
-    let create_re = Regex::new(
-        r"(?is)CREATE TABLE\s+(?:IF NOT EXISTS\s+)?(?:`?(\w+)`?|(\w+))\s*\((.*?)\);"
-    ).ok()?;
-
-    let column_def_re = Regex::new(r"^\s*(?:`?(\w+)`?|(\w+))\s+").ok()?;
-    let mut table_columns = std::collections::HashMap::new();
 
-    for cap in create_re.captures_iter(chunk) {
+    for cap in CREATE_RE.captures_iter(chunk) {
         let table_name = cap
             .get(1)
             .or_else(|| cap.get(2))
@@ -44,29 +40,21 @@ pub fn parse_sqlite(chunk: &str, args: &Args) -> Option<Vec<Value>> {
                 {
                     continue;
                 }
-                if let Some(col_cap) = column_def_re.captures(trimmed_line) {
-                    if let Some(col_name) = col_cap.get(1).or_else(|| col_cap.get(2)) {
-                        cols.push(col_name.as_str().to_string());
-                    }
+                if
+                    let Some(col_cap) = COLUMN_DEF_RE.captures(trimmed_line) &&
+                    let Some(col_name) = col_cap.get(1).or_else(|| col_cap.get(2))
+                {
+                    cols.push(col_name.as_str().to_string());
                 }
             }
             if !cols.is_empty() {
                 debug!("Found columns for table '{}': {:?}", table_name, cols);
-                table_columns.insert(table_name.to_string(), cols);
+                ctx.table_columns.insert(table_name.to_string(), cols);
             }
         }
     }
 
-    if table_columns.is_empty() {
-        warn!("Could not parse any CREATE TABLE statements to find column names in SQLite chunk.");
-        return None;
-    }
-
-    let insert_re = Regex::new(
-        r"(?is)INSERT INTO\s+(?:`?(\w+)`?|(\w+))\s+VALUES\s*\((.*?)\);"
-    ).ok()?;
-
-    for cap in insert_re.captures_iter(chunk) {
+    for cap in INSERT_RE.captures_iter(chunk) {
         let table = match cap.get(1).or_else(|| cap.get(2)) {
             Some(t) => t.as_str(),
             None => {
@@ -78,17 +66,15 @@ pub fn parse_sqlite(chunk: &str, args: &Args) -> Option<Vec<Value>> {
             continue;
         }
         
-        if let Some(ref excl) = excluder {
-            if excl.ignore_table(table) {
-                info!("Skipping excluded SQLite table: {}", table);
-                continue;
-            }
+        if ctx.ignore_table(table) {
+            info!("Skipping excluded SQLite table: {}", table);
+            continue;
         }
 
-        let columns = match table_columns.get(table) {
-            Some(cols) => cols,
+        let columns = match ctx.table_columns.get(table) {
+            Some(cols) => cols.clone(),
             None => {
-                warn!("Skipping INSERT for table '{}' because columns were not found (CREATE TABLE missing or unparsed).", table);
+                ctx.reject(table, "columns unknown (CREATE TABLE missing or unparsed)".to_string(), &cap[0]);
                 continue;
             }
         };
@@ -118,13 +104,8 @@ pub fn parse_sqlite(chunk: &str, args: &Args) -> Option<Vec<Value>> {
         fields.push(current_field.trim().to_string());
 
         if fields.len() != columns.len() {
-            warn!(
-                "Mismatched number of columns ({}) and values ({}) for table '{}'. Row: '{}'",
-                columns.len(),
-                fields.len(),
-                table,
-                values_str
-            );
+            let reason = format!("{} values for {} columns", fields.len(), columns.len());
+            ctx.reject(table, reason, &cap[0]);
             continue;
         }
 
@@ -137,23 +118,7 @@ pub fn parse_sqlite(chunk: &str, args: &Args) -> Option<Vec<Value>> {
             if val_str == "NULL" {
             } else if val_str.starts_with('\'') && val_str.ends_with('\'') && val_str.len() >= 2 {
                 let inner_str = &val_str[1..val_str.len() - 1];
-                let unescaped_str = inner_str.replace("''", "'");
-
-                if
-                    (unescaped_str.starts_with('[') && unescaped_str.ends_with(']')) ||
-                    (unescaped_str.starts_with('{') && unescaped_str.ends_with('}'))
-                {
-                    match serde_json::from_str::<Value>(&unescaped_str) {
-                        Ok(json_value) => {
-                            value = json_value;
-                        }
-                        Err(_) => {
-                            value = Value::String(unescaped_str);
-                        }
-                    }
-                } else {
-                    value = Value::String(unescaped_str);
-                }
+                value = Value::String(inner_str.replace("''", "'"));
             } else if let Ok(n) = val_str.parse::<i64>() {
                 value = Value::Number(n.into());
             } else if let Ok(f) = val_str.parse::<f64>() {
@@ -172,25 +137,10 @@ pub fn parse_sqlite(chunk: &str, args: &Args) -> Option<Vec<Value>> {
             obj.insert(col.clone(), value);
         }
 
-        let id_key = obj
-            .keys()
-            .find(|k| k.eq_ignore_ascii_case("id"))
-            .cloned();
-        if let Some(key) = id_key {
-            obj.remove(&key);
-            debug!("Removed 'id' field (key: {}) from SQLite record", key);
-        }
-
         if obj.len() > 1 {
-            let mut final_value = Value::Object(obj);
-            clean_html_in_value(&mut final_value);
-            records.push(final_value);
+            records.push(Value::Object(obj));
         } else {
-            warn!(
-                "Skipping SQLite record for table '{}', became empty after removing ID. Original values: '{}'",
-                table,
-                values_str
-            );
+            ctx.reject(table, "row has no values".to_string(), &cap[0]);
         }
     }
 
@@ -1,166 +1,551 @@
+use once_cell::sync::Lazy;
 use regex::Regex;
-use log::{ info, warn, debug };
-use serde_json::Value;
-use crate::parser::parse_regex::clean_html_in_value;
-use crate::cli::Args;
-use crate::util::exclude::Excluder;
-
-pub fn parse_surreal(chunk: &str, args: &Args) -> Option<Vec<Value>> {
-    info!("Using parse method: Surreal");
-    let mut records = Vec::new();
+use log::{ info, debug };
+use serde_json::{ Map, Number, Value };
+use crate::parser::ParseContext;
 
-    let excluder = if args.use_exclude {
-        Some(Excluder::load("config/exclude.json"))
-    } else {
-        None
-    };
+static TABLE_HEADER_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"--\s*TABLE DATA:\s*([a-zA-Z0-9_]+)").unwrap()
+});
+static DURATION_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?:\d+(?:ns|us|µs|ms|s|m|h|d|w|y))+$").unwrap()
+});
+
+/// A position in one SurrealQL statement, reading the value grammar of
+/// `surreal export`: objects, arrays, strings, numbers, durations, record IDs
+/// (`person:john`, `person:⟨john smith⟩`), prefixed strings (`d'...'`,
+/// `u'...'`, `r'...'`) and casts (`<datetime> '...'`).
+struct Cursor<'a> {
+    chars: &'a [char],
+    i: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn peek(&self, k: usize) -> Option<char> {
+        self.chars.get(self.i + k).copied()
+    }
 
-    let table_header_re = Regex::new(r"--\s*TABLE DATA:\s*([a-zA-Z0-9_]+)").ok()?;
-    let insert_re = Regex::new(r"INSERT\s*\[(?s)(.*?)\]\s*;").ok()?;
+    fn at_end(&mut self) -> bool {
+        self.skip_ws();
+        self.i >= self.chars.len()
+    }
 
-    let mut inserts = Vec::new();
-    for insert_cap in insert_re.captures_iter(chunk) {
-        if let Some(array_content) = insert_cap.get(1) {
-            let array_text = array_content.as_str();
-            let full_match = insert_cap.get(0).unwrap().as_str();
-            inserts.push((full_match, array_text));
+    /// Skips whitespace and `--`, `//`, `#` and `/* */` comments.
+    fn skip_ws(&mut self) {
+        loop {
+            match (self.peek(0), self.peek(1)) {
+                (Some(c), _) if c.is_whitespace() => {
+                    self.i += 1;
+                }
+                (Some('-'), Some('-')) | (Some('/'), Some('/')) | (Some('#'), _) => {
+                    while self.peek(0).is_some_and(|c| c != '\n') {
+                        self.i += 1;
+                    }
+                }
+                (Some('/'), Some('*')) => {
+                    self.i += 2;
+                    while self.i < self.chars.len() && !(self.peek(0) == Some('*') && self.peek(1) == Some('/')) {
+                        self.i += 1;
+                    }
+                    self.i = (self.i + 2).min(self.chars.len());
+                }
+                _ => {
+                    return;
+                }
+            }
         }
     }
 
-    if inserts.is_empty() {
-        warn!("No INSERT statements found in chunk");
-        return None;
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_ws();
+        if self.peek(0) == Some(c) {
+            self.i += 1;
+            true
+        } else {
+            false
+        }
     }
 
-    let mut table_sections = Vec::new();
-    for table_cap in table_header_re.captures_iter(chunk) {
-        if let Some(table_name) = table_cap.get(1) {
-            let pos = table_cap.get(0).unwrap().start();
-            table_sections.push((table_name.as_str().to_string(), pos));
+    fn eat_str(&mut self, s: &str) -> bool {
+        self.skip_ws();
+        let n = s.chars().count();
+        if self.chars.len() >= self.i + n && self.chars[self.i..self.i + n].iter().copied().eq(s.chars()) {
+            self.i += n;
+            true
+        } else {
+            false
         }
     }
 
-    table_sections.sort_by_key(|&(_, pos)| pos);
+    /// Consumes `keyword` if it is the next word, ignoring case.
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        self.skip_ws();
+        let start = self.i;
+        match self.word() {
+            Some(word) if word.eq_ignore_ascii_case(keyword) => true,
+            _ => {
+                self.i = start;
+                false
+            }
+        }
+    }
 
-    for (i, (insert_stmt, array_content)) in inserts.iter().enumerate() {
-        let insert_pos = chunk.find(insert_stmt).unwrap_or(0);
-        let mut table_name = "unknown_table".to_string();
-        for (t_name, t_pos) in &table_sections {
-            if *t_pos < insert_pos {
-                table_name = t_name.clone();
-            } else {
+    fn word(&mut self) -> Option<String> {
+        let start = self.i;
+        while self.peek(0).is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            self.i += 1;
+        }
+        (self.i > start).then(|| self.chars[start..self.i].iter().collect())
+    }
+
+    /// A table or field name, bare or quoted with backticks or `⟨⟩`.
+    fn ident(&mut self) -> Option<String> {
+        self.skip_ws();
+        match self.peek(0) {
+            Some('`') => Some(self.delimited('`')),
+            Some('⟨') => Some(self.delimited('⟩')),
+            _ => self.word(),
+        }
+    }
+
+    /// Text up to `close`, after the opening delimiter at the cursor.
+    fn delimited(&mut self, close: char) -> String {
+        self.i += 1;
+        let start = self.i;
+        while self.peek(0).is_some_and(|c| c != close) {
+            if self.peek(0) == Some('\\') {
+                self.i += 1;
+            }
+            self.i += 1;
+        }
+        let text: String = self.chars[start..self.i.min(self.chars.len())].iter().collect();
+        self.i = (self.i + 1).min(self.chars.len());
+        text.replace(&format!("\\{}", close), &close.to_string())
+    }
+
+    fn string(&mut self) -> String {
+        let quote = self.peek(0).unwrap_or('\'');
+        self.i += 1;
+        let mut text = String::new();
+        while let Some(c) = self.peek(0) {
+            self.i += 1;
+            if c == quote {
                 break;
             }
+            if c != '\\' {
+                text.push(c);
+                continue;
+            }
+            let Some(escaped) = self.peek(0) else {
+                break;
+            };
+            self.i += 1;
+            match escaped {
+                'n' => text.push('\n'),
+                'r' => text.push('\r'),
+                't' => text.push('\t'),
+                '0' => text.push('\0'),
+                'b' => text.push('\u{8}'),
+                'f' => text.push('\u{c}'),
+                'u' => {
+                    let hex: String = self.chars[self.i..(self.i + 4).min(self.chars.len())].iter().collect();
+                    match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                        Some(ch) => {
+                            text.push(ch);
+                            self.i += 4;
+                        }
+                        None => text.push('u'),
+                    }
+                }
+                other => text.push(other),
+            }
         }
+        text
+    }
+
+    /// Text of a balanced `(...)`, `[...]` or `{...}` group at the cursor.
+    fn balanced(&mut self) -> String {
+        let start = self.i;
+        let mut depth = 0;
+        while let Some(c) = self.peek(0) {
+            match c {
+                '\'' | '"' => {
+                    self.string();
+                    continue;
+                }
+                '(' | '[' | '{' => {
+                    depth += 1;
+                }
+                ')' | ']' | '}' => {
+                    depth -= 1;
+                    if depth <= 0 {
+                        self.i += 1;
+                        break;
+                    }
+                }
+                _ => {}
+            }
+            self.i += 1;
+        }
+        self.chars[start..self.i].iter().collect()
+    }
 
-        if let Some(ref excl) = excluder {
-            if excl.ignore_table(&table_name) {
-                info!("Skipping excluded table: {}", table_name);
-                continue; 
+    /// The part of a record ID after `table:`.
+    fn record_key(&mut self) -> String {
+        match self.peek(0) {
+            Some('⟨') => self.delimited('⟩'),
+            Some('`') => self.delimited('`'),
+            Some('[' | '{') => self.value().to_string(),
+            Some('\'' | '"') => self.string(),
+            _ => {
+                let start = self.i;
+                // `-` is part of the key unless it starts a `->` arrow.
+                while
+                    self.peek(0).is_some_and(|c| c.is_alphanumeric() || c == '_') ||
+                    (self.peek(0) == Some('-') && self.peek(1) != Some('>'))
+                {
+                    self.i += 1;
+                }
+                if self.peek(0) == Some('(') {
+                    self.balanced();
+                }
+                self.chars[start..self.i].iter().collect()
             }
         }
+    }
 
-        info!("Processing INSERT #{} for table: {}", i, table_name);
-        debug!("Parsing data from table {}: {:.100}...", table_name, array_content);
+    fn number(&mut self) -> Value {
+        let start = self.i;
+        if matches!(self.peek(0), Some('-' | '+')) {
+            self.i += 1;
+        }
+        while let Some(c) = self.peek(0) {
+            let exponent_sign = matches!(c, '-' | '+') && matches!(self.chars[self.i - 1], 'e' | 'E');
+            if c.is_alphanumeric() || c == '.' || c == '_' || exponent_sign {
+                self.i += 1;
+            } else {
+                break;
+            }
+        }
+        let token: String = self.chars[start..self.i].iter().collect();
+        if DURATION_RE.is_match(&token) {
+            return Value::String(token);
+        }
+        let digits = token.trim_end_matches("dec").trim_end_matches('f').replace('_', "");
+        if let Ok(n) = digits.parse::<i64>() {
+            return Value::Number(n.into());
+        }
+        match digits.parse::<f64>().ok().and_then(Number::from_f64) {
+            Some(n) => Value::Number(n),
+            None => Value::String(token),
+        }
+    }
 
-        let object_re = Regex::new(r"\}\s*,\s*\{").unwrap();
-        let items: Vec<String> = object_re
-            .split(array_content)
-            .map(|s| {
-                let trimmed = s.trim();
-                let mut obj = trimmed.to_string();
-                if !obj.starts_with('{') {
-                    obj.insert(0, '{');
+    fn object(&mut self) -> Map<String, Value> {
+        let mut obj = Map::new();
+        self.i += 1;
+        loop {
+            if self.eat('}') || self.at_end() {
+                return obj;
+            }
+            let key = match self.peek(0) {
+                Some('\'' | '"') => self.string(),
+                _ => {
+                    let start = self.i;
+                    match self.ident() {
+                        Some(key) => key,
+                        None => {
+                            // Not an object literal (e.g. a `{ ... }` block); skip it.
+                            self.i = start;
+                            self.skip_group('{', '}');
+                            return obj;
+                        }
+                    }
                 }
-                if !obj.ends_with('}') {
-                    obj.push('}');
+            };
+            if !self.eat(':') {
+                self.skip_group('{', '}');
+                return obj;
+            }
+            let value = self.value();
+            obj.insert(key, value);
+            self.eat(',');
+        }
+    }
+
+    /// Skips to just past the `close` ending the group the cursor is in.
+    fn skip_group(&mut self, open: char, close: char) {
+        let mut depth = 1;
+        while let Some(c) = self.peek(0) {
+            match c {
+                '\'' | '"' => {
+                    self.string();
+                    continue;
                 }
-                obj
-            })
-            .collect();
+                c if c == open => {
+                    depth += 1;
+                }
+                c if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.i += 1;
+                        return;
+                    }
+                }
+                _ => {}
+            }
+            self.i += 1;
+        }
+    }
 
-        for item_str in items {
-            if let Ok(mut obj) = serde_json::from_str::<serde_json::Map<String, Value>>(&item_str) {
-                obj.insert("table".to_string(), Value::String(table_name.clone()));
-                let mut value = Value::Object(obj);
-                clean_html_in_value(&mut value);
-                records.push(value);
-                continue;
+    fn array(&mut self, close: char) -> Vec<Value> {
+        let mut items = Vec::new();
+        self.i += 1;
+        loop {
+            if self.eat(close) || self.at_end() {
+                return items;
+            }
+            let before = self.i;
+            items.push(self.value());
+            if !self.eat(',') && self.i == before {
+                // Unreadable element; give up on the rest of the array.
+                self.skip_group('[', close);
+                return items;
             }
+        }
+    }
 
-            let mut record = serde_json::Map::new();
-            let kv_regex = Regex::new(
-                r#"([a-zA-Z_][a-zA-Z0-9_]*)\s*:\s*("(?:\\.|[^"\\])*"|'[^']*'|\[.*?\]|\{.*?\}|[0-9.]+(?:f)?|true|false|null)"#
-            ).unwrap();
-
-            for caps in kv_regex.captures_iter(&item_str) {
-                let key = caps.get(1).unwrap().as_str();
-                let raw_val = caps.get(2).unwrap().as_str().trim();
-
-                let value = if raw_val.starts_with('[') && raw_val.ends_with(']') {
-                    serde_json
-                        ::from_str::<Value>(raw_val)
-                        .unwrap_or(Value::String(raw_val.to_string()))
-                } else if raw_val.starts_with('{') && raw_val.ends_with('}') {
-                    serde_json
-                        ::from_str::<Value>(raw_val)
-                        .unwrap_or(Value::String(raw_val.to_string()))
-                } else if raw_val.starts_with('\'') && raw_val.ends_with('\'') {
-                    Value::String(raw_val.trim_matches('\'').to_string())
-                } else if raw_val.starts_with('"') && raw_val.ends_with('"') {
-                    match serde_json::from_str::<String>(raw_val) {
-                        Ok(s) => Value::String(s),
-                        Err(_) => {
-                            let s = raw_val[1..raw_val.len()-1]
-                                .replace("\\\"", "\"")
-                                .replace("\\\\", "\\")
-                                .replace("\\n", "\n")
-                                .replace("\\r", "\r")
-                                .replace("\\t", "\t");
-                            Value::String(s)
+    fn value(&mut self) -> Value {
+        self.skip_ws();
+        let Some(c) = self.peek(0) else {
+            return Value::Null;
+        };
+        match c {
+            '{' => Value::Object(self.object()),
+            '[' => Value::Array(self.array(']')),
+            '(' => {
+                // Geometry points `(x, y)` and parenthesised values.
+                let mut items = self.array(')');
+                if items.len() == 1 { items.remove(0) } else { Value::Array(items) }
+            }
+            '\'' | '"' => Value::String(self.string()),
+            '<' => {
+                // `<datetime> '...'`, `<decimal> 1.5`: the cast is dropped.
+                while self.peek(0).is_some_and(|c| c != '>') {
+                    self.i += 1;
+                }
+                self.i += 1;
+                self.value()
+            }
+            's' | 'r' | 'd' | 'u' | 'b' if matches!(self.peek(1), Some('\'' | '"')) => {
+                self.i += 1;
+                Value::String(self.string())
+            }
+            c if c.is_ascii_digit() || (matches!(c, '-' | '+') && self.peek(1).is_some_and(|d| d.is_ascii_digit())) =>
+                self.number(),
+            '`' | '⟨' => Value::String(self.ident().unwrap_or_default()),
+            c if c.is_alphabetic() || c == '_' => {
+                let start = self.i;
+                let word = self.word().unwrap_or_default();
+                match (self.peek(0), self.peek(1)) {
+                    (Some(':'), Some(':')) => {
+                        // Function calls such as `time::now()` are kept as text.
+                        while self.peek(0).is_some_and(|c| c.is_alphanumeric() || c == '_' || c == ':') {
+                            self.i += 1;
+                        }
+                        if self.peek(0) == Some('(') {
+                            self.balanced();
                         }
+                        Value::String(self.chars[start..self.i].iter().collect())
                     }
-                } else if let Ok(n) = raw_val.trim_end_matches('f').parse::<f64>() {
-                    if n.fract() == 0.0 {
-                        Value::Number((n as i64).into())
-                    } else {
-                        serde_json::Number
-                            ::from_f64(n)
-                            .map(Value::Number)
-                            .unwrap_or(Value::String(raw_val.to_string()))
+                    (Some(':'), _) => {
+                        self.i += 1;
+                        let key = self.record_key();
+                        Value::String(format!("{}:{}", word, key))
                     }
-                } else if raw_val == "true" {
-                    Value::Bool(true)
-                } else if raw_val == "false" {
-                    Value::Bool(false)
-                } else if raw_val == "null" {
-                    Value::Null
-                } else {
-                    Value::String(raw_val.to_string())
-                };
+                    _ =>
+                        match word.to_lowercase().as_str() {
+                            "true" => Value::Bool(true),
+                            "false" => Value::Bool(false),
+                            "null" | "none" => Value::Null,
+                            _ => Value::String(word),
+                        }
+                }
+            }
+            _ => {
+                self.i += 1;
+                Value::Null
+            }
+        }
+    }
+
+    /// `table` or `table:key`, returning the table and the full record ID.
+    fn target(&mut self) -> Option<(String, Option<String>)> {
+        let table = self.ident()?;
+        if self.peek(0) == Some(':') && self.peek(1) != Some(':') {
+            self.i += 1;
+            let key = self.record_key();
+            let id = format!("{}:{}", table, key);
+            return Some((table, Some(id)));
+        }
+        Some((table, None))
+    }
+
+    /// `CONTENT {...}`, `MERGE {...}`, `REPLACE {...}` or `SET a = 1, b = 2`.
+    fn data_clause(&mut self) -> Map<String, Value> {
+        if self.eat_keyword("CONTENT") || self.eat_keyword("MERGE") || self.eat_keyword("REPLACE") {
+            return match self.value() {
+                Value::Object(obj) => obj,
+                _ => Map::new(),
+            };
+        }
+        let mut obj = Map::new();
+        if self.eat_keyword("SET") {
+            loop {
+                let start = self.i;
+                let mut path = Vec::new();
+                while let Some(part) = self.ident() {
+                    path.push(part);
+                    if !self.eat('.') {
+                        break;
+                    }
+                }
+                if path.is_empty() || !(self.eat('=') || self.eat_str("+=")) {
+                    self.i = start;
+                    break;
+                }
+                obj.insert(path.join("."), self.value());
+                if !self.eat(',') {
+                    break;
+                }
+            }
+        }
+        obj
+    }
 
-                record.insert(key.to_string(), value);
+    /// Moves past the next top-level `;`.
+    fn skip_statement(&mut self) {
+        while let Some(c) = self.peek(0) {
+            match c {
+                '\'' | '"' => {
+                    self.string();
+                }
+                ';' => {
+                    self.i += 1;
+                    return;
+                }
+                _ => {
+                    self.i += 1;
+                }
             }
+        }
+    }
+}
+
+/// Table of a record ID such as `person:john`.
+fn id_table(obj: &Map<String, Value>) -> Option<String> {
+    let id = obj.get("id")?.as_str()?;
+    let (table, _) = id.split_once(':')?;
+    (!table.is_empty()).then(|| table.to_string())
+}
+
+fn push_record(
+    ctx: &mut ParseContext,
+    table: String,
+    mut obj: Map<String, Value>,
+    raw: &[char],
+    records: &mut Vec<Value>
+) {
+    if ctx.ignore_table(&table) {
+        info!("Skipping excluded table: {}", table);
+        return;
+    }
+    if obj.is_empty() {
+        ctx.reject(&table, "record has no fields".to_string(), &raw.iter().collect::<String>());
+        return;
+    }
+    obj.insert("table".to_string(), Value::String(table));
+    records.push(Value::Object(obj));
+}
 
-            record.insert("table".to_string(), Value::String(table_name.clone()));
-            record.remove("id");
+/// Parses `INSERT`, `CREATE`, `UPSERT` and `RELATE` statements from a
+/// `surreal export`. The table comes from `INSERT INTO`, the statement
+/// target, the preceding `-- TABLE DATA:` header or the record ID, in that
+/// order; `RELATE` edges become records of the edge table with `in`/`out`.
+pub fn parse_surreal(chunk: &str, ctx: &mut ParseContext) -> Option<Vec<Value>> {
+    debug!("Using parse method: Surreal");
+    let mut records = Vec::new();
+    let chars: Vec<char> = chunk.chars().collect();
+    let mut cur = Cursor { chars: &chars, i: 0 };
 
-            if record.len() > 1 {
-                let mut value = Value::Object(record);
-                clean_html_in_value(&mut value);
-                records.push(value);
-            } else {
-                warn!("Regex fallback produced empty record for: {}", item_str);
+    loop {
+        let before: String = {
+            let start = cur.i;
+            cur.skip_ws();
+            chars[start..cur.i].iter().collect()
+        };
+        if let Some(table_cap) = TABLE_HEADER_RE.captures_iter(&before).last() {
+            ctx.current_table = Some(table_cap[1].to_string());
+        }
+        if cur.at_end() {
+            break;
+        }
+        let start = cur.i;
+
+        if cur.eat_keyword("INSERT") {
+            let relation = cur.eat_keyword("RELATION");
+            cur.eat_keyword("IGNORE");
+            let into = if cur.eat_keyword("INTO") { cur.ident() } else { None };
+            let rows = match cur.value() {
+                Value::Array(rows) => rows,
+                row @ Value::Object(_) => vec![row],
+                _ => Vec::new(),
+            };
+            debug!("Parsed {} rows from SurrealDB INSERT{}", rows.len(), if relation { " RELATION" } else { "" });
+            for row in rows {
+                let table = into
+                    .clone()
+                    .or_else(|| ctx.current_table.clone())
+                    .or_else(|| row.as_object().and_then(id_table))
+                    .unwrap_or_else(|| "unknown_table".to_string());
+                let Value::Object(obj) = row else {
+                    ctx.reject(&table, "INSERT row is not an object".to_string(), &row.to_string());
+                    continue;
+                };
+                push_record(ctx, table, obj, &chars[start..cur.i], &mut records);
+            }
+        } else if cur.eat_keyword("CREATE") || cur.eat_keyword("UPSERT") {
+            cur.eat_keyword("ONLY");
+            if let Some((table, id)) = cur.target() {
+                let mut obj = cur.data_clause();
+                if let Some(id) = id {
+                    obj.insert("id".to_string(), Value::String(id));
+                }
+                push_record(ctx, table, obj, &chars[start..cur.i], &mut records);
+            }
+        } else if cur.eat_keyword("RELATE") {
+            cur.eat_keyword("ONLY");
+            let from = cur.value();
+            let edge = if cur.eat_str("->") { cur.target() } else { None };
+            let to = if cur.eat_str("->") { cur.value() } else { Value::Null };
+            if let Some((table, id)) = edge {
+                let mut obj = cur.data_clause();
+                if let Some(id) = id {
+                    obj.insert("id".to_string(), Value::String(id));
+                }
+                obj.insert("in".to_string(), from);
+                obj.insert("out".to_string(), to);
+                push_record(ctx, table, obj, &chars[start..cur.i], &mut records);
             }
         }
+        cur.skip_statement();
     }
 
     if records.is_empty() {
-        warn!("No records parsed from section");
+        debug!("No records parsed from statement");
         None
     } else {
-   
-        info!("Successfully parsed {} records", records.len());
+        debug!("Parsed {} SurrealDB records", records.len());
         Some(records)
     }
 }
@@ -0,0 +1,135 @@
+use crate::cli::Args;
+use crate::parser::detect::signature_matches;
+use crate::parser::parse_regex::mssql::{ self, parse_mssql };
+use crate::parser::parse_regex::mysql::parse_mysql;
+use crate::parser::parse_regex::oracle::parse_oracle;
+use crate::parser::parse_regex::postgres::{ self, parse_postgres };
+use crate::parser::parse_regex::sqlite::parse_sqlite;
+use crate::parser::parse_regex::surreal::parse_surreal;
+use crate::parser::schema::{ normalize_identifier, DumpSchema };
+use crate::parser::csv_file::CsvParser;
+use crate::parser::json_file::JsonParser;
+use crate::parser::{ stream_database_export, ParseContext, RecordIter };
+use log::info;
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use std::io::BufRead;
+use std::sync::{ Arc, RwLock };
+
+/// A dump format that db2vec can detect and stream.
+///
+/// The built-in SQL dialects and the JSON and CSV readers are registered
+/// implementations; library users can add their own with `register_parser`.
+pub trait ExportParser: Send + Sync {
+    /// Name accepted by `--format` and shown in logs, e.g. `mysql`.
+    fn name(&self) -> &'static str;
+
+    /// Confidence that the file at `path`, whose first bytes are `head`, is
+    /// in this format. 0 means no; the built-in formats score 3 for each
+    /// signature only their dump tool writes and 1 for weaker hints.
+    fn detect(&self, path: &str, head: &[u8]) -> u32;
+
+    /// Streams the records of the dump read from `reader`. Every record is
+    /// an object with a `table` field naming its collection.
+    fn parse(&self, path: &str, reader: Box<dyn BufRead + Send>, args: &Args) -> RecordIter;
+
+    /// Like `parse`, adding the column types and foreign keys declared in
+    /// the dump to `schema` for the sinks and `--join` suggestions. Parsers
+    /// of formats without DDL can keep the default.
+    fn parse_with_schema(
+        &self,
+        path: &str,
+        reader: Box<dyn BufRead + Send>,
+        args: &Args,
+        _schema: Arc<DumpSchema>
+    ) -> RecordIter {
+        self.parse(path, reader, args)
+    }
+}
+
+/// Parses the records out of one complete statement.
+type StatementParser = fn(&str, &mut ParseContext) -> Option<Vec<Value>>;
+
+/// Record table name for a possibly schema-qualified table name.
+type TableName = fn(&str) -> String;
+
+/// A built-in SQL dump dialect: the dump is split into statements by
+/// `StatementReader` and each is handed to the dialect's statement parser.
+#[derive(Clone, Copy)]
+pub struct SqlDialect {
+    name: &'static str,
+    pub(crate) parse_statement: StatementParser,
+    /// Names tables in DDL the same way `parse_statement` names records.
+    pub(crate) table_name: TableName,
+}
+
+impl ExportParser for SqlDialect {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn detect(&self, _path: &str, head: &[u8]) -> u32 {
+        signature_matches(self.name, &String::from_utf8_lossy(head)).0
+    }
+
+    fn parse(&self, path: &str, reader: Box<dyn BufRead + Send>, args: &Args) -> RecordIter {
+        Box::new(stream_database_export(reader, self.name, args).with_source(path))
+    }
+
+    fn parse_with_schema(
+        &self,
+        path: &str,
+        reader: Box<dyn BufRead + Send>,
+        args: &Args,
+        schema: Arc<DumpSchema>
+    ) -> RecordIter {
+        Box::new(stream_database_export(reader, self.name, args).with_source(path).with_schema(schema))
+    }
+}
+
+/// The SQL dialects db2vec ships with.
+static SQL_DIALECTS: &[SqlDialect] = &[
+    SqlDialect { name: "mysql", parse_statement: parse_mysql, table_name: normalize_identifier },
+    SqlDialect { name: "postgres", parse_statement: parse_postgres, table_name: postgres::table_name },
+    SqlDialect { name: "mssql", parse_statement: parse_mssql, table_name: mssql::table_name },
+    SqlDialect { name: "sqlite", parse_statement: parse_sqlite, table_name: normalize_identifier },
+    SqlDialect { name: "oracle", parse_statement: parse_oracle, table_name: normalize_identifier },
+    SqlDialect { name: "surreal", parse_statement: parse_surreal, table_name: normalize_identifier },
+];
+
+static PARSERS: Lazy<RwLock<Vec<Arc<dyn ExportParser>>>> = Lazy::new(|| {
+    RwLock::new(
+        SQL_DIALECTS.iter()
+            .map(|dialect| Arc::new(*dialect) as Arc<dyn ExportParser>)
+            .chain([Arc::new(JsonParser) as Arc<dyn ExportParser>, Arc::new(CsvParser)])
+            .collect()
+    )
+});
+
+/// Adds `parser` to the formats considered by detection and `--format`.
+/// A parser with the name of a registered one replaces it.
+pub fn register_parser<P: ExportParser + 'static>(parser: P) {
+    let mut parsers = PARSERS.write().unwrap();
+    parsers.retain(|p| !p.name().eq_ignore_ascii_case(parser.name()));
+    info!("Registered dump parser '{}'", parser.name());
+    parsers.push(Arc::new(parser));
+}
+
+/// Every registered parser, built-in dialects and readers first.
+pub fn registered_parsers() -> Vec<Arc<dyn ExportParser>> {
+    PARSERS.read().unwrap().clone()
+}
+
+/// The registered parser called `name`, ignoring case.
+pub fn find_parser(name: &str) -> Option<Arc<dyn ExportParser>> {
+    PARSERS.read()
+        .unwrap()
+        .iter()
+        .find(|p| p.name().eq_ignore_ascii_case(name))
+        .cloned()
+}
+
+/// The built-in SQL dialect called `name`.
+pub(crate) fn sql_dialect(name: &str) -> Option<&'static SqlDialect> {
+    SQL_DIALECTS.iter().find(|d| d.name == name)
+}
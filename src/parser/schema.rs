@@ -0,0 +1,626 @@
+use base64::{ engine::general_purpose::STANDARD, Engine as _ };
+use log::{ debug, warn };
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{ Deserialize, Serialize };
+use serde_json::{ Number, Value };
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::RwLock;
+use crate::parser::parse_regex::{ json_text, parse_array };
+
+/// A possibly quoted identifier: `name`, `"name"`, `` `name` `` or `[name]`.
+const IDENT: &str = r#"(?:"[^"]+"|`[^`]+`|\[[^\]]+\]|[\w$]+)"#;
+
+static CREATE_TABLE_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        &format!(
+            r"(?is)CREATE\s+(?:GLOBAL\s+|LOCAL\s+)?(?:TEMP(?:ORARY)?\s+|UNLOGGED\s+)?TABLE\s+(?:IF\s+NOT\s+EXISTS\s+)?({0}(?:\.{0})*)\s*\(",
+            IDENT
+        )
+    ).unwrap()
+});
+static ALTER_PK_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        &format!(
+            r"(?is)ALTER\s+TABLE\s+(?:ONLY\s+)?(?:IF\s+EXISTS\s+)?({0}(?:\.{0})*)\s+ADD\s+(?:CONSTRAINT\s+{0}\s+)?PRIMARY\s+KEY\s*(?:(?:NON)?CLUSTERED\s*)?\(([^)]*)\)",
+            IDENT
+        )
+    ).unwrap()
+});
+static TABLE_PK_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?is)^(?:CONSTRAINT\s+\S+\s+)?PRIMARY\s+KEY\s*(?:(?:NON)?CLUSTERED\s*)?\(([^)]*)\)"
+    ).unwrap()
+});
+static INLINE_PK_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)\bPRIMARY\s+KEY\b").unwrap());
+static ALTER_FK_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        &format!(
+            r"(?is)ALTER\s+TABLE\s+(?:ONLY\s+)?(?:IF\s+EXISTS\s+)?({0}(?:\.{0})*)\s+(?:WITH\s+(?:NO)?CHECK\s+)?ADD\s+(?:CONSTRAINT\s+{0}\s+)?FOREIGN\s+KEY\s*(?:{0}\s*)?\(([^)]*)\)\s*REFERENCES\s+({0}(?:\.{0})*)\s*\(([^)]*)\)",
+            IDENT
+        )
+    ).unwrap()
+});
+static TABLE_FK_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        &format!(
+            r"(?is)^(?:CONSTRAINT\s+{0}\s+)?FOREIGN\s+KEY\s*(?:{0}\s*)?\(([^)]*)\)\s*REFERENCES\s+({0}(?:\.{0})*)\s*\(([^)]*)\)",
+            IDENT
+        )
+    ).unwrap()
+});
+static INLINE_FK_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!(r"(?is)\bREFERENCES\s+({0}(?:\.{0})*)\s*\(([^)]*)\)", IDENT)).unwrap()
+});
+static REFERENCES_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)\bREFERENCES\b").unwrap());
+static TIMESTAMP_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^(\d{4}-\d{2}-\d{2})[ T](\d{2}:\d{2}(?::\d{2}(?:\.\d+)?)?)\s*(Z|[+-]\d{2}(?::?\d{2})?)?$"
+    ).unwrap()
+});
+
+/// Strips quoting (`"`, `` ` ``, `[]`) and any schema prefix from a table name.
+pub fn normalize_identifier(name: &str) -> String {
+    let last = name.trim().rsplit('.').next().unwrap_or(name);
+    last.trim().trim_matches(|c| matches!(c, '"' | '`' | '[' | ']')).to_string()
+}
+
+fn parse_key_columns(list: &str) -> Vec<String> {
+    list.split(',')
+        .filter_map(|col| col.split_whitespace().next())
+        .map(normalize_identifier)
+        .filter(|col| !col.is_empty())
+        .collect()
+}
+
+/// Returns the text between the parenthesis opening at `open` and its match.
+pub(crate) fn balanced_body(statement: &str, open: usize) -> Option<&str> {
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    for (i, c) in statement[open..].char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => {
+                quote = None;
+            }
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => {
+                quote = Some(c);
+            }
+            (None, '(') => {
+                depth += 1;
+            }
+            (None, ')') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&statement[open + 1..open + i]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+pub(crate) fn split_top_level(body: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+    for (i, c) in body.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => {
+                quote = None;
+            }
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => {
+                quote = Some(c);
+            }
+            (None, '(') => {
+                depth += 1;
+            }
+            (None, ')') => {
+                depth -= 1;
+            }
+            (None, ',') if depth == 0 => {
+                parts.push(&body[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&body[start..]);
+    parts
+}
+
+/// Finds primary key declarations in a DDL statement.
+///
+/// Understands table-level `PRIMARY KEY (...)` constraints and inline column
+/// constraints inside `CREATE TABLE`, as well as `ALTER TABLE ... ADD
+/// [CONSTRAINT x] PRIMARY KEY (...)` as emitted by pg_dump and Oracle.
+/// Tables are named by `table_name`, the dialect's record table name for a
+/// qualified name, so keys, types and records of a table share one name.
+pub fn extract_primary_keys(statement: &str, table_name: fn(&str) -> String) -> Vec<(String, Vec<String>)> {
+    if !INLINE_PK_RE.is_match(statement) {
+        return Vec::new();
+    }
+
+    let mut keys = Vec::new();
+
+    for cap in CREATE_TABLE_RE.captures_iter(statement) {
+        let table = table_name(&cap[1]);
+        let open = cap.get(0).unwrap().end() - 1;
+        let Some(body) = balanced_body(statement, open) else {
+            continue;
+        };
+
+        let mut columns = Vec::new();
+        for element in split_top_level(body) {
+            let element = element.trim();
+            if let Some(pk) = TABLE_PK_RE.captures(element) {
+                columns = parse_key_columns(&pk[1]);
+                break;
+            }
+            if INLINE_PK_RE.is_match(element) && let Some(name) = element.split_whitespace().next() {
+                columns.push(normalize_identifier(name));
+            }
+        }
+
+        if !columns.is_empty() {
+            keys.push((table, columns));
+        }
+    }
+
+    for cap in ALTER_PK_RE.captures_iter(statement) {
+        let columns = parse_key_columns(&cap[2]);
+        if !columns.is_empty() {
+            keys.push((table_name(&cap[1]), columns));
+        }
+    }
+
+    keys
+}
+
+/// A single-column foreign key: `table.column` references `ref_table.ref_column`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignKey {
+    pub table: String,
+    pub column: String,
+    pub ref_table: String,
+    pub ref_column: String,
+}
+
+impl std::fmt::Display for ForeignKey {
+    /// Formats the key in `--join` syntax.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{} -> {}.{}", self.table, self.column, self.ref_table, self.ref_column)
+    }
+}
+
+fn foreign_key(
+    table_name: fn(&str) -> String,
+    table: &str,
+    columns: &str,
+    ref_table: &str,
+    ref_columns: &str
+) -> Option<ForeignKey> {
+    let (columns, ref_columns) = (parse_key_columns(columns), parse_key_columns(ref_columns));
+    // Composite keys cannot be expressed as a join spec.
+    if columns.len() != 1 || ref_columns.len() != 1 {
+        return None;
+    }
+    Some(ForeignKey {
+        table: table_name(table),
+        column: columns[0].clone(),
+        ref_table: table_name(ref_table),
+        ref_column: ref_columns[0].clone(),
+    })
+}
+
+/// Finds single-column foreign keys in a DDL statement: table-level
+/// `FOREIGN KEY (...) REFERENCES t (...)` constraints, inline column
+/// `REFERENCES t (...)` clauses, and `ALTER TABLE ... ADD [CONSTRAINT x]
+/// FOREIGN KEY` as emitted by pg_dump, MSSQL and Oracle. Tables are named
+/// by `table_name` as in `extract_primary_keys`.
+pub fn extract_foreign_keys(statement: &str, table_name: fn(&str) -> String) -> Vec<ForeignKey> {
+    if !REFERENCES_RE.is_match(statement) {
+        return Vec::new();
+    }
+
+    let mut keys = Vec::new();
+
+    for cap in CREATE_TABLE_RE.captures_iter(statement) {
+        let open = cap.get(0).unwrap().end() - 1;
+        let Some(body) = balanced_body(statement, open) else {
+            continue;
+        };
+        for element in split_top_level(body) {
+            let element = element.trim();
+            if let Some(fk) = TABLE_FK_RE.captures(element) {
+                keys.extend(foreign_key(table_name, &cap[1], &fk[1], &fk[2], &fk[3]));
+            } else if
+                let Some(fk) = INLINE_FK_RE.captures(element) &&
+                let Some((column, _)) = column_definition(element)
+            {
+                keys.extend(foreign_key(table_name, &cap[1], &column, &fk[1], &fk[2]));
+            }
+        }
+    }
+
+    for cap in ALTER_FK_RE.captures_iter(statement) {
+        keys.extend(foreign_key(table_name, &cap[1], &cap[2], &cap[3], &cap[4]));
+    }
+
+    keys
+}
+
+/// Broad type of a column, from its declared SQL type in any supported dialect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnKind {
+    Integer,
+    Float,
+    Boolean,
+    Timestamp,
+    Date,
+    Json,
+    Array,
+    Binary,
+    Text,
+}
+
+/// Columns of a table in declaration order.
+pub type TableSchema = Vec<(String, ColumnKind)>;
+
+/// Maps a declared column type such as `tinyint(1)`, `numeric(10,2)`,
+/// `timestamp with time zone`, `[nvarchar](50)` or `text[]` to its kind.
+pub fn column_kind(sql_type: &str) -> ColumnKind {
+    let ty = sql_type.trim().trim_start_matches('[').to_lowercase();
+    let base_len = ty.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(ty.len());
+    let (base, rest) = ty.split_at(base_len);
+    let rest = rest.trim_start_matches(']').trim_start();
+    let (params, rest) = match rest.strip_prefix('(').and_then(|r| r.split_once(')')) {
+        Some((params, rest)) => (Some(params), rest),
+        None => (None, rest),
+    };
+    if rest.starts_with("[]") || base.starts_with('_') {
+        return ColumnKind::Array;
+    }
+    let scale = params.map(|p| p.split(',').nth(1).map_or(0, |s| s.trim().parse::<i64>().unwrap_or(1)));
+
+    match base {
+        "bool" | "boolean" => ColumnKind::Boolean,
+        "bit" if params.is_none_or(|p| p.trim() == "1") => ColumnKind::Boolean,
+        "tinyint" if params.is_some_and(|p| p.trim() == "1") => ColumnKind::Boolean,
+        | "bit"
+        | "int"
+        | "integer"
+        | "bigint"
+        | "smallint"
+        | "tinyint"
+        | "mediumint"
+        | "int2"
+        | "int4"
+        | "int8"
+        | "serial"
+        | "bigserial"
+        | "smallserial"
+        | "pls_integer"
+        | "binary_integer" => ColumnKind::Integer,
+        "number" | "numeric" | "decimal" | "dec" if scale == Some(0) => ColumnKind::Integer,
+        | "number"
+        | "numeric"
+        | "decimal"
+        | "dec"
+        | "real"
+        | "float"
+        | "float4"
+        | "float8"
+        | "double"
+        | "binary_float"
+        | "binary_double"
+        | "money"
+        | "smallmoney" => ColumnKind::Float,
+        "timestamp" | "timestamptz" | "datetime" | "datetime2" | "datetimeoffset" | "smalldatetime" =>
+            ColumnKind::Timestamp,
+        "date" => ColumnKind::Date,
+        "json" | "jsonb" => ColumnKind::Json,
+        "array" => ColumnKind::Array,
+        "long" if rest.trim_start().starts_with("raw") => ColumnKind::Binary,
+        | "bytea"
+        | "blob"
+        | "tinyblob"
+        | "mediumblob"
+        | "longblob"
+        | "binary"
+        | "varbinary"
+        | "image"
+        | "raw"
+        | "bfile" => ColumnKind::Binary,
+        _ => ColumnKind::Text,
+    }
+}
+
+/// Splits a column definition into its unquoted name and declared type.
+/// Returns `None` for table constraints and index definitions.
+fn column_definition(element: &str) -> Option<(String, &str)> {
+    let element = element.trim();
+    let first = element.split_whitespace().next()?.to_uppercase();
+    if
+        [
+            "CONSTRAINT",
+            "PRIMARY",
+            "UNIQUE",
+            "CHECK",
+            "FOREIGN",
+            "KEY",
+            "INDEX",
+            "FULLTEXT",
+            "SPATIAL",
+            "EXCLUDE",
+            "LIKE",
+            "PERIOD",
+        ].contains(&first.as_str())
+    {
+        return None;
+    }
+    let (name, rest) = match element.chars().next()? {
+        open @ ('"' | '`' | '[') => {
+            let close = if open == '[' { ']' } else { open };
+            let end = element[1..].find(close)? + 1;
+            (element[1..end].to_string(), &element[end + 1..])
+        }
+        _ => {
+            let end = element.find(char::is_whitespace).unwrap_or(element.len());
+            (element[..end].to_string(), &element[end..])
+        }
+    };
+    Some((name, rest.trim()))
+}
+
+/// Column names and kinds from the body of a `CREATE TABLE (...)` statement.
+pub fn column_definitions(body: &str) -> TableSchema {
+    split_top_level(body)
+        .into_iter()
+        .filter_map(column_definition)
+        .map(|(name, ty)| (name, column_kind(ty)))
+        .collect()
+}
+
+/// Finds the column types declared by `CREATE TABLE` statements, naming
+/// tables by `table_name` as in `extract_primary_keys`.
+pub fn extract_column_types(statement: &str, table_name: fn(&str) -> String) -> Vec<(String, TableSchema)> {
+    let mut tables = Vec::new();
+    for cap in CREATE_TABLE_RE.captures_iter(statement) {
+        let open = cap.get(0).unwrap().end() - 1;
+        let Some(body) = balanced_body(statement, open) else {
+            continue;
+        };
+        let columns = column_definitions(body);
+        if !columns.is_empty() {
+            tables.push((table_name(&cap[1]), columns));
+        }
+    }
+    tables
+}
+
+/// Column types and foreign keys declared in the dumps of one run. The
+/// parsers fill it in while streaming; the sinks read it when they create a
+/// table, and `--join` suggestions come from its foreign keys.
+#[derive(Debug, Default)]
+pub struct DumpSchema {
+    tables: RwLock<HashMap<String, TableSchema>>,
+    foreign_keys: RwLock<Vec<ForeignKey>>,
+}
+
+impl DumpSchema {
+    /// Records the declared columns of `table`, replacing earlier ones.
+    pub fn register_table(&self, table: &str, schema: TableSchema) {
+        self.tables.write().unwrap().insert(table.to_string(), schema);
+    }
+
+    /// Column types declared for `table`, if any.
+    pub fn table(&self, table: &str) -> Option<TableSchema> {
+        let tables = self.tables.read().unwrap();
+        tables
+            .get(table)
+            .or_else(|| {
+                tables
+                    .iter()
+                    .find(|(t, _)| t.eq_ignore_ascii_case(table))
+                    .map(|(_, s)| s)
+            })
+            .cloned()
+    }
+
+    /// Remembers a foreign key so it can be suggested as a join.
+    pub fn register_foreign_key(&self, key: ForeignKey) {
+        let mut keys = self.foreign_keys.write().unwrap();
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+
+    /// Foreign keys found so far, in discovery order.
+    pub fn foreign_keys(&self) -> Vec<ForeignKey> {
+        self.foreign_keys.read().unwrap().clone()
+    }
+}
+
+/// Bytes of a hex string such as `89504e47`.
+pub fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// `2024-01-02 03:04:05+02` as `2024-01-02T03:04:05+02:00`; other text is kept.
+fn iso_timestamp(text: &str) -> Option<String> {
+    let cap = TIMESTAMP_RE.captures(text.trim())?;
+    let zone = match cap.get(3).map(|m| m.as_str()) {
+        None => String::new(),
+        Some("Z") => "Z".to_string(),
+        Some(z) if z.len() == 3 => format!("{}:00", z),
+        Some(z) if z.len() == 5 => format!("{}:{}", &z[..3], &z[3..]),
+        Some(z) => z.to_string(),
+    };
+    Some(format!("{}T{}{}", &cap[1], &cap[2], zone))
+}
+
+/// Converts a parsed value to the declared kind of its column. Values that do
+/// not fit are left as parsed. Binary data in Postgres `\x` hex form becomes
+/// base64, like the blobs decoded by the MSSQL parser.
+pub fn coerce_value(value: &mut Value, kind: ColumnKind) {
+    let coerced = match (kind, &*value) {
+        (_, Value::Null) => None,
+        (ColumnKind::Text, Value::Number(n)) => Some(Value::String(n.to_string())),
+        (ColumnKind::Text, Value::Array(_) | Value::Object(_)) => Some(Value::String(value.to_string())),
+        (ColumnKind::Text, _) => None,
+        (ColumnKind::Binary, Value::String(s)) =>
+            s.strip_prefix("\\x").map(|hex| {
+                decode_hex(hex).map_or(Value::Null, |bytes| Value::String(STANDARD.encode(bytes)))
+            }),
+        (ColumnKind::Binary, _) => Some(Value::Null),
+        (ColumnKind::Integer, Value::String(s)) => s.trim().parse::<i64>().ok().map(Value::from),
+        (ColumnKind::Integer, Value::Bool(b)) => Some(Value::from(*b as i64)),
+        (ColumnKind::Float, Value::String(s)) =>
+            s.trim().parse::<f64>().ok().and_then(Number::from_f64).map(Value::Number),
+        (ColumnKind::Boolean, Value::String(s)) =>
+            match s.trim().to_lowercase().as_str() {
+                "t" | "true" | "y" | "yes" | "on" | "1" => Some(Value::Bool(true)),
+                "f" | "false" | "n" | "no" | "off" | "0" => Some(Value::Bool(false)),
+                _ => None,
+            }
+        (ColumnKind::Boolean, Value::Number(n)) => n.as_f64().map(|n| Value::Bool(n != 0.0)),
+        (ColumnKind::Timestamp, Value::String(s)) => iso_timestamp(s).map(Value::String),
+        (ColumnKind::Json, Value::String(s)) => serde_json::from_str(s).ok(),
+        (ColumnKind::Array, Value::String(s)) if s.starts_with('{') && s.ends_with('}') => parse_array(s),
+        (ColumnKind::Array, Value::String(s)) if s.starts_with('[') => serde_json::from_str(s).ok(),
+        _ => None,
+    };
+    if let Some(coerced) = coerced {
+        *value = coerced;
+    }
+}
+
+/// Applies `coerce_value` to every field of `record` with a declared type.
+/// With `guess_json`, text of the other fields holding a JSON array or
+/// object becomes that value.
+pub fn coerce_record(record: &mut Value, schema: &[(String, ColumnKind)], guess_json: bool) {
+    let Some(obj) = record.as_object_mut() else {
+        return;
+    };
+    for (key, value) in obj.iter_mut() {
+        if key == "table" {
+            continue;
+        }
+        let kind = schema
+            .iter()
+            .find(|(col, _)| col == key)
+            .or_else(|| schema.iter().find(|(col, _)| col.eq_ignore_ascii_case(key)))
+            .map(|(_, kind)| *kind);
+        match (kind, &*value) {
+            (Some(kind), _) => coerce_value(value, kind),
+            (None, Value::String(text)) if guess_json => {
+                *value = json_text(text.clone());
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KeyColumns {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Loads per-table primary key overrides, e.g. `{ "order_items": ["order_id", "product_id"] }`.
+///
+/// A missing file yields no overrides.
+pub fn load_primary_key_config<P: AsRef<Path>>(path: P) -> HashMap<String, Vec<String>> {
+    let Ok(data) = fs::read_to_string(&path) else {
+        return HashMap::new();
+    };
+    match serde_json::from_str::<HashMap<String, KeyColumns>>(&data) {
+        Ok(map) => {
+            debug!("Loaded primary key overrides for {} tables", map.len());
+            map.into_iter()
+                .map(|(table, cols)| {
+                    let cols = match cols {
+                        KeyColumns::One(col) => vec![col],
+                        KeyColumns::Many(cols) => cols,
+                    };
+                    (table, cols)
+                })
+                .collect()
+        }
+        Err(e) => {
+            warn!("Ignoring invalid primary key config '{}': {}", path.as_ref().display(), e);
+            HashMap::new()
+        }
+    }
+}
+
+pub(crate) fn source_id_part(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Copies the primary key of `record` into its `source_id` field.
+///
+/// `key_columns` are the declared or configured key columns; when absent, a
+/// column named `id` is used. Composite keys are joined with `:`. A key column
+/// named `id` is moved rather than copied so it cannot clash with the vector
+/// ID that sinks store under the same name.
+pub fn assign_source_id(record: &mut Value, key_columns: Option<&[String]>) {
+    let Some(obj) = record.as_object_mut() else {
+        return;
+    };
+
+    let find = |obj: &serde_json::Map<String, Value>, col: &str| {
+        obj.keys()
+            .find(|k| k.eq_ignore_ascii_case(col))
+            .cloned()
+    };
+
+    let keys: Vec<String> = match key_columns {
+        Some(cols) => {
+            let found: Vec<String> = cols
+                .iter()
+                .filter_map(|c| find(obj, c))
+                .collect();
+            if found.len() != cols.len() {
+                return;
+            }
+            found
+        }
+        None =>
+            match find(obj, "id") {
+                Some(k) => vec![k],
+                None => {
+                    return;
+                }
+            }
+    };
+
+    if keys.iter().any(|k| obj[k].is_null()) {
+        return;
+    }
+    let parts: Vec<String> = keys
+        .iter()
+        .map(|k| source_id_part(&obj[k]))
+        .collect();
+    if keys.len() == 1 && keys[0].eq_ignore_ascii_case("id") {
+        obj.remove(&keys[0]);
+    }
+    obj.insert("source_id".to_string(), Value::String(parts.join(":")));
+}
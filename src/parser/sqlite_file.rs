@@ -0,0 +1,224 @@
+use crate::cli::Args;
+use base64::{ engine::general_purpose::STANDARD, Engine as _ };
+use crate::parser::schema::{ column_kind, DumpSchema, ForeignKey };
+use crate::parser::ParseContext;
+use log::{ debug, info };
+use rusqlite::types::ValueRef;
+use rusqlite::{ Connection, OpenFlags };
+use serde_json::{ Map, Number, Value };
+use std::fs::File;
+use std::io::{ self, Read };
+use std::path::Path;
+use std::sync::mpsc::{ sync_channel, Receiver, SyncSender };
+use std::sync::Arc;
+use std::thread;
+
+/// First 16 bytes of every SQLite 3 database file.
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+/// Records read ahead of the consumer.
+const READ_AHEAD: usize = 1024;
+
+/// Returns true if `path` is a SQLite database file rather than a text dump.
+pub fn is_sqlite_database<P: AsRef<Path>>(path: P) -> io::Result<bool> {
+    let mut header = [0u8; 16];
+    match File::open(path)?.read_exact(&mut header) {
+        Ok(()) => Ok(&header == SQLITE_HEADER),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+struct TableInfo {
+    name: String,
+    /// Column names and declared types, in table order.
+    columns: Vec<(String, String)>,
+    primary_key: Vec<String>,
+    /// Single-column foreign keys as (column, referenced table, referenced column).
+    foreign_keys: Vec<(String, String, String)>,
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn read_schema(conn: &Connection) -> rusqlite::Result<Vec<TableInfo>> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' \
+         AND sql NOT LIKE 'CREATE VIRTUAL TABLE%' ORDER BY rowid"
+    )?;
+    let names = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut tables = Vec::new();
+    for name in names {
+        let mut info = conn.prepare(&format!("PRAGMA table_info({})", quote_ident(&name)))?;
+        let rows = info
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, i64>(5)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut primary_key: Vec<(i64, String)> = rows
+            .iter()
+            .filter(|(_, _, pk)| *pk > 0)
+            .map(|(col, _, pk)| (*pk, col.clone()))
+            .collect();
+        primary_key.sort();
+
+        let mut fk = conn.prepare(&format!("PRAGMA foreign_key_list({})", quote_ident(&name)))?;
+        let references = fk
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        // Composite keys share an id; they cannot be expressed as a join spec.
+        let foreign_keys = references
+            .iter()
+            .filter(|(id, ..)| references.iter().filter(|(other, ..)| other == id).count() == 1)
+            .filter_map(|(_, ref_table, from, to)| {
+                to.clone().map(|to| (from.clone(), ref_table.clone(), to))
+            })
+            .collect();
+
+        tables.push(TableInfo {
+            name,
+            columns: rows
+                .into_iter()
+                .map(|(col, ty, _)| (col, ty))
+                .collect(),
+            primary_key: primary_key
+                .into_iter()
+                .map(|(_, col)| col)
+                .collect(),
+            foreign_keys,
+        });
+    }
+    Ok(tables)
+}
+
+/// Converts a stored value using the column's declared type where SQLite's
+/// storage class loses information (booleans are stored as integers).
+fn sqlite_value(value: ValueRef, declared_type: &str) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(n) if declared_type.to_uppercase().contains("BOOL") => Value::Bool(n != 0),
+        ValueRef::Integer(n) => Value::Number(n.into()),
+        ValueRef::Real(f) => Number::from_f64(f).map(Value::Number).unwrap_or(Value::Null),
+        ValueRef::Text(bytes) => Value::String(String::from_utf8_lossy(bytes).into_owned()),
+        ValueRef::Blob(bytes) =>
+            match std::str::from_utf8(bytes) {
+                Ok(text) => Value::String(text.to_string()),
+                Err(_) => Value::String(STANDARD.encode(bytes)),
+            }
+    }
+}
+
+/// Reads every table of `conn` in schema order, sending one record per row.
+/// Returns early when the receiver hangs up.
+fn read_tables(
+    conn: &Connection,
+    tables: &[TableInfo],
+    ctx: &ParseContext,
+    tx: &SyncSender<io::Result<Value>>
+) -> rusqlite::Result<()> {
+    for table in tables {
+        if ctx.ignore_table(&table.name) {
+            info!("Skipping excluded SQLite table: {}", table.name);
+            continue;
+        }
+        if table.columns.is_empty() {
+            continue;
+        }
+
+        let column_list: Vec<String> = table.columns
+            .iter()
+            .map(|(col, _)| quote_ident(col))
+            .collect();
+        let mut stmt = conn.prepare(
+            &format!("SELECT {} FROM {}", column_list.join(", "), quote_ident(&table.name))
+        )?;
+        let mut rows = stmt.query([])?;
+        let mut count = 0;
+        while let Some(row) = rows.next()? {
+            let mut obj = Map::new();
+            obj.insert("table".to_string(), Value::String(table.name.clone()));
+            for (i, (col, declared_type)) in table.columns.iter().enumerate() {
+                obj.insert(col.clone(), sqlite_value(row.get_ref(i)?, declared_type));
+            }
+            let mut record = Value::Object(obj);
+            ctx.finish_record(&mut record);
+            if tx.send(Ok(record)).is_err() {
+                return Ok(());
+            }
+            count += 1;
+        }
+        debug!("Read {} rows from SQLite table '{}'", count, table.name);
+    }
+    Ok(())
+}
+
+/// Records read from a SQLite database file on a background thread.
+pub struct SqliteRecords {
+    rx: Receiver<io::Result<Value>>,
+}
+
+impl Iterator for SqliteRecords {
+    type Item = io::Result<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rx.recv().ok()
+    }
+}
+
+/// Streams the rows of every table in a SQLite database file as records shaped
+/// like those of the text dump parser. The file is opened read-only. Column
+/// types and foreign keys of its tables are added to `schema`.
+pub fn stream_sqlite_database<P: AsRef<Path>>(
+    path: P,
+    args: &Args,
+    schema: Arc<DumpSchema>
+) -> io::Result<SqliteRecords> {
+    let conn = Connection::open_with_flags(
+        path.as_ref(),
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX
+    ).map_err(io::Error::other)?;
+    let tables = read_schema(&conn).map_err(io::Error::other)?;
+    info!("Reading {} tables from SQLite database {}", tables.len(), path.as_ref().display());
+
+    let mut ctx = ParseContext::new(args);
+    ctx.schema = schema;
+    for table in &tables {
+        if !table.primary_key.is_empty() {
+            ctx.primary_keys.insert(table.name.clone(), table.primary_key.clone());
+        }
+        let types = table.columns
+            .iter()
+            // Columns declared without a type hold anything, JSON included.
+            .filter(|(_, declared_type)| !declared_type.trim().is_empty())
+            .map(|(col, declared_type)| (col.clone(), column_kind(declared_type)))
+            .collect();
+        ctx.register_column_types(&table.name, types);
+        for (column, ref_table, ref_column) in &table.foreign_keys {
+            ctx.schema.register_foreign_key(ForeignKey {
+                table: table.name.clone(),
+                column: column.clone(),
+                ref_table: ref_table.clone(),
+                ref_column: ref_column.clone(),
+            });
+        }
+    }
+
+    let (tx, rx) = sync_channel(READ_AHEAD);
+    thread::spawn(move || {
+        if let Err(e) = read_tables(&conn, &tables, &ctx, &tx) {
+            let _ = tx.send(Err(io::Error::other(format!("Failed to read SQLite database: {}", e))));
+        }
+    });
+    Ok(SqliteRecords { rx })
+}
@@ -0,0 +1,477 @@
+use log::{ debug, info };
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::io::{ self, BufRead };
+use std::sync::Arc;
+use crate::cli::Args;
+use crate::parser::diagnostics::report_rejection;
+use crate::parser::parse_regex::oracle::q_quote_close;
+use crate::parser::registry::sql_dialect;
+use crate::parser::{ parse_with_regex, ParseContext };
+use crate::parser::schema::{
+    extract_column_types,
+    extract_foreign_keys,
+    extract_primary_keys,
+    normalize_identifier,
+    DumpSchema,
+};
+
+/// Number of COPY data rows handed to the Postgres parser at a time.
+const COPY_ROWS_PER_CHUNK: usize = 1000;
+
+static COPY_FROM_STDIN_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?is)^COPY\s.+\sFROM\s+STDIN\b").unwrap()
+});
+
+/// Splits a dump into complete statements without loading the whole file.
+///
+/// Statements end at a `;` outside of quotes and comments. MSSQL scripts are
+/// also split at line ends (they rarely use `;`), and Postgres `COPY ... FROM
+/// stdin;` blocks are re-emitted as self-contained COPY chunks of at most
+/// `COPY_ROWS_PER_CHUNK` rows. Comments preceding a statement stay attached
+/// to it so parsers can still read markers like `-- TABLE DATA: <name>`.
+pub struct StatementReader<R: BufRead> {
+    reader: R,
+    format: String,
+    buffer: String,
+    /// Statements ready to be returned, with their line anchors.
+    ready: VecDeque<(LineAnchors, String)>,
+    /// Lines read so far.
+    lines_read: usize,
+    /// Line the statement in `buffer` starts on.
+    buffer_line: usize,
+    /// Line anchors of the statement last returned.
+    statement_lines: LineAnchors,
+    quote: Option<char>,
+    /// Closing delimiter of an open Oracle `q'[...]'` literal.
+    q_quote: Option<char>,
+    dollar_tag: Option<String>,
+    in_block_comment: bool,
+    depth: i32,
+    copy_header: Option<String>,
+    /// Pending COPY data rows with the line each was read from.
+    copy_rows: Vec<(usize, String)>,
+    eof: bool,
+}
+
+/// `(byte offset, line)` pairs placing a statement in the input: the text
+/// from each offset on starts at that line. COPY chunks have one per row,
+/// other statements one for their first non-blank character.
+type LineAnchors = Vec<(usize, usize)>;
+
+impl<R: BufRead> StatementReader<R> {
+    pub fn new(reader: R, format: &str) -> Self {
+        StatementReader {
+            reader,
+            format: format.to_string(),
+            buffer: String::new(),
+            ready: VecDeque::new(),
+            lines_read: 0,
+            buffer_line: 0,
+            statement_lines: Vec::new(),
+            quote: None,
+            q_quote: None,
+            dollar_tag: None,
+            in_block_comment: false,
+            depth: 0,
+            copy_header: None,
+            copy_rows: Vec::new(),
+            eof: false,
+        }
+    }
+
+    /// 1-based line of the input where the statement last returned starts.
+    pub fn line(&self) -> usize {
+        self.statement_lines.first().map_or(0, |&(_, line)| line)
+    }
+
+    /// 1-based line of the input holding byte `offset` of `statement`, the
+    /// statement last returned.
+    pub fn line_at(&self, statement: &str, offset: usize) -> usize {
+        let Some(&(start, line)) = self.statement_lines.iter().rev().find(|(start, _)| *start <= offset) else {
+            return self.line();
+        };
+        line + statement.get(start..offset).map_or(0, |text| text.matches('\n').count())
+    }
+
+    fn backslash_escapes(&self) -> bool {
+        matches!(self.format.as_str(), "mysql" | "surreal")
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut bytes = Vec::new();
+        if self.reader.read_until(b'\n', &mut bytes)? == 0 {
+            return Ok(None);
+        }
+        self.lines_read += 1;
+        String::from_utf8(bytes)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn scan_line(&mut self, line: &str) {
+        let chars: Vec<(usize, char)> = line.char_indices().collect();
+        let mut start = 0;
+        let mut i = 0;
+
+        while i < chars.len() {
+            let (pos, c) = chars[i];
+            let next = chars.get(i + 1).map(|&(_, n)| n);
+
+            if self.in_block_comment {
+                if c == '*' && next == Some('/') {
+                    self.in_block_comment = false;
+                    i += 1;
+                }
+                i += 1;
+                continue;
+            }
+
+            if let Some(tag) = &self.dollar_tag {
+                if line[pos..].starts_with(tag.as_str()) {
+                    i += tag.chars().count();
+                    self.dollar_tag = None;
+                } else {
+                    i += 1;
+                }
+                continue;
+            }
+
+            if let Some(close) = self.q_quote {
+                if c == close && next == Some('\'') {
+                    self.q_quote = None;
+                    i += 1;
+                }
+                i += 1;
+                continue;
+            }
+
+            if let Some(q) = self.quote {
+                if c == '\\' && self.backslash_escapes() {
+                    i += 2;
+                    continue;
+                }
+                if c == q {
+                    self.quote = None;
+                }
+                i += 1;
+                continue;
+            }
+
+            match c {
+                '-' if next == Some('-') => {
+                    break;
+                }
+                '/' if next == Some('*') => {
+                    self.in_block_comment = true;
+                    i += 1;
+                }
+                'q' | 'Q' if self.format == "oracle" && next == Some('\'') => {
+                    if let Some(&(_, open)) = chars.get(i + 2) {
+                        self.q_quote = Some(q_quote_close(open));
+                        i += 3;
+                        continue;
+                    }
+                }
+                '\'' | '"' | '`' => {
+                    self.quote = Some(c);
+                }
+                '$' if self.format == "postgres" => {
+                    if let Some(tag) = dollar_quote_tag(&line[pos..]) {
+                        i += tag.chars().count();
+                        self.dollar_tag = Some(tag);
+                        continue;
+                    }
+                }
+                '(' => {
+                    self.depth += 1;
+                }
+                ')' => {
+                    self.depth -= 1;
+                }
+                ';' => {
+                    let end = pos + c.len_utf8();
+                    self.push_text(&line[start..end]);
+                    start = end;
+                    self.finish_statement();
+                    if self.copy_header.is_some() {
+                        return;
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+
+        self.push_text(&line[start..]);
+
+        if
+            self.format == "mssql" &&
+            self.quote.is_none() &&
+            self.q_quote.is_none() &&
+            !self.in_block_comment &&
+            self.depth <= 0
+        {
+            self.finish_statement();
+        }
+    }
+
+    fn push_text(&mut self, text: &str) {
+        if self.buffer.trim().is_empty() && !text.trim().is_empty() {
+            self.buffer_line = self.lines_read;
+        }
+        self.buffer.push_str(text);
+    }
+
+    fn finish_statement(&mut self) {
+        let statement = std::mem::take(&mut self.buffer);
+        self.depth = 0;
+        if statement.trim().is_empty() {
+            return;
+        }
+
+        if self.format == "postgres" {
+            let code = strip_leading_comments(&statement);
+            if is_copy_from_stdin(code) {
+                debug!("Entering COPY block: {}", code.trim());
+                self.copy_header = Some(code.trim().to_string());
+                return;
+            }
+        }
+
+        let start = statement.len() - statement.trim_start().len();
+        self.ready.push_back((vec![(start, self.buffer_line)], statement));
+    }
+
+    fn handle_copy_line(&mut self, line: &str) {
+        let row = line.trim_end_matches(['\n', '\r']);
+        if row == "\\." {
+            self.flush_copy_rows();
+            self.copy_header = None;
+            return;
+        }
+        self.copy_rows.push((self.lines_read, row.to_string()));
+        if self.copy_rows.len() >= COPY_ROWS_PER_CHUNK {
+            self.flush_copy_rows();
+        }
+    }
+
+    fn flush_copy_rows(&mut self) {
+        if self.copy_rows.is_empty() {
+            return;
+        }
+        if let Some(header) = &self.copy_header {
+            let mut chunk = header.clone();
+            let mut anchors = Vec::with_capacity(self.copy_rows.len());
+            for (line, row) in self.copy_rows.drain(..) {
+                chunk.push('\n');
+                anchors.push((chunk.len(), line));
+                chunk.push_str(&row);
+            }
+            chunk.push_str("\n\\.");
+            self.ready.push_back((anchors, chunk));
+        }
+        self.copy_rows.clear();
+    }
+}
+
+impl<R: BufRead> Iterator for StatementReader<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((lines, statement)) = self.ready.pop_front() {
+                self.statement_lines = lines;
+                return Some(Ok(statement));
+            }
+            if self.eof {
+                return None;
+            }
+
+            match self.read_line() {
+                Ok(Some(line)) => {
+                    if self.copy_header.is_some() {
+                        self.handle_copy_line(&line);
+                    } else {
+                        self.scan_line(&line);
+                    }
+                }
+                Ok(None) => {
+                    self.eof = true;
+                    if self.copy_header.is_some() {
+                        self.flush_copy_rows();
+                        self.copy_header = None;
+                    } else {
+                        self.finish_statement();
+                    }
+                }
+                Err(e) => {
+                    self.eof = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+fn dollar_quote_tag(text: &str) -> Option<String> {
+    let rest = text.strip_prefix('$')?;
+    let end = rest.find('$')?;
+    let tag = &rest[..end];
+    if tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') &&
+        !tag.starts_with(|c: char| c.is_ascii_digit())
+    {
+        Some(format!("${}$", tag))
+    } else {
+        None
+    }
+}
+
+fn strip_leading_comments(statement: &str) -> &str {
+    let mut rest = statement;
+    loop {
+        let trimmed = rest.trim_start();
+        if trimmed.starts_with("--") {
+            match trimmed.find('\n') {
+                Some(nl) => {
+                    rest = &trimmed[nl + 1..];
+                }
+                None => {
+                    return "";
+                }
+            }
+        } else {
+            return trimmed;
+        }
+    }
+}
+
+/// `COPY <table> [(...)] FROM stdin [WITH ...];`, the start of inline data.
+fn is_copy_from_stdin(code: &str) -> bool {
+    COPY_FROM_STDIN_RE.is_match(code.trim_end())
+}
+
+/// Lazily parses records out of a dump, one statement at a time.
+///
+/// Memory use is bounded by the largest single statement (or COPY chunk)
+/// rather than by the size of the dump.
+pub struct RecordStream<R: BufRead> {
+    statements: StatementReader<R>,
+    format: String,
+    args: Args,
+    ctx: ParseContext,
+    pending: VecDeque<Value>,
+    statement_index: usize,
+    /// Input named in rejection reports.
+    source: String,
+    /// The dialect's name for tables declared in DDL.
+    table_name: fn(&str) -> String,
+}
+
+impl<R: BufRead> RecordStream<R> {
+    /// Names the input in rejection reports, e.g. with its file path.
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source = source.to_string();
+        self
+    }
+
+    /// Collects the column types and foreign keys declared in the dump into
+    /// `schema` instead of a schema of its own.
+    pub fn with_schema(mut self, schema: Arc<DumpSchema>) -> Self {
+        self.ctx.schema = schema;
+        self
+    }
+
+    /// Column types and foreign keys declared in the statements read so far.
+    pub fn schema(&self) -> &Arc<DumpSchema> {
+        &self.ctx.schema
+    }
+}
+
+impl<R: BufRead> Iterator for RecordStream<R> {
+    type Item = io::Result<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.pending.pop_front() {
+                return Some(Ok(record));
+            }
+
+            let statement = match self.statements.next()? {
+                Ok(statement) => statement,
+                Err(e) => {
+                    return Some(Err(e));
+                }
+            };
+            let index = self.statement_index;
+            self.statement_index += 1;
+
+            for (table, columns) in extract_primary_keys(&statement, self.table_name) {
+                debug!("Primary key for table '{}': {:?}", table, columns);
+                self.ctx.primary_keys.insert(table, columns);
+            }
+            for (table, columns) in extract_column_types(&statement, self.table_name) {
+                self.ctx.register_column_types(&table, columns);
+            }
+            for key in extract_foreign_keys(&statement, self.table_name) {
+                debug!("Foreign key: {}", key);
+                self.ctx.schema.register_foreign_key(key);
+            }
+
+            let parsed = parse_with_regex(&statement, &self.format, &mut self.ctx);
+            // Rejections come in statement order; each row is found after the previous one.
+            let mut searched = 0;
+            for mut rejection in self.ctx.rejections.drain(..) {
+                let offset = match statement.get(searched..).and_then(|rest| rest.find(&rejection.raw)) {
+                    Some(i) => {
+                        searched += i + rejection.raw.len();
+                        searched - rejection.raw.len()
+                    }
+                    None => 0,
+                };
+                rejection.file = self.source.clone();
+                rejection.line = self.statements.line_at(&statement, offset);
+                report_rejection(&rejection);
+            }
+
+            let Some(mut records) = parsed else {
+                if self.args.debug {
+                    debug!(
+                        "No records in statement {} (truncated): {}",
+                        index,
+                        statement.chars().take(1000).collect::<String>()
+                    );
+                }
+                continue;
+            };
+
+            for record in &mut records {
+                self.ctx.finish_record(record);
+            }
+
+            if self.args.debug {
+                for (j, rec) in records.iter().enumerate() {
+                    debug!("Debug: Record {} in statement {}: {}", j, index, rec);
+                }
+            }
+            self.pending.extend(records);
+        }
+    }
+}
+
+/// Returns an iterator of parsed records read incrementally from `reader`.
+pub fn stream_database_export<R: BufRead>(reader: R, format: &str, args: &Args) -> RecordStream<R> {
+    info!("Streaming {} records from dump", format);
+    RecordStream {
+        statements: StatementReader::new(reader, format),
+        format: format.to_string(),
+        args: args.clone(),
+        ctx: ParseContext::new(args),
+        pending: VecDeque::new(),
+        statement_index: 0,
+        source: String::new(),
+        table_name: sql_dialect(format).map_or(normalize_identifier, |dialect| dialect.table_name),
+    }
+}
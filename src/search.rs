@@ -0,0 +1,76 @@
+use crate::cli::{ Args, SearchArgs };
+use crate::db::{ Database, DbError, SearchFilter, SearchHit };
+use crate::embedding::embeding::initialize_embedding_generator;
+use crate::util::handle_tei::start_and_wait_for_tei;
+use log::info;
+use serde_json::Value;
+use tokio::runtime::Runtime;
+
+/// Parses `--filter`, a JSON object of metadata fields to match exactly.
+pub fn parse_filter(filter: Option<&str>) -> Result<Option<SearchFilter>, DbError> {
+    let Some(filter) = filter.map(str::trim).filter(|f| !f.is_empty()) else {
+        return Ok(None);
+    };
+    match serde_json::from_str::<Value>(filter) {
+        Ok(Value::Object(map)) => Ok(Some(map)),
+        Ok(_) => Err("--filter must be a JSON object, e.g. '{\"category\":\"books\"}'".into()),
+        Err(e) => Err(format!("Invalid --filter JSON: {}", e).into()),
+    }
+}
+
+/// Embeds `search.query` with the same provider settings as a migration and
+/// returns the `top_k` nearest records of `search.table`.
+pub fn execute_search(
+    args: &Args,
+    search: &SearchArgs,
+    database: &dyn Database,
+) -> Result<Vec<SearchHit>, DbError> {
+    let filter = parse_filter(search.filter.as_deref())?;
+
+    let mut tei_process = None;
+    let mut override_url = None;
+    if args.embedding_provider == "tei" && args.embedding_url.is_none() {
+        let (proc, url) = start_and_wait_for_tei(args)?;
+        tei_process = Some(proc);
+        override_url = Some(url);
+    }
+
+    let generator = initialize_embedding_generator(args, override_url.as_deref())
+        .map_err(|e| DbError::from(format!("Init embed gen failed: {}", e)))?;
+    let rt = Runtime::new()?;
+    let embedded = rt.block_on(generator.generate_embeddings_batch(std::slice::from_ref(&search.query)));
+
+    if let Some(mut p) = tei_process {
+        let _ = p.kill();
+    }
+
+    let vector = embedded?
+        .into_iter()
+        .next()
+        .ok_or("Embedding provider returned no vector for the query")?;
+    if vector.len() != args.dimension {
+        return Err(
+            format!(
+                "Query embedding has {} dimensions but --dimension is {}",
+                vector.len(),
+                args.dimension
+            ).into()
+        );
+    }
+
+    info!("Searching '{}' for the {} nearest records", search.table, search.top_k);
+    database.search(&search.table, &vector, search.top_k, filter.as_ref())
+}
+
+pub fn print_search_results(hits: &[SearchHit]) {
+    if hits.is_empty() {
+        println!("No results.");
+        return;
+    }
+    for (rank, hit) in hits.iter().enumerate() {
+        println!("{:>3}. {}  (score {:.4})", rank + 1, hit.id, hit.score);
+        if !hit.metadata.is_null() {
+            println!("     {}", hit.metadata);
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use serde::{ Deserialize, Serialize };
+use sha2::{ Digest, Sha256 };
+use std::collections::{ BTreeMap, BTreeSet, HashMap };
+use std::fs::{ self, File };
+use std::io::{ self, Read, Seek, SeekFrom };
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use log::{ debug, info };
+use crate::cli::Args;
+use crate::db::{ DbError, OutputParts };
+use crate::util::exclude::EXCLUDE_FILE;
+use crate::util::utils::resolve_dump_files;
+
+/// Bumped whenever the meaning of `records_committed` changes.
+const CHECKPOINT_VERSION: u32 = 3;
+
+/// Bytes hashed from the start and from the end of each dump file.
+const SAMPLE_BYTES: u64 = 1024 * 1024;
+
+/// Progress of a migration, persisted after every stored batch.
+///
+/// `records_committed` counts records in dump order, so a resumed run can skip
+/// exactly the records that already reached the vector database.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub version: u32,
+    pub dump_file: String,
+    pub dump_fingerprint: String,
+    pub target: BTreeMap<String, String>,
+    pub records_committed: usize,
+    /// Vectors stored per table; one per chunk with `--text-chunking`.
+    pub vectors: BTreeMap<String, usize>,
+    /// Last closed part file per table, for sinks that write numbered parts.
+    #[serde(default)]
+    pub parts: OutputParts,
+}
+
+impl Checkpoint {
+    /// Creates an empty checkpoint for the dump and target described by `args`.
+    pub fn new(args: &Args) -> Result<Self, DbError> {
+        let dump_fingerprint = dump_fingerprint(&args.dump_file).map_err(|e|
+            format!("Failed to fingerprint dump file '{}': {}", args.dump_file, e)
+        )?;
+        Ok(Checkpoint {
+            version: CHECKPOINT_VERSION,
+            dump_file: args.dump_file.clone(),
+            dump_fingerprint,
+            target: target_config(args),
+            records_committed: 0,
+            vectors: BTreeMap::new(),
+            parts: OutputParts::new(),
+        })
+    }
+
+    /// Reads a checkpoint, returning `None` when the file does not exist.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Option<Self>, DbError> {
+        let data = match fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(None);
+            }
+            Err(e) => {
+                return Err(e.into());
+            }
+        };
+        let checkpoint = serde_json
+            ::from_str(&data)
+            .map_err(|e| format!("Invalid checkpoint file '{}': {}", path.as_ref().display(), e))?;
+        Ok(Some(checkpoint))
+    }
+
+    /// Writes the checkpoint atomically (temp file + rename).
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), DbError> {
+        let path = path.as_ref();
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&tmp, path)?;
+        debug!("Checkpoint saved: {} records committed", self.records_committed);
+        Ok(())
+    }
+
+    /// Removes the checkpoint file once a migration has completed.
+    pub fn remove<P: AsRef<Path>>(path: P) -> Result<(), DbError> {
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                info!("Removed checkpoint file {}", path.as_ref().display());
+                Ok(())
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Fails if `current` describes a different dump or target than `self`.
+    pub fn ensure_matches(&self, current: &Checkpoint) -> Result<(), DbError> {
+        if self.version != current.version {
+            return Err(
+                format!(
+                    "Checkpoint version {} is not supported (expected {})",
+                    self.version,
+                    current.version
+                ).into()
+            );
+        }
+        if self.dump_fingerprint != current.dump_fingerprint {
+            return Err(
+                format!(
+                    "Dump file '{}' has changed since the checkpoint was written; refusing to resume",
+                    current.dump_file
+                ).into()
+            );
+        }
+        let keys: BTreeSet<&String> = self.target.keys().chain(current.target.keys()).collect();
+        let changed: Vec<&str> = keys
+            .into_iter()
+            .filter(|k| self.target.get(*k) != current.target.get(*k))
+            .map(String::as_str)
+            .collect();
+        if !changed.is_empty() {
+            return Err(
+                format!(
+                    "Target configuration differs from the checkpoint ({}); refusing to resume",
+                    changed.join(", ")
+                ).into()
+            );
+        }
+        Ok(())
+    }
+
+    /// Records a fully stored batch of `count` records and the vectors it
+    /// stored per table.
+    pub fn commit_batch(&mut self, count: usize, vector_counts: &HashMap<String, usize>) {
+        self.records_committed += count;
+        for (table, n) in vector_counts {
+            *self.vectors.entry(table.clone()).or_default() += n;
+        }
+    }
+}
+
+/// Settings that change what ends up in the vector database. Resuming with any
+/// of them altered would mix incompatible records, so they are pinned.
+///
+/// CSV header handling and cell type inference, and the JSON table-map
+/// unwrapping, take no settings beyond the ones below: they depend only on the
+/// file contents, which the dump fingerprint covers.
+fn target_config(args: &Args) -> BTreeMap<String, String> {
+    [
+        ("vector_export_type", args.vector_export_type.clone()),
+        ("vector_host", args.vector_host.clone()),
+        ("database", args.database.clone()),
+        ("namespace", args.namespace.clone()),
+        ("tenant", args.tenant.clone()),
+        ("indexes", args.indexes.clone()),
+        ("dimension", args.dimension.to_string()),
+        ("metric", args.metric.clone()),
+        ("group_redis", args.group_redis.to_string()),
+        ("embedding_provider", args.embedding_provider.clone()),
+        ("embedding_model", args.embedding_model.clone()),
+        ("use_exclude", args.use_exclude.to_string()),
+        ("text_chunking", args.text_chunking.clone()),
+        ("text_chunk_size", args.text_chunk_size.map(|n| n.to_string()).unwrap_or_default()),
+        ("text_chunk_overlap", args.text_chunk_overlap.map(|n| n.to_string()).unwrap_or_default()),
+        ("json_table", args.json_table.clone().unwrap_or_default()),
+        ("json_table_field", args.json_table_field.clone()),
+        ("csv_delimiter", args.csv_delimiter.clone().unwrap_or_default()),
+        ("format", args.format.clone().unwrap_or_default()),
+        ("templates_file", args.templates_file.clone()),
+        ("templates_sha256", file_sha256(&args.templates_file)),
+        ("joins", args.joins.join("; ")),
+        ("primary_keys_file", args.primary_keys_file.clone()),
+        ("primary_keys_sha256", file_sha256(&args.primary_keys_file)),
+        ("exclude_sha256", if args.use_exclude { file_sha256(EXCLUDE_FILE) } else { String::new() }),
+        ("output_dir", args.output_dir.clone()),
+        ("pg_index", args.pg_index.clone()),
+        ("pg_insert_mode", args.pg_insert_mode.clone()),
+        ("pg_typed_columns", args.pg_typed_columns.to_string()),
+        ("embedding_url", args.embedding_url.clone().unwrap_or_default()),
+        ("embedding_send_dimensions", args.embedding_send_dimensions.to_string()),
+        ("reject_file", args.reject_file.clone().unwrap_or_default()),
+        ("max_reject_ratio", args.max_reject_ratio.map(|r| r.to_string()).unwrap_or_default()),
+    ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect()
+}
+
+/// SHA-256 of a small settings file such as the templates, or "" when it cannot be read.
+fn file_sha256(path: &str) -> String {
+    fs::read(path).map_or_else(|_| String::new(), |data| format!("{:x}", Sha256::digest(data)))
+}
+
+/// SHA-256 over the size, modification time and first and last
+/// `SAMPLE_BYTES` of the dump, so large dumps are not read in full on every
+/// run. When `spec` is a directory or glob, the names of its files are hashed
+/// too, in order.
+pub fn dump_fingerprint(spec: &str) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    let files = resolve_dump_files(spec)?;
+    if let [file] = files.as_slice() && file.as_path() == Path::new(spec) {
+        fingerprint_file_into(file, &mut hasher)?;
+    } else {
+        for file in &files {
+            hasher.update(file.file_name().unwrap_or_default().as_encoded_bytes());
+            fingerprint_file_into(file, &mut hasher)?;
+        }
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn fingerprint_file_into(path: &Path, hasher: &mut Sha256) -> io::Result<()> {
+    let mut file = File::open(path)?;
+    let metadata = file.metadata()?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |elapsed| elapsed.as_nanos());
+    hasher.update(metadata.len().to_le_bytes());
+    hasher.update(modified.to_le_bytes());
+
+    let mut sample = Vec::new();
+    file.by_ref().take(SAMPLE_BYTES).read_to_end(&mut sample)?;
+    if metadata.len() > SAMPLE_BYTES {
+        file.seek(SeekFrom::Start(SAMPLE_BYTES.max(metadata.len() - SAMPLE_BYTES)))?;
+        file.read_to_end(&mut sample)?;
+    }
+    hasher.update(&sample);
+    Ok(())
+}
@@ -1,8 +1,10 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
 use serde::Deserialize;
 use serde_json::Value;
 use std::{collections::HashMap, fs, path::Path};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 #[serde(default)]
 pub struct ExcludeEntry {
     pub table: String,
@@ -11,16 +13,6 @@ pub struct ExcludeEntry {
     pub exclude_fields: HashMap<String, FieldExclude>,
 }
 
-impl Default for ExcludeEntry {
-    fn default() -> Self {
-        ExcludeEntry {
-            table: String::new(),
-            ignore_table: false,
-            exclude_fields: HashMap::new(),
-        }
-    }
-}
-
 #[derive(Debug, Deserialize)]
 #[serde(untagged)]
 pub enum FieldExclude {
@@ -28,6 +20,12 @@ pub enum FieldExclude {
     Sub(Vec<String>),
 }
 
+/// Field exclusions applied with `--use-exclude`.
+pub const EXCLUDE_FILE: &str = "config/exclude.json";
+
+static TRAILING_COMMA_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r",\s*}").unwrap());
+static DOUBLE_COMMA_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r",\s*,").unwrap());
+
 pub struct Excluder {
     entries: HashMap<String, ExcludeEntry>,
 }
@@ -57,51 +55,45 @@ impl Excluder {
             None => return,
         };
         
-        if let Some(entry) = self.entries.get(table) {
-            if let Value::Object(map) = record {
-                for (field, rule) in &entry.exclude_fields {
-                    match rule {
-                        FieldExclude::All(true) => {
-                            map.remove(field);
-                        }
-                        FieldExclude::Sub(keys) => {
-                            if let Some(Value::Object(sub_map)) = map.get_mut(field) {
-                                for k in keys {
-                                    sub_map.remove(k);
-                                }
-                            } 
-                         
-                            else if let Some(Value::String(obj_str)) = map.get_mut(field) {
-                                if obj_str.trim().starts_with('{') && obj_str.trim().ends_with('}') {
-                                    for key in keys {
-                                        let patterns = [
-                                            format!("{}:\\s*[^,}}]+,", regex::escape(key)),
-                                            format!("{}:\\s*[^,}}]+}}", regex::escape(key)),
-                                            format!("\"{}\":\\s*[^,}}]+,", regex::escape(key)),
-                                            format!("'{}\':\\s*[^,}}]+,", regex::escape(key)),
-                                        ];
-                                        
-                                        for pattern in patterns {
-                                            if let Ok(re) = regex::Regex::new(&pattern) {
-                                                *obj_str = re.replace(obj_str, "").to_string();
-                                            }
-                                        }
-                                        
-                                        if let Ok(re) = regex::Regex::new(r",\s*}") {
-                                            *obj_str = re.replace(obj_str, "}").to_string();
-                                        }
-                                        if let Ok(re) = regex::Regex::new(r",\s*,") {
-                                            *obj_str = re.replace(obj_str, ",").to_string();
-                                        }
+        if let Some(entry) = self.entries.get(table) && let Value::Object(map) = record {
+            for (field, rule) in &entry.exclude_fields {
+                match rule {
+                    FieldExclude::All(true) => {
+                        map.remove(field);
+                    }
+                    FieldExclude::Sub(keys) => {
+                        if let Some(Value::Object(sub_map)) = map.get_mut(field) {
+                            for k in keys {
+                                sub_map.remove(k);
+                            }
+                        } else if
+                            let Some(Value::String(obj_str)) = map.get_mut(field) &&
+                            obj_str.trim().starts_with('{') &&
+                            obj_str.trim().ends_with('}')
+                        {
+                            for key in keys {
+                                let patterns = [
+                                    format!("{}:\\s*[^,}}]+,", regex::escape(key)),
+                                    format!("{}:\\s*[^,}}]+}}", regex::escape(key)),
+                                    format!("\"{}\":\\s*[^,}}]+,", regex::escape(key)),
+                                    format!("'{}\':\\s*[^,}}]+,", regex::escape(key)),
+                                ];
+                                
+                                for pattern in patterns {
+                                    if let Ok(re) = Regex::new(&pattern) {
+                                        *obj_str = re.replace(obj_str, "").to_string();
                                     }
                                 }
+                                
+                                *obj_str = TRAILING_COMMA_RE.replace(obj_str, "}").to_string();
+                                *obj_str = DOUBLE_COMMA_RE.replace(obj_str, ",").to_string();
                             }
-                        },
-                        _ => {}
-                    }
+                        }
+                    },
+                    _ => {}
                 }
             }
         }
     }
 
-}
\ No newline at end of file
+}
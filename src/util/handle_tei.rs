@@ -5,6 +5,8 @@ use crate::{cli::Args, util::spinner::start_operation_animation};
 use std::sync::atomic::Ordering;
 use std::sync::mpsc; 
 
+/// A child process that is killed when dropped, so it cannot outlive the
+/// migration that started it however that migration ends.
 pub struct ManagedProcess {
     child: Child,
     name: String,
@@ -21,15 +23,20 @@ impl ManagedProcess {
         self.child.id()
     }
 
+    /// Kills the process and waits for it to exit; does nothing once it has.
     pub fn kill(&mut self) -> Result<(), Box<dyn StdError + Send + Sync>> {
-        info!("Manually terminating process '{}' (PID: {})", self.name, self.child.id());
-        match self.child.kill() {
+        if let Ok(Some(status)) = self.child.try_wait() {
+            info!("Process '{}' has already exited ({})", self.name, status);
+            return Ok(());
+        }
+        info!("Terminating process '{}' (PID: {})", self.name, self.child.id());
+        match self.child.kill().and_then(|_| self.child.wait()) {
             Ok(_) => {
-                info!("Successfully sent kill signal to process '{}'", self.name);
+                info!("Terminated process '{}'", self.name);
                 Ok(())
             }
             Err(e) => {
-                let err = format!("Failed to kill process '{}': {}", self.name, e);
+                let err = format!("Failed to kill process '{}' (PID: {}): {}", self.name, self.child.id(), e);
                 error!("{}", err);
                 Err(err.into())
             }
@@ -39,14 +46,8 @@ impl ManagedProcess {
 
 impl Drop for ManagedProcess {
     fn drop(&mut self) {
-        info!("Attempting to terminate managed process '{}' (PID: {})", self.name, self.child.id());
-        match self.child.kill() { 
-            Ok(_) => {
-                info!("Successfully sent kill signal to process '{}'", self.name);
-            }
-            Err(e) =>
-                error!("Failed to kill process '{}' (PID: {}): {}", self.name, self.child.id(), e),
-        }
+        // Failures are logged by `kill`.
+        let _ = self.kill();
     }
 }
 
@@ -115,22 +116,18 @@ pub fn start_and_wait_for_tei(
 
     thread::spawn(move || {
         let reader = BufReader::new(stdout);
-        for line in reader.lines() {
-            if let Ok(line) = line {
-                if let Err(_) = tx.send(line) {
-                    break;
-                }
+        for line in reader.lines().map_while(Result::ok) {
+            if tx.send(line).is_err() {
+                break;
             }
         }
     });
 
     thread::spawn(move || {
         let reader = BufReader::new(stderr);
-        for line in reader.lines() {
-            if let Ok(line) = line {
-                if let Err(_) = tx_stderr.send(line) {
-                    break;
-                }
+        for line in reader.lines().map_while(Result::ok) {
+            if tx_stderr.send(line).is_err() {
+                break;
             }
         }
     });
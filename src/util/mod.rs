@@ -3,5 +3,6 @@ pub mod spinner;
 pub use utils::*;
 pub mod handle_tei;
 pub mod exclude;
+pub mod checkpoint;
 pub use handle_tei::ManagedProcess;
 pub use handle_tei::start_and_wait_for_tei;
@@ -18,6 +18,7 @@ impl AnimationHandle {
     }
 }
 
+/// Starts a progress spinner; a `total` of 0 means the total is unknown.
 pub fn start_spinner_animation(
     counter: Arc<AtomicUsize>,
     total: usize,
@@ -35,14 +36,18 @@ pub fn start_spinner_animation(
             let count = counter.load(Ordering::Relaxed);
             spinner_idx = (spinner_idx + 1) % spinner_chars.len();
 
-            print!(
-                "\r{} {}... [{}/{}] ({}%)",
-                spinner_chars[spinner_idx],
-                message,
-                count,
-                total,
-                (count * 100) / total.max(1)
-            );
+            if total == 0 {
+                print!("\r{} {}... [{}]", spinner_chars[spinner_idx], message, count);
+            } else {
+                print!(
+                    "\r{} {}... [{}/{}] ({}%)",
+                    spinner_chars[spinner_idx],
+                    message,
+                    count,
+                    total,
+                    (count * 100) / total
+                );
+            }
 
             let _ = stdout().flush();
             thread::sleep(Duration::from_millis(80));
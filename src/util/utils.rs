@@ -1,41 +1,173 @@
-use std::fs;
-use std::io::{ Cursor, Read, Result as IoResult };
-use std::path::Path;
+use std::fs::{ self, File };
+use std::io::{ BufRead, BufReader, Cursor, Read, Result as IoResult };
+use std::path::{ Path, PathBuf };
 use encoding_rs::UTF_16LE;
 use encoding_rs_io::DecodeReaderBytesBuilder;
 use log::info;
-use crate::parser::detect_format;
+use crate::parser::detect_format_checked;
 
+/// Bytes of the dump inspected by `detect_format` when streaming.
+const FORMAT_SAMPLE_BYTES: u64 = 1024 * 1024;
 
-pub fn read_file_content<P: AsRef<Path>>(file_path: P) -> IoResult<String> {
-    info!("Reading file: {}", file_path.as_ref().display());
-    let raw = fs::read(&file_path)?;
-    if raw.starts_with(&[0xff, 0xfe]) {
-        let mut decoder = DecodeReaderBytesBuilder::new()
+/// Extensions picked up when `--dump-file` names a directory.
+const DUMP_EXTENSIONS: &[&str] = &[
+    "sql",
+    "surql",
+    "dump",
+    "json",
+    "jsonl",
+    "ndjson",
+    "csv",
+    "tsv",
+    "tab",
+    "db",
+    "sqlite",
+    "sqlite3",
+];
+
+/// Expands `--dump-file` into the files to read, in name order.
+///
+/// A directory yields its files with a known dump extension, a pattern
+/// containing `*`, `?` or `[` yields every matching file, and anything else
+/// is taken as a single file.
+pub fn resolve_dump_files(spec: &str) -> IoResult<Vec<PathBuf>> {
+    let path = Path::new(spec);
+    let mut files: Vec<PathBuf> = if path.is_dir() {
+        fs::read_dir(path)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && is_dump_file(p))
+            .collect()
+    } else if spec.contains(['*', '?', '[']) {
+        glob::glob(spec)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?
+            .filter_map(Result::ok)
+            .filter(|p| p.is_file())
+            .collect()
+    } else {
+        return Ok(vec![path.to_path_buf()]);
+    };
+    files.sort();
+    if files.is_empty() {
+        return Err(
+            std::io::Error::new(std::io::ErrorKind::NotFound, format!("No dump files match '{}'", spec))
+        );
+    }
+    Ok(files)
+}
+
+fn is_dump_file(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    !name.starts_with('.') &&
+        Path::new(strip_compression_extension(name))
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| DUMP_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+/// Compression extensions stripped by `strip_compression_extension`.
+const COMPRESSION_EXTENSIONS: &[&str] = &[".gz", ".gzip", ".zst", ".zstd", ".bz2", ".xz"];
+
+/// The name of a dump inside its compression wrapper, e.g. `dump.sql` for
+/// `dump.sql.gz`.
+pub fn strip_compression_extension(path: &str) -> &str {
+    let lower = path.to_lowercase();
+    COMPRESSION_EXTENSIONS.iter()
+        .find(|ext| lower.ends_with(*ext))
+        .map_or(path, |ext| &path[..path.len() - ext.len()])
+}
+
+/// Compression of a dump, recognized by its magic bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+impl Compression {
+    pub fn from_magic(magic: &[u8]) -> Self {
+        match magic {
+            [0x1f, 0x8b, ..] => Compression::Gzip,
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Compression::Zstd,
+            [b'B', b'Z', b'h', ..] => Compression::Bzip2,
+            [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => Compression::Xz,
+            _ => Compression::None,
+        }
+    }
+
+    pub fn detect<P: AsRef<Path>>(file_path: P) -> IoResult<Self> {
+        let mut magic = Vec::with_capacity(6);
+        File::open(file_path)?.take(6).read_to_end(&mut magic)?;
+        Ok(Compression::from_magic(&magic))
+    }
+}
+
+/// Opens a dump, decompressing gzip, zstd, bzip2 and xz files on the fly.
+pub fn open_decompressed<P: AsRef<Path>>(file_path: P) -> IoResult<Box<dyn Read + Send>> {
+    let compression = Compression::detect(&file_path)?;
+    let file = BufReader::new(File::open(&file_path)?);
+    if compression != Compression::None {
+        info!("Decompressing {:?} dump: {}", compression, file_path.as_ref().display());
+    }
+    Ok(match compression {
+        Compression::None => Box::new(file),
+        // Multi-member files are what `pigz` and concatenated `.gz` parts produce.
+        Compression::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(file)),
+        Compression::Zstd => Box::new(zstd::Decoder::with_buffer(file)?),
+        Compression::Bzip2 => Box::new(bzip2::bufread::MultiBzDecoder::new(file)),
+        Compression::Xz => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(file)),
+    })
+}
+
+/// Opens a dump for incremental reading, decompressing it if needed and
+/// transcoding UTF-16LE files to UTF-8.
+pub fn open_dump_reader<P: AsRef<Path>>(file_path: P) -> IoResult<Box<dyn BufRead + Send>> {
+    info!("Opening file: {}", file_path.as_ref().display());
+    let mut reader = BufReader::new(open_decompressed(&file_path)?);
+    let mut bom = [0u8; 2];
+    let n = reader.read(&mut bom)?;
+    let reader = Cursor::new(bom[..n].to_vec()).chain(reader);
+
+    if n == 2 && bom == [0xff, 0xfe] {
+        let decoder = DecodeReaderBytesBuilder::new()
             .encoding(Some(UTF_16LE))
             .bom_override(true)
-            .build(Cursor::new(raw));
-
-        let mut content = String::new();
-        decoder.read_to_string(&mut content)?;
-        Ok(content)
+            .build(reader);
+        Ok(Box::new(BufReader::new(decoder)))
     } else {
-        String::from_utf8(raw).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+        Ok(Box::new(BufReader::new(reader)))
     }
 }
 
-pub fn read_file_and_detect_format<P: AsRef<Path>>(file_path: P) -> IoResult<(String, String)> {
-    let content = read_file_content(&file_path)?;
+/// Opens a dump as a stream and detects its format from the first megabyte,
+/// unless `format` (from `--format`) is given.
+pub fn open_and_detect_format<P: AsRef<Path>>(
+    file_path: P,
+    format: Option<&str>
+) -> IoResult<(Box<dyn BufRead + Send>, String)> {
+    if let Some(format) = format {
+        info!("Processing {} format file (set by --format): {}", format, file_path.as_ref().display());
+        return Ok((open_dump_reader(&file_path)?, format.to_string()));
+    }
 
-    info!("Detecting format...");
+    let mut sample = Vec::new();
+    open_dump_reader(&file_path)?.take(FORMAT_SAMPLE_BYTES).read_to_end(&mut sample)?;
+    let sample = String::from_utf8_lossy(&sample);
 
+    info!("Detecting format...");
     let file_path_str = file_path.as_ref().to_str().unwrap_or("unknown_path");
-    let format = detect_format(file_path_str, &content);
+    let format = detect_format_checked(file_path_str, &sample).map_err(|e|
+        std::io::Error::new(std::io::ErrorKind::InvalidData, e)
+    )?;
 
     info!("Detected format: {}", format);
     info!("Processing {} format file: {}", format, file_path.as_ref().display());
 
-    Ok((content, format))
+    Ok((open_dump_reader(&file_path)?, format))
 }
 
 pub fn logo() {
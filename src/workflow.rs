@@ -1,37 +1,98 @@
 use crate::cli::Args;
 use crate::db::{ Database, DbError, store_in_batches };
-use crate::embedding::embeding::{ initialize_embedding_generator, process_records_with_embeddings };
+use crate::embedding::AsyncEmbeddingGenerator;
+use crate::embedding::embeding::{
+    embed_records,
+    initialize_embedding_generator,
+    PreparedRecord,
+    TextBuilder,
+};
 use crate::util::spinner::start_spinner_animation;
 use crate::util::handle_tei::{start_and_wait_for_tei, ManagedProcess};
+use crate::util::checkpoint::Checkpoint;
 use log::{ info, warn, error };
 use serde_json::Value;
 use std::collections::HashMap;
+use futures::stream::{ self, StreamExt };
 use std::sync::Arc;
 use std::sync::atomic::{ AtomicUsize, Ordering };
+use std::thread;
 use std::time::Instant;
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc;
 
 pub struct MigrationStats {
     pub total_records: usize,
     pub processed_records: usize,
+    pub skipped_records: usize,
     pub elapsed_seconds: f64,
 }
 
-pub fn execute_migration_workflow(
-    records: Vec<Value>,
+/// Embeds and stores records as they are produced by `records`.
+///
+/// Records are pulled in batches of `embedding_batch_size`. Up to
+/// `embedding_concurrency` batches are embedded at once while a separate
+/// thread stores finished batches in dump order, so memory use is bounded by
+/// the number of batches in flight rather than by the size of the dump.
+///
+/// After every stored batch the checkpoint at `args.checkpoint_file` is
+/// updated. With `args.resume` the records it marks as committed are skipped,
+/// provided the dump and target configuration are unchanged.
+pub fn execute_migration_workflow<I>(
+    records: I,
     database: &dyn Database,
     args: &Args,
-) -> Result<MigrationStats, DbError> {
-    let total_records = records.len();
-    if total_records == 0 {
+) -> Result<MigrationStats, DbError>
+where
+    I: Iterator<Item = std::io::Result<Value>>,
+{
+    let mut records = records.peekable();
+    if records.peek().is_none() {
         warn!("No records to process");
         return Ok(MigrationStats {
             total_records: 0,
             processed_records: 0,
+            skipped_records: 0,
             elapsed_seconds: 0.0,
         });
     }
 
-    let mut tei_process: Option<ManagedProcess> = None;
+    // Bad templates or chunking options fail here, before any state is written.
+    let text = TextBuilder::from_args(args)?;
+    let mut checkpoint = prepare_checkpoint(args)?;
+    let skipped_records = checkpoint.records_committed;
+    for _ in 0..skipped_records {
+        match records.next() {
+            Some(Ok(_)) => {}
+            Some(Err(e)) => {
+                return Err(format!("Failed to read dump: {}", e).into());
+            }
+            None => {
+                return Err(
+                    format!(
+                        "Checkpoint records {} committed records but the dump has fewer",
+                        skipped_records
+                    ).into()
+                );
+            }
+        }
+    }
+    if skipped_records > 0 {
+        println!("Resuming after {} already stored records", skipped_records);
+    }
+    if records.peek().is_none() {
+        println!("Nothing left to migrate.");
+        Checkpoint::remove(&args.checkpoint_file)?;
+        return Ok(MigrationStats {
+            total_records: skipped_records,
+            processed_records: 0,
+            skipped_records,
+            elapsed_seconds: 0.0,
+        });
+    }
+
+    // Killed when dropped, on failure as well as on success.
+    let mut _tei_process: Option<ManagedProcess> = None;
     let mut override_url: Option<String> = None;
 
     if args.embedding_provider == "tei" && args.embedding_url.is_none() {
@@ -39,72 +100,56 @@ pub fn execute_migration_workflow(
         let (proc, url) = std::thread::spawn(move || start_and_wait_for_tei(&args))
             .join()
             .map_err(|e| format!("TEI thread panicked: {:?}", e))??;
-        tei_process = Some(proc);
+        _tei_process = Some(proc);
         override_url = Some(url);
     }
 
     let generator = initialize_embedding_generator(args, override_url.as_deref())
         .map_err(|e| DbError::from(format!("Init embed gen failed: {}", e)))?;
+    let rt = Runtime::new()?;
+
+    // Only written once the embedding side is ready, so a run that cannot
+    // start leaves an earlier checkpoint as it was.
+    checkpoint.save(&args.checkpoint_file)?;
 
     let start_time = Instant::now();
     let embedding_count = Arc::new(AtomicUsize::new(0));
-    let embedding_animation = start_spinner_animation(
-        embedding_count.clone(),
-        total_records,
-        "Generating embeddings"
-    );
-
-    info!("Starting embedding generation for {} records", total_records);
-
-    let prepared_records = match
-        process_records_with_embeddings(records, args, embedding_count.clone(), generator)
-    {
-        Ok(records) => records,
-        Err(e) => {
-            embedding_animation.stop();
-            error!("CRITICAL: Embedding generation failed: {}", e);
-            return Err(format!("Embedding generation critical error: {}", e).into());
-        }
-    };
+    let stored_count = Arc::new(AtomicUsize::new(0));
+    let animation = start_spinner_animation(stored_count.clone(), 0, "Embedding and storing records");
 
-    embedding_animation.stop();
+    info!("Starting streaming migration");
 
-    if prepared_records.is_empty() {
-        warn!("No records were prepared for storage after embedding process.");
-    } else {
-        println!("\nEmbedding generation complete! Storing data...");
+    let concurrency = args.embedding_concurrency.max(1);
+    let (tx, rx) = mpsc::channel::<(usize, Vec<PreparedRecord>)>(concurrency);
+    let mut total_records = skipped_records;
 
-        let mut grouped_records: HashMap<String, Vec<(String, Vec<f32>, Value)>> = HashMap::new();
-        for (table, id, vec, meta) in prepared_records {
-            grouped_records.entry(table).or_insert_with(Vec::new).push((id, vec, meta));
-        }
-
-        let processed_count = Arc::new(AtomicUsize::new(0));
-        let storage_animation = start_spinner_animation(
-            processed_count.clone(),
-            total_records,
-            "Storing in database"
+    let (embed_result, store_result) = thread::scope(|scope| {
+        let storer = scope.spawn(|| store_batches(rx, database, args, &mut checkpoint, &stored_count));
+        let embedded = rt.block_on(
+            embed_batches(
+                &mut records,
+                tx,
+                args,
+                &text,
+                &*generator,
+                &embedding_count,
+                &mut total_records
+            )
         );
+        let stored = storer.join().unwrap_or_else(|_| Err("Storage thread panicked".into()));
+        (embedded, stored)
+    });
 
-        let max_payload_bytes = args.max_payload_size_mb * 1024 * 1024;
-        let chunk_size = args.chunk_size;
+    animation.stop();
+    let finish_result = database.finish();
 
-        for (table, items) in grouped_records {
-            info!("Storing {} items for table '{}'", items.len(), table);
-            for batch in items.chunks(chunk_size) {
-                match store_in_batches(database, &table, batch, max_payload_bytes) {
-                    Ok(_) => {
-                        let _ = processed_count.fetch_add(batch.len(), Ordering::Relaxed);
-                    }
-                    Err(e) => {
-                        storage_animation.stop();
-                        error!("CRITICAL: Database storage error for table '{}': {}", table, e);
-                        return Err(format!("Database storage error: {}", e).into());
-                    }
-                }
-            }
-        }
-        storage_animation.stop();
+    if let Err(e) = store_result.and(embed_result).and(finish_result) {
+        error!(
+            "Progress saved to '{}' ({} records committed); rerun with --resume to continue",
+            args.checkpoint_file,
+            checkpoint.records_committed
+        );
+        return Err(e);
     }
 
     let elapsed_time = start_time.elapsed();
@@ -120,15 +165,169 @@ pub fn execute_migration_workflow(
             0.0
         }
     );
-    println!("Migration Complete.");
-
-    if let Some(mut p) = tei_process {
-        let _ = p.kill();
+    if let Some(tokens) = generator.tokens_used() {
+        println!("Embedding tokens used: {}", tokens);
     }
+    println!("Migration Complete.");
+    Checkpoint::remove(&args.checkpoint_file)?;
 
     Ok(MigrationStats {
         total_records,
         processed_records: final_count,
+        skipped_records,
         elapsed_seconds: elapsed_time.as_secs_f64(),
     })
-}
\ No newline at end of file
+}
+
+/// Producer side of the pipeline: reads batches of `embedding_batch_size`
+/// records and keeps up to `embedding_concurrency` embedding requests in
+/// flight. Finished batches are handed to the storage thread in dump order.
+pub async fn embed_batches<I>(
+    records: &mut I,
+    tx: mpsc::Sender<(usize, Vec<PreparedRecord>)>,
+    args: &Args,
+    text: &TextBuilder,
+    generator: &(dyn AsyncEmbeddingGenerator + Send + Sync),
+    embedding_count: &AtomicUsize,
+    total_records: &mut usize,
+) -> Result<(), DbError>
+where
+    I: Iterator<Item = std::io::Result<Value>>,
+{
+    let batch_size = args.embedding_batch_size.max(1);
+    let mut failed = false;
+    let batches = std::iter::from_fn(|| {
+        if failed {
+            return None;
+        }
+        let mut batch = Vec::with_capacity(batch_size);
+        for record in records.by_ref() {
+            match record {
+                Ok(record) => {
+                    batch.push(record);
+                    if batch.len() >= batch_size {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    failed = true;
+                    error!("CRITICAL: Failed to read records from dump: {}", e);
+                    return Some(Err(format!("Failed to read dump: {}", e)));
+                }
+            }
+        }
+        *total_records += batch.len();
+        (!batch.is_empty()).then_some(Ok(batch))
+    });
+
+    let mut results = stream
+        ::iter(batches)
+        .map(|batch| async move {
+            let batch = batch?;
+            let record_count = batch.len();
+            embed_records(batch, args, text, generator).await.map(|prepared| (record_count, prepared))
+        })
+        .buffered(args.embedding_concurrency.max(1));
+
+    while let Some(result) = results.next().await {
+        let (record_count, prepared) = result.map_err(|e| {
+            DbError::from(format!("Embedding generation critical error: {}", e))
+        })?;
+        embedding_count.fetch_add(record_count, Ordering::Relaxed);
+        if tx.send((record_count, prepared)).await.is_err() {
+            // The storage thread stopped; its error is reported by the caller.
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Consumer side of the pipeline: stores embedded batches as they arrive and
+/// advances the checkpoint by the number of source records in each one, once
+/// the database reports them durable.
+fn store_batches(
+    mut rx: mpsc::Receiver<(usize, Vec<PreparedRecord>)>,
+    database: &dyn Database,
+    args: &Args,
+    checkpoint: &mut Checkpoint,
+    stored_count: &AtomicUsize,
+) -> Result<(), DbError> {
+    let mut pending_records = 0;
+    let mut pending_vectors: HashMap<String, usize> = HashMap::new();
+    while let Some((record_count, prepared)) = rx.blocking_recv() {
+        let vector_counts = store_prepared_batch(prepared, database, args, stored_count)?;
+        pending_records += record_count;
+        for (table, n) in vector_counts {
+            *pending_vectors.entry(table).or_default() += n;
+        }
+        if let Some(parts) = database.commit()? {
+            checkpoint.commit_batch(pending_records, &pending_vectors);
+            checkpoint.parts.extend(parts);
+            checkpoint.save(&args.checkpoint_file)?;
+            pending_records = 0;
+            pending_vectors.clear();
+        }
+    }
+    Ok(())
+}
+
+fn store_prepared_batch(
+    prepared_records: Vec<PreparedRecord>,
+    database: &dyn Database,
+    args: &Args,
+    stored_count: &AtomicUsize,
+) -> Result<HashMap<String, usize>, DbError> {
+    let mut grouped_records: HashMap<String, Vec<(String, Vec<f32>, Value)>> = HashMap::new();
+    for (table, id, vec, meta) in prepared_records {
+        grouped_records.entry(table).or_default().push((id, vec, meta));
+    }
+
+    let max_payload_bytes = args.max_payload_size_mb * 1024 * 1024;
+    let chunk_size = args.chunk_size.max(1);
+
+    let mut vector_counts = HashMap::new();
+    for (table, items) in grouped_records {
+        info!("Storing {} items for table '{}'", items.len(), table);
+        for batch in items.chunks(chunk_size) {
+            if let Err(e) = store_in_batches(database, &table, batch, max_payload_bytes) {
+                error!("CRITICAL: Database storage error for table '{}': {}", table, e);
+                return Err(format!("Database storage error: {}", e).into());
+            }
+            stored_count.fetch_add(batch.len(), Ordering::Relaxed);
+        }
+        vector_counts.insert(table, items.len());
+    }
+
+    Ok(vector_counts)
+}
+
+/// Loads and validates the checkpoint for `--resume`, or starts a fresh one.
+fn prepare_checkpoint(args: &Args) -> Result<Checkpoint, DbError> {
+    let current = Checkpoint::new(args)?;
+    let saved = Checkpoint::load(&args.checkpoint_file)?;
+
+    match saved {
+        Some(saved) if args.resume => {
+            saved.ensure_matches(&current)?;
+            info!(
+                "Resuming from checkpoint '{}': {} records committed",
+                args.checkpoint_file,
+                saved.records_committed
+            );
+            Ok(saved)
+        }
+        None if args.resume => {
+            warn!("No checkpoint found at '{}'; starting from the beginning", args.checkpoint_file);
+            Ok(current)
+        }
+        Some(_) => {
+            warn!(
+                "Overwriting existing checkpoint '{}' (use --resume to continue from it)",
+                args.checkpoint_file
+            );
+            Ok(current)
+        }
+        None => Ok(current),
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{ self, BufRead, BufReader, Read, Write };
+use std::net::TcpListener;
+use std::path::Path;
+use clap::Parser;
+use db2vec::cli::Args;
+use db2vec::db::{ select_database, DbError };
+use db2vec::parser::{ join_records, open_dump_files, parse_joins, RecordIter };
+use db2vec::util::checkpoint::Checkpoint;
+use db2vec::workflow::{ execute_migration_workflow, MigrationStats };
+use serde_json::Value;
+
+fn args_for(dump_file: &str) -> Args {
+    Args::parse_from(["db2vec", "--dump-file", dump_file])
+}
+
+#[test]
+fn test_checkpoint_roundtrip_and_validation() {
+    let dir = std::env::temp_dir().join(format!("db2vec-checkpoint-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let dump = dir.join("dump.sql");
+    let state = dir.join("state.json");
+    fs::write(&dump, "INSERT INTO `t` VALUES (1,'a');\n").unwrap();
+
+    let args = args_for(dump.to_str().unwrap());
+    let mut checkpoint = Checkpoint::new(&args).unwrap();
+    checkpoint.commit_batch(3, &HashMap::from([("t".to_string(), 3)]));
+    checkpoint.save(&state).unwrap();
+
+    let loaded = Checkpoint::load(&state).unwrap().expect("checkpoint should exist");
+    assert_eq!(loaded, checkpoint);
+    assert_eq!(loaded.vectors.get("t"), Some(&3));
+    assert!(loaded.ensure_matches(&Checkpoint::new(&args).unwrap()).is_ok());
+
+    let mut other_target = args.clone();
+    other_target.dimension = 384;
+    let err = loaded.ensure_matches(&Checkpoint::new(&other_target).unwrap()).unwrap_err();
+    assert!(err.to_string().contains("dimension"));
+
+    let mut other_joins = args.clone();
+    other_joins.joins = vec!["orders.customer_id -> customers.id".to_string()];
+    let err = loaded.ensure_matches(&Checkpoint::new(&other_joins).unwrap()).unwrap_err();
+    assert!(err.to_string().contains("joins"));
+
+    let templates = dir.join("templates.json");
+    let mut with_templates = args.clone();
+    with_templates.templates_file = templates.to_str().unwrap().to_string();
+    fs::write(&templates, r#"{"t": "{name}"}"#).unwrap();
+    let pinned = Checkpoint::new(&with_templates).unwrap();
+    fs::write(&templates, r#"{"t": "{name} {id}"}"#).unwrap();
+    let err = pinned.ensure_matches(&Checkpoint::new(&with_templates).unwrap()).unwrap_err();
+    assert!(err.to_string().contains("templates_sha256"));
+
+    let keys = dir.join("primary_keys.json");
+    let mut with_keys = args.clone();
+    with_keys.primary_keys_file = keys.to_str().unwrap().to_string();
+    with_keys.pg_insert_mode = "copy".to_string();
+    fs::write(&keys, r#"{"t": ["id"]}"#).unwrap();
+    let pinned = Checkpoint::new(&with_keys).unwrap();
+    fs::write(&keys, r#"{"t": ["id", "name"]}"#).unwrap();
+    let mut changed = with_keys.clone();
+    changed.pg_insert_mode = "insert".to_string();
+    changed.output_dir = "elsewhere".to_string();
+    let err = pinned.ensure_matches(&Checkpoint::new(&changed).unwrap()).unwrap_err().to_string();
+    for key in ["primary_keys_sha256", "pg_insert_mode", "output_dir"] {
+        assert!(err.contains(key), "{}", err);
+    }
+
+    fs::write(&dump, "INSERT INTO `t` VALUES (2,'b');\n").unwrap();
+    assert!(loaded.ensure_matches(&Checkpoint::new(&args).unwrap()).is_err());
+
+    Checkpoint::remove(&state).unwrap();
+    assert!(Checkpoint::load(&state).unwrap().is_none());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Answers TEI `/embed` requests with vectors derived from each text, so that
+/// identical records get identical vectors across runs.
+fn serve_embeddings() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            let request: Value = serde_json::from_slice(&body).unwrap();
+            let vectors: Vec<[f32; 2]> = request["inputs"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|text| [text.as_str().unwrap().len() as f32, 1.0])
+                .collect();
+            let body = serde_json::to_string(&vectors).unwrap();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            ).unwrap();
+        }
+    });
+    url
+}
+
+/// Runs a migration the way `main` does, failing the input after
+/// `interrupt_after` records when given.
+fn migrate(args: &Args, interrupt_after: Option<usize>) -> Result<MigrationStats, DbError> {
+    let schema = Default::default();
+    let (records, _) = open_dump_files(&args.dump_file, args, &schema)?;
+    let records = join_records(records, parse_joins(&args.joins)?);
+    let records: RecordIter = match interrupt_after {
+        Some(n) => Box::new(records.take(n).chain(std::iter::once(Err(io::Error::other("interrupted"))))),
+        None => records,
+    };
+    let database = select_database(args, schema)?;
+    execute_migration_workflow(records, &*database, args)
+}
+
+fn read_output(dir: &Path, table: &str) -> String {
+    fs::read_to_string(dir.join(format!("{}.jsonl", table))).unwrap_or_default()
+}
+
+#[test]
+fn test_resume_joined_migration() {
+    let dir = std::env::temp_dir().join(format!("db2vec-join-resume-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let dump = dir.join("shop.sql");
+    fs::write(
+        &dump,
+        "CREATE TABLE customers (id INT PRIMARY KEY, name VARCHAR(50));\n\
+         CREATE TABLE orders (id INT PRIMARY KEY, customer_id INT, total INT);\n\
+         CREATE TABLE order_items (id INT PRIMARY KEY, order_id INT, product VARCHAR(50));\n\
+         CREATE TABLE notes (id INT PRIMARY KEY, body TEXT);\n\
+         INSERT INTO orders VALUES (10, 1, 5), (11, 2, 7), (12, 1, 9), (13, 3, 2), (14, 2, 4);\n\
+         INSERT INTO notes VALUES (1, 'first'), (2, 'second');\n\
+         INSERT INTO order_items VALUES (1, 10, 'Desk'), (2, 10, 'Lamp'), (3, 12, 'Pen'), (4, 14, 'Ink');\n\
+         INSERT INTO customers VALUES (1, 'Ada'), (2, 'Linus'), (3, 'Grace');\n"
+    ).unwrap();
+
+    let url = serve_embeddings();
+    let args_for_run = |run: &str, resume: bool| {
+        let mut argv = vec![
+            "db2vec".to_string(),
+            "-f".to_string(),
+            dump.to_string_lossy().to_string(),
+            "--format".to_string(),
+            "mysql".to_string(),
+            "-t".to_string(),
+            "jsonl".to_string(),
+            "--output-dir".to_string(),
+            dir.join(run).to_string_lossy().to_string(),
+            "--dimension".to_string(),
+            "2".to_string(),
+            "--checkpoint-file".to_string(),
+            dir.join(format!("{}.state.json", run)).to_string_lossy().to_string(),
+            "--embedding-provider".to_string(),
+            "tei".to_string(),
+            "--embedding-url".to_string(),
+            url.clone(),
+            "--embedding-batch-size".to_string(),
+            "2".to_string(),
+            "--embedding-concurrency".to_string(),
+            "1".to_string(),
+            "--join".to_string(),
+            "orders.customer_id -> customers.id".to_string(),
+            "--join".to_string(),
+            "order_items.order_id -> orders.id as items[]".to_string()
+        ];
+        if resume {
+            argv.push("--resume".to_string());
+        }
+        Args::parse_from(argv)
+    };
+
+    let full = migrate(&args_for_run("full", false), None).unwrap();
+    assert_eq!(full.processed_records, 10);
+
+    // Two batches of two are committed before the input fails in the third.
+    assert!(migrate(&args_for_run("resumed", false), Some(5)).is_err());
+    let checkpoint = Checkpoint::load(dir.join("resumed.state.json")).unwrap().unwrap();
+    assert_eq!(checkpoint.records_committed, 4);
+
+    // A run whose embedding provider cannot start leaves the checkpoint as it was.
+    let mut broken = args_for_run("resumed", false);
+    broken.embedding_provider = "unknown".to_string();
+    assert!(migrate(&broken, None).is_err());
+    assert_eq!(Checkpoint::load(dir.join("resumed.state.json")).unwrap(), Some(checkpoint));
+
+    let resumed = migrate(&args_for_run("resumed", true), None).unwrap();
+    assert_eq!(resumed.skipped_records, 4);
+    assert_eq!(resumed.processed_records, 6);
+    assert!(Checkpoint::load(dir.join("resumed.state.json")).unwrap().is_none());
+
+    // The joined output comes in the same order on every run, so the resumed
+    // migration stores each record exactly once, as the uninterrupted one did.
+    for (table, rows) in [("notes", 2), ("orders", 5), ("customers", 3), ("order_items", 0)] {
+        let output = read_output(&dir.join("resumed"), table);
+        assert_eq!(output.lines().count(), rows, "{}", table);
+        assert_eq!(output, read_output(&dir.join("full"), table), "{}", table);
+    }
+    let orders: Vec<Value> = read_output(&dir.join("resumed"), "orders")
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(orders[0]["metadata"]["customer"]["name"], "Ada");
+    assert_eq!(orders[0]["metadata"]["items"].as_array().unwrap().len(), 2);
+    assert!(orders[1]["metadata"].get("items").is_none());
+    fs::remove_dir_all(&dir).unwrap();
+}
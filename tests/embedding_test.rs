@@ -0,0 +1,216 @@
+use std::error::Error as StdError;
+use std::sync::atomic::{ AtomicUsize, Ordering };
+use std::time::Duration;
+use async_trait::async_trait;
+use clap::Parser;
+use db2vec::cli::Args;
+use db2vec::embedding::AsyncEmbeddingGenerator;
+use db2vec::embedding::embeding::{ embed_records, TextBuilder };
+use db2vec::util::handle_tei::ManagedProcess;
+use db2vec::workflow::embed_batches;
+use serde_json::json;
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc;
+
+/// Returns each record's `n` as its one-element vector, sleeping longer for
+/// earlier batches so completion order differs from submission order.
+struct SlowEchoGenerator {
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+#[async_trait]
+impl AsyncEmbeddingGenerator for SlowEchoGenerator {
+    async fn generate_embeddings_batch(
+        &self,
+        texts: &[String]
+    ) -> Result<Vec<Vec<f32>>, Box<dyn StdError + Send + Sync>> {
+        let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+
+        let values: Vec<f32> = texts
+            .iter()
+            .map(|t| t.trim_start_matches("n: ").parse::<f32>().unwrap())
+            .collect();
+        let delay = 80u64.saturating_sub((values[0] as u64) * 10);
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        Ok(values.into_iter().map(|v| vec![v]).collect())
+    }
+
+    fn get_dimension(&self) -> usize {
+        1
+    }
+}
+
+#[test]
+fn test_concurrent_embedding_keeps_record_order() {
+    let args = Args::parse_from([
+        "db2vec",
+        "--embedding-batch-size",
+        "2",
+        "--embedding-concurrency",
+        "4",
+    ]);
+    let mut records = (0..8).map(|n| Ok(json!({ "table": "t", "n": n })));
+    let generator = SlowEchoGenerator {
+        in_flight: AtomicUsize::new(0),
+        max_in_flight: AtomicUsize::new(0),
+    };
+    let counter = AtomicUsize::new(0);
+    let mut total_records = 0;
+    let (tx, mut rx) = mpsc::channel(8);
+    let rt = Runtime::new().unwrap();
+
+    rt.block_on(
+        embed_batches(
+            &mut records,
+            tx,
+            &args,
+            &TextBuilder::from_args(&args).unwrap(),
+            &generator,
+            &counter,
+            &mut total_records
+        )
+    ).unwrap();
+
+    let mut order = Vec::new();
+    while let Ok((record_count, prepared)) = rx.try_recv() {
+        assert_eq!(record_count, 2);
+        order.extend(prepared.iter().map(|(_, _, vec, _)| vec[0]));
+    }
+    assert_eq!(order, (0..8).map(|n| n as f32).collect::<Vec<_>>());
+    assert_eq!(counter.load(Ordering::SeqCst), 8);
+    assert_eq!(total_records, 8);
+    assert!(generator.max_in_flight.load(Ordering::SeqCst) > 1);
+    assert!(generator.max_in_flight.load(Ordering::SeqCst) <= 4);
+}
+
+#[test]
+fn test_openai_client_orders_by_index_and_counts_tokens() {
+    use std::io::{ Read, Write };
+    use std::net::TcpListener;
+    use db2vec::embedding::models::openai::OpenAiEmbeddingClient;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut request = String::new();
+        while !request.contains("]") {
+            let n = stream.read(&mut buf).unwrap();
+            request.push_str(&String::from_utf8_lossy(&buf[..n]));
+        }
+        let body =
+            r#"{"data":[{"index":1,"embedding":[2.0,2.0]},{"index":0,"embedding":[1.0,1.0]}],"usage":{"prompt_tokens":5,"total_tokens":5}}"#;
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        ).unwrap();
+        request
+    });
+
+    let client = OpenAiEmbeddingClient::new(
+        Some(&format!("http://{}", addr)),
+        Some("secret".to_string()),
+        "test-model",
+        2,
+        10
+    ).unwrap();
+    let rt = Runtime::new().unwrap();
+    let embeddings = rt
+        .block_on(client.generate_embeddings_batch(&["a".to_string(), "b".to_string()]))
+        .unwrap();
+
+    let request = server.join().unwrap();
+    assert!(request.starts_with("POST /v1/embeddings"));
+    assert!(request.to_lowercase().contains("authorization: bearer secret"));
+    assert_eq!(embeddings, vec![vec![1.0, 1.0], vec![2.0, 2.0]]);
+    assert_eq!(client.tokens_used(), Some(5));
+}
+
+struct WordCountGenerator;
+
+#[async_trait]
+impl AsyncEmbeddingGenerator for WordCountGenerator {
+    async fn generate_embeddings_batch(
+        &self,
+        texts: &[String]
+    ) -> Result<Vec<Vec<f32>>, Box<dyn StdError + Send + Sync>> {
+        Ok(
+            texts
+                .iter()
+                .map(|t| vec![t.split_whitespace().count() as f32])
+                .collect()
+        )
+    }
+
+    fn get_dimension(&self) -> usize {
+        1
+    }
+}
+
+#[test]
+fn test_chunker_windows_overlap() {
+    use db2vec::embedding::chunk::{ ChunkMode, Chunker };
+
+    let words = Chunker { mode: ChunkMode::Tokens, size: 4, overlap: 1 };
+    assert_eq!(words.split("a b c d e f g"), vec!["a b c d", "d e f g"]);
+    assert_eq!(words.split("short text"), vec!["short text"]);
+
+    let sentences = Chunker { mode: ChunkMode::Sentences, size: 2, overlap: 1 };
+    assert_eq!(
+        sentences.split("One. Two! Three? Four."),
+        vec!["One. Two!", "Two! Three?", "Three? Four."]
+    );
+
+    let chars = Chunker { mode: ChunkMode::Chars, size: 4, overlap: 2 };
+    assert_eq!(chars.split("abcdefgh"), vec!["abcd", "cdef", "efgh"]);
+}
+
+#[test]
+fn test_long_records_are_chunked_with_parent_metadata() {
+    use db2vec::embedding::embeding::{ chunk_id, record_id };
+
+    let args = Args::parse_from([
+        "db2vec",
+        "--text-chunking",
+        "tokens",
+        "--text-chunk-size",
+        "4",
+        "--text-chunk-overlap",
+        "1",
+    ]);
+    let record = json!({ "table": "articles", "source_id": "7", "body": "w1 w2 w3 w4 w5 w6 w7" });
+    let rt = Runtime::new().unwrap();
+
+    let text = TextBuilder::from_args(&args).unwrap();
+    let prepared = rt.block_on(embed_records(vec![record.clone()], &args, &text, &WordCountGenerator)).unwrap();
+
+    let parent = record_id("articles", &record);
+    assert_eq!(prepared.len(), 3);
+    for (i, (table, id, _, meta)) in prepared.iter().enumerate() {
+        assert_eq!(table, "articles");
+        assert_eq!(id, &chunk_id(&parent, i));
+        assert_eq!(meta["parent_id"], json!(parent));
+        assert_eq!(meta["chunk_index"], json!(i));
+        assert_eq!(meta["chunk_count"], json!(3));
+        assert_eq!(meta["body"], record["body"]);
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_managed_process_is_killed_on_drop() {
+    let child = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+    let process = ManagedProcess::new(child, "sleep".to_string());
+    let proc_dir = format!("/proc/{}", process.id());
+    assert!(std::path::Path::new(&proc_dir).exists());
+    drop(process);
+    // Killed and reaped, so not even a zombie entry is left.
+    assert!(!std::path::Path::new(&proc_dir).exists());
+}
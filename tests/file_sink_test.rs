@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+use std::fs::{ self, File };
+use std::path::{ Path, PathBuf };
+use arrow_array::{ Array, FixedSizeListArray, Float32Array, StringArray };
+use clap::Parser;
+use db2vec::cli::Args;
+use db2vec::db::{ Database, JsonlDatabase, NpyDatabase, ParquetDatabase };
+use db2vec::util::checkpoint::Checkpoint;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use serde_json::{ json, Value };
+
+fn sink_args(kind: &str, dir: &Path, resume: bool) -> Args {
+    let mut argv = vec![
+        "db2vec".to_string(),
+        "-t".to_string(),
+        kind.to_string(),
+        "--output-dir".to_string(),
+        dir.to_string_lossy().to_string(),
+        "--dimension".to_string(),
+        "2".to_string(),
+        "--checkpoint-file".to_string(),
+        dir.join("state.json").to_string_lossy().to_string()
+    ];
+    if resume {
+        argv.push("--resume".to_string());
+    }
+    Args::parse_from(argv)
+}
+
+fn items(start: usize, count: usize) -> Vec<(String, Vec<f32>, Value)> {
+    (start..start + count)
+        .map(|i| (format!("id-{}", i), vec![i as f32, -(i as f32)], json!({ "table": "users", "n": i })))
+        .collect()
+}
+
+/// Checkpoint recording `rows` stored rows of `users`, and its last closed
+/// Parquet part, as left by an interrupted run.
+fn save_checkpoint(dir: &Path, rows: usize, last_part: Option<usize>) {
+    let checkpoint = Checkpoint {
+        version: 3,
+        dump_file: "dump.sql".to_string(),
+        dump_fingerprint: String::new(),
+        target: BTreeMap::new(),
+        records_committed: rows,
+        vectors: BTreeMap::from([("users".to_string(), rows)]),
+        parts: last_part.map(|part| ("users".to_string(), part)).into_iter().collect(),
+    };
+    checkpoint.save(dir.join("state.json")).unwrap();
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("db2vec-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn test_jsonl_sink_writes_and_resumes() {
+    let dir = temp_dir("jsonl");
+    let db = JsonlDatabase::new(&sink_args("jsonl", &dir, false)).unwrap();
+    db.store_vector("users", &items(0, 2)).unwrap();
+    db.store_vector("users", &items(2, 1)).unwrap();
+    drop(db);
+    save_checkpoint(&dir, 2, None);
+
+    // The row written after the checkpoint is dropped before the run goes on.
+    let resumed = JsonlDatabase::new(&sink_args("jsonl", &dir, true)).unwrap();
+    resumed.store_vector("users", &items(2, 2)).unwrap();
+
+    let lines: Vec<Value> = fs::read_to_string(dir.join("users.jsonl"))
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[2]["id"], "id-2");
+    assert_eq!(lines[3], json!({ "id": "id-3", "vector": [3.0, -3.0], "metadata": { "table": "users", "n": 3 } }));
+
+    let fresh = JsonlDatabase::new(&sink_args("jsonl", &dir, false)).unwrap();
+    fresh.store_vector("users", &items(0, 1)).unwrap();
+    assert_eq!(fs::read_to_string(dir.join("users.jsonl")).unwrap().lines().count(), 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_npy_sink_grows_array_across_batches() {
+    let dir = temp_dir("npy");
+    let db = NpyDatabase::new(&sink_args("npy", &dir, false)).unwrap();
+    db.store_vector("users", &items(0, 2)).unwrap();
+    db.store_vector("users", &items(2, 3)).unwrap();
+
+    let bytes = fs::read(dir.join("users.npy")).unwrap();
+    assert!(bytes.starts_with(b"\x93NUMPY\x01\x00"));
+    let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+    let header = String::from_utf8_lossy(&bytes[10..10 + header_len]);
+    assert!(header.contains("'shape': (5, 2)"), "{}", header);
+    assert_eq!((10 + header_len) % 64, 0);
+
+    let data = &bytes[10 + header_len..];
+    let values: Vec<f32> = data
+        .chunks(4)
+        .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect();
+    assert_eq!(&values[8..10], &[4.0, -4.0]);
+
+    let meta = fs::read_to_string(dir.join("users.metadata.jsonl")).unwrap();
+    assert_eq!(meta.lines().count(), 5);
+    assert!(meta.lines().nth(4).unwrap().contains("\"id\":\"id-4\""));
+    drop(db);
+
+    save_checkpoint(&dir, 2, None);
+    let resumed = NpyDatabase::new(&sink_args("npy", &dir, true)).unwrap();
+    resumed.store_vector("users", &items(2, 1)).unwrap();
+    let bytes = fs::read(dir.join("users.npy")).unwrap();
+    assert!(String::from_utf8_lossy(&bytes[10..10 + header_len]).contains("'shape': (3, 2)"));
+    assert_eq!(bytes.len(), 10 + header_len + 3 * 2 * 4);
+    let meta = fs::read_to_string(dir.join("users.metadata.jsonl")).unwrap();
+    assert_eq!(meta.lines().count(), 3);
+    assert!(meta.lines().nth(2).unwrap().contains("\"id\":\"id-2\""));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_parquet_sink_writes_fixed_size_vectors() {
+    let dir = temp_dir("parquet");
+    let db = ParquetDatabase::new(&sink_args("parquet", &dir, false)).unwrap();
+    db.store_vector("users", &items(0, 2)).unwrap();
+    db.store_vector("users", &items(2, 1)).unwrap();
+    // Parts stay open, and the checkpoint waits, until enough rows are written.
+    assert_eq!(db.commit().unwrap(), None);
+    db.finish().unwrap();
+
+    let file = File::open(dir.join("users").join("part-00000.parquet")).unwrap();
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file).unwrap().build().unwrap();
+    let batches: Vec<_> = reader.map(|b| b.unwrap()).collect();
+    assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 3);
+
+    let batch = &batches[0];
+    let ids = batch.column(0).as_any().downcast_ref::<StringArray>().unwrap();
+    assert_eq!(ids.value(1), "id-1");
+    let vectors = batch.column(1).as_any().downcast_ref::<FixedSizeListArray>().unwrap();
+    assert_eq!(vectors.value_length(), 2);
+    let second = vectors.value(1);
+    let second = second.as_any().downcast_ref::<Float32Array>().unwrap();
+    assert_eq!(second.values(), &[1.0, -1.0]);
+    let meta = batch.column(2).as_any().downcast_ref::<StringArray>().unwrap();
+    assert_eq!(serde_json::from_str::<Value>(meta.value(0)).unwrap()["n"], 0);
+    assert_eq!(vectors.len(), batch.num_rows());
+
+    // Rows closed by `drop` after the last checkpoint are not resumed from.
+    db.store_vector("users", &items(3, 2)).unwrap();
+    drop(db);
+    assert!(dir.join("users").join("part-00001.parquet").exists());
+    save_checkpoint(&dir, 3, Some(0));
+    // Parts are ordered by number, not name, once they outgrow five digits.
+    fs::write(dir.join("users").join("part-100000.parquet"), b"left over").unwrap();
+
+    let resumed = ParquetDatabase::new(&sink_args("parquet", &dir, true)).unwrap();
+    resumed.store_vector("users", &items(3, 1)).unwrap();
+    resumed.finish().unwrap();
+    let rows: Vec<i64> = ["part-00000.parquet", "part-00001.parquet"]
+        .iter()
+        .map(|part| {
+            let file = File::open(dir.join("users").join(part)).unwrap();
+            ParquetRecordBatchReaderBuilder::try_new(file).unwrap().metadata().file_metadata().num_rows()
+        })
+        .collect();
+    assert_eq!(rows, [3, 1]);
+    assert!(!dir.join("users").join("part-00002.parquet").exists());
+    assert!(!dir.join("users").join("part-100000.parquet").exists());
+
+    let big = ParquetDatabase::new(&sink_args("parquet", &dir, false)).unwrap();
+    big.store_vector("events", &items(0, 8 * 8192)).unwrap();
+    assert_eq!(big.commit().unwrap(), Some(BTreeMap::from([("events".to_string(), 0)])));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -7,16 +7,15 @@ use db2vec::cli::Args;
 use db2vec::embedding::models::google::GoogleEmbeddingClient;
 use db2vec::embedding::models::ollama::OllamaEmbeddingClient;
 use db2vec::embedding::models::tei::TeiEmbeddingClient;
-use db2vec::parser::parse_database_export;
+use db2vec::parser::stream_database_export;
 use db2vec::db::Database;
 use db2vec::embedding::AsyncEmbeddingGenerator;
-use db2vec::util::utils::read_file_content;
+use db2vec::util::utils::open_dump_reader;
 use uuid::Uuid; 
 use std::sync::OnceLock;
 use db2vec::util::handle_tei::{start_and_wait_for_tei, ManagedProcess};
 use async_trait::async_trait;
 use tokio::runtime::Runtime;
-use portpicker;
 static TEI_PROCESS: OnceLock<Option<ManagedProcess>> = OnceLock::new();
 
 #[derive(Debug, Clone)]
@@ -268,6 +267,19 @@ fn test_all_database_combinations() {
                 -v ./qdrant_storage:/qdrant/storage \
                 qdrant/qdrant"
         },
+        TestConfig {
+            db_type: "pgvector",
+            host: "postgres://localhost",
+            port: 12006,
+            container_name: "pgvector-test",
+            docker_cmd: "docker run -d \
+                --name pgvector-test \
+                -e POSTGRES_USER=root \
+                -e POSTGRES_PASSWORD=root \
+                -e POSTGRES_DB=db2vec_test \
+                -p 12006:5432 \
+                pgvector/pgvector:pg16"
+        },
     ];
 
     cleanup_test_containers(&db_configs);
@@ -290,7 +302,7 @@ fn test_all_database_combinations() {
     
     let provider_type = match specified_provider.as_deref() {
         Some("google") => {
-            if let Ok(api_key) = std::env::var("EMBEDDING_API_KEY") {
+            if std::env::var("EMBEDDING_API_KEY").is_ok() {
                 println!("⚠️ Using Google API for embeddings (may incur costs)");
                 TestEmbeddingProvider::Google
             } else {
@@ -358,9 +370,10 @@ fn test_all_database_combinations() {
                 vector_export_type: db_config.db_type.to_string(),
                 vector_host: format!("{}:{}", db_config.host, db_config.port),
                 database: TEST_DB_NAME.to_string(),
+                output_dir: "vectors".to_string(),
                 tenant: "default_tenant".to_string(),
                 namespace: "default_ns".to_string(),
-                user: if db_config.db_type == "milvus" { "root" } else { "root" }.to_string(),
+                user: "root".to_string(),
                 pass: if db_config.db_type == "milvus" { "Milvus" } else { "root" }.to_string(),
                 secret: "".to_string(),
                 chunk_size: 10,
@@ -383,11 +396,34 @@ fn test_all_database_combinations() {
                 use_auth: db_config.db_type != "redis",
                 group_redis: false,
                 use_exclude: false,
+                primary_keys_file: "config/primary_keys.json".to_string(),
+                templates_file: "config/templates.json".to_string(),
+                text_chunking: "off".to_string(),
+                text_chunk_size: None,
+                text_chunk_overlap: None,
+                joins: Vec::new(),
+                reject_file: None,
+                max_reject_ratio: None,
+                resume: false,
+                checkpoint_file: std::env::temp_dir()
+                    .join(format!("db2vec-test-{}-{}.json", db_config.db_type, format))
+                    .to_string_lossy()
+                    .to_string(),
+                command: None,
+                json_table: None,
+                json_table_field: "_collection".to_string(),
+                csv_delimiter: None,
+                format: None,
                 indexes: "test_index".to_string(),
                 cloud: "aws".to_string(),
                 region: "us-east-1".to_string(),
+                pg_index: "hnsw".to_string(),
+                pg_insert_mode: "insert".to_string(),
+                pg_typed_columns: true,
                 embedding_api_key: None,
                 embedding_url: None,
+                embedding_auth: "auto".to_string(),
+                embedding_send_dimensions: false,
                 embedding_max_tokens: 8000,
                 embedding_timeout: 60,
                 embedding_task_type: "SEMANTIC_SIMILARITY".to_string(),
@@ -477,7 +513,7 @@ fn start_container(config: &TestConfig) {
 
     let parts: Vec<&str> = config.docker_cmd.split_whitespace().collect();
     
-    let status = if parts.len() > 0 {
+    let status = if !parts.is_empty() {
         Command::new(parts[0])
             .args(&parts[1..])
             .status()
@@ -519,10 +555,11 @@ fn run_test_combination(
 ) -> Result<(), String> {
     let sample_path = Path::new(&args.dump_file);
     
-    let content = read_file_content(&sample_path)
+    let reader = open_dump_reader(sample_path)
         .map_err(|e| format!("Failed to read file {}: {}", args.dump_file, e))?;
     
-    let parsed_records = parse_database_export(&content, format, args)
+    let parsed_records = stream_database_export(reader, format, args)
+        .collect::<Result<Vec<_>, _>>()
         .map_err(|e| format!("Failed to parse export: {}", e))?;
     
     if parsed_records.is_empty() {
@@ -536,7 +573,7 @@ fn run_test_combination(
     for record in parsed_records {
         if let Some(table) = record.get("table").and_then(|t| t.as_str()) {
             table_groups.entry(table.to_string())
-                .or_insert_with(Vec::new)
+                .or_default()
                 .push(record);
         }
     }
@@ -584,7 +621,8 @@ fn create_db_client(args: &Args) -> Result<Box<dyn Database>, String> {
         qdrant::QdrantDatabase,
         redis::RedisDatabase,
         milvus::MilvusDatabase,
-        surreal::SurrealDatabase
+        surreal::SurrealDatabase,
+        pgvector::PgVectorDatabase
     };
     
     match args.vector_export_type.as_str() {
@@ -594,6 +632,7 @@ fn create_db_client(args: &Args) -> Result<Box<dyn Database>, String> {
         "redis" => RedisDatabase::new(args).map(|db| Box::new(db) as Box<dyn Database>),
         "milvus" => MilvusDatabase::new(args).map(|db| Box::new(db) as Box<dyn Database>),
         "surrealdb" => SurrealDatabase::new(args).map(|db| Box::new(db) as Box<dyn Database>),
+        "pgvector" => PgVectorDatabase::new(args).map(|db| Box::new(db) as Box<dyn Database>),
         _ => Err(Box::<dyn std::error::Error + Send + Sync>::from(
             format!("Unsupported database type: {}", args.vector_export_type)
         )),
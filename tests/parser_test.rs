@@ -0,0 +1,910 @@
+use std::io::{ BufRead, Cursor };
+use clap::Parser;
+use db2vec::cli::Args;
+use serde_json::json;
+use db2vec::embedding::embeding::record_id;
+use db2vec::parser::{
+    detect_format,
+    detect_format_checked,
+    find_parser,
+    formats,
+    is_sqlite_database,
+    join_records,
+    open_dump_files,
+    open_export,
+    open_reject_file,
+    parse_joins,
+    parse_with_regex,
+    register_parser,
+    rejected_counts,
+    score_formats,
+    stream_csv_export,
+    stream_database_export,
+    stream_json_export,
+    table_name_from_path,
+    validate_format,
+    ExportParser,
+    ParseContext,
+    RecordIter,
+    StatementReader,
+};
+use db2vec::parser::schema::{
+    column_kind,
+    extract_foreign_keys,
+    extract_primary_keys,
+    normalize_identifier,
+    ColumnKind,
+};
+use db2vec::util::utils::open_and_detect_format;
+
+const SAMPLE_DIR: &str = "samples";
+
+fn default_args() -> Args {
+    Args::parse_from(["db2vec"])
+}
+
+#[test]
+fn test_statement_reader_splits_copy_blocks_and_quoted_semicolons() {
+    let dump =
+        "-- comment; not a statement\n\
+         INSERT INTO notes VALUES (1, 'a; b');\n\
+         COPY public.items (id, name) FROM stdin;\n\
+         1\tfirst\n\
+         2\tsecond\n\
+         \\.\n";
+    let statements: Vec<String> = StatementReader::new(Cursor::new(dump), "postgres")
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(statements.len(), 2);
+    assert!(statements[0].contains("'a; b'"));
+    assert!(statements[1].starts_with("COPY public.items (id, name) FROM stdin;"));
+    assert!(statements[1].contains("2\tsecond"));
+}
+
+#[test]
+fn test_streaming_sample_record_counts() {
+    let args = default_args();
+    let samples = [
+        ("mssql_sample.sql", 4),
+        ("mysql_sample.sql", 9),
+        ("oracle_sample.sql", 9),
+        ("postgres_sample.sql", 26),
+        ("sqlite_sample.sql", 12),
+        ("surreal_sample.surql", 3),
+    ];
+
+    for (filename, expected) in samples {
+        let path = format!("{}/{}", SAMPLE_DIR, filename);
+        let (reader, format) = open_and_detect_format(&path, None).unwrap();
+        let records: Vec<_> = stream_database_export(reader, &format, &args)
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(records.len(), expected, "Unexpected record count for {}", filename);
+        assert!(
+            records.iter().all(|r| r.get("table").and_then(|t| t.as_str()).is_some()),
+            "Record without table name in {}",
+            filename
+        );
+    }
+}
+
+#[test]
+fn test_primary_keys_from_ddl() {
+    let mysql = "CREATE TABLE `orders` (\n  `order_id` int NOT NULL,\n  `line` int NOT NULL,\n  PRIMARY KEY (`order_id`,`line`)\n) ENGINE=InnoDB;";
+    assert_eq!(
+        extract_primary_keys(mysql, normalize_identifier),
+        vec![("orders".to_string(), vec!["order_id".to_string(), "line".to_string()])]
+    );
+
+    let sqlite = "CREATE TABLE users (\n    uid INTEGER PRIMARY KEY AUTOINCREMENT,\n    name TEXT\n);";
+    assert_eq!(extract_primary_keys(sqlite, normalize_identifier), vec![("users".to_string(), vec!["uid".to_string()])]);
+
+    let postgres = "ALTER TABLE ONLY public.products\n    ADD CONSTRAINT products_pkey PRIMARY KEY (sku);";
+    assert_eq!(extract_primary_keys(postgres, normalize_identifier), vec![("products".to_string(), vec!["sku".to_string()])]);
+
+    let mssql = "CREATE TABLE [dbo].[Items](\n\t[ItemId] [int] NOT NULL,\nPRIMARY KEY CLUSTERED \n(\n\t[ItemId] ASC\n)WITH (PAD_INDEX = OFF) ON [PRIMARY]\n) ON [PRIMARY]";
+    assert_eq!(extract_primary_keys(mssql, normalize_identifier), vec![("Items".to_string(), vec!["ItemId".to_string()])]);
+}
+
+#[test]
+fn test_source_id_and_deterministic_record_id() {
+    let args = default_args();
+    let dump =
+        "CREATE TABLE `orders` (\n  `order_id` int NOT NULL,\n  `note` text,\n  PRIMARY KEY (`order_id`)\n);\n\
+         INSERT INTO `orders` (`order_id`, `note`) VALUES (42,'first'),(43,'second');\n";
+    let first: Vec<_> = stream_database_export(Cursor::new(dump), "mysql", &args)
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(first.len(), 2);
+    assert_eq!(first[0]["source_id"], json!("42"));
+    assert_eq!(first[0]["order_id"], json!(42));
+
+    let second: Vec<_> = stream_database_export(Cursor::new(dump), "mysql", &args)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(record_id("orders", &first[0]), record_id("orders", &second[0]));
+    assert_ne!(record_id("orders", &first[0]), record_id("orders", &first[1]));
+
+    // Rows without a primary key are identified by their contents, whatever
+    // the file is called or where they appear; repeats are numbered.
+    let dir = std::env::temp_dir().join(format!("db2vec-keyless-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let read = |name: &str, values: &str| -> Vec<serde_json::Value> {
+        let path = dir.join(name);
+        std::fs::write(&path, format!("INSERT INTO `log` (`msg`) VALUES {};\n", values)).unwrap();
+        let args = Args::parse_from(["db2vec", "--format", "mysql"]);
+        open_dump_files(path.to_str().unwrap(), &args, &Default::default()).unwrap().0.collect::<Result<_, _>>().unwrap()
+    };
+    let first = read("log.sql", "('ping'),('pong'),('ping')");
+    let second = read("log-renamed.sql", "('hello'),('ping'),('pong'),('ping')");
+    assert!(first[0].get("source_occurrence").is_none());
+    assert_eq!(first[2]["source_occurrence"], json!(2));
+    assert_ne!(record_id("log", &first[0]), record_id("log", &first[2]));
+    assert_eq!(record_id("log", &first[0]), record_id("log", &second[1]));
+    assert_eq!(record_id("log", &first[1]), record_id("log", &second[2]));
+    assert_eq!(record_id("log", &first[2]), record_id("log", &second[3]));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_native_sqlite_database_file() {
+    let path = std::env::temp_dir().join("db2vec-parser-test.db");
+    let _ = std::fs::remove_file(&path);
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE posts (post_id INTEGER PRIMARY KEY, title TEXT, body TEXT, tags TEXT, \
+         published BOOLEAN, score REAL, thumb BLOB, extra);\n\
+         INSERT INTO posts VALUES (7, 'Hello', '<p>Hi <b>there</b></p>', '[\"a\",\"b\"]', 1, 4.5, x'89504e47ff', '{\"k\": 1}');\n\
+         CREATE TABLE order_items (order_id INTEGER, line INTEGER, qty INTEGER, PRIMARY KEY (order_id, line));\n\
+         INSERT INTO order_items VALUES (1, 2, 3);"
+    ).unwrap();
+    drop(conn);
+
+    assert!(is_sqlite_database(&path).unwrap());
+    assert!(!is_sqlite_database(format!("{}/sqlite_sample.sql", SAMPLE_DIR)).unwrap());
+
+    let schema = Default::default();
+    let (records, format) = open_export(path.to_str().unwrap(), &default_args(), &schema).unwrap();
+    let records: Vec<_> = records.collect::<Result<_, _>>().unwrap();
+    assert_eq!(format, "sqlite-db");
+    assert_eq!(schema.table("posts").unwrap()[4], ("published".to_string(), ColumnKind::Boolean));
+    assert_eq!(records.len(), 2);
+
+    let post = &records[0];
+    assert_eq!(post["table"], json!("posts"));
+    assert_eq!(post["source_id"], json!("7"));
+    assert_eq!(post["body"], json!("Hi there"));
+    assert_eq!(post["tags"], json!("[\"a\",\"b\"]"));
+    assert_eq!(post["published"], json!(true));
+    assert_eq!(post["score"], json!(4.5));
+    assert_eq!(post["thumb"], json!("iVBOR/8="));
+    assert_eq!(post["extra"], json!({ "k": 1 }));
+
+    assert_eq!(records[1]["source_id"], json!("1:2"));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_json_array_with_extended_json() {
+    let input = r#"[
+        {"_id": {"$oid": "64b7f0c2a1"}, "name": "Ann, \"A\" [x]", "joined": {"$date": {"$numberLong": "1700000000123"}},
+         "visits": {"$numberLong": "42"}, "address": {"city": "Oslo"}},
+        {"_id": {"$oid": "64b7f0c2a2"}, "name": "Bob", "joined": {"$date": "2024-01-02T00:00:00Z"}}
+    ]"#;
+    let records: Vec<_> = stream_json_export(Cursor::new(input), "users", &default_args())
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["table"], json!("users"));
+    assert_eq!(records[0]["source_id"], json!("64b7f0c2a1"));
+    assert_eq!(records[0]["name"], json!("Ann, \"A\" [x]"));
+    assert_eq!(records[0]["joined"], json!("2023-11-14T22:13:20.123Z"));
+    assert_eq!(records[0]["visits"], json!(42));
+    assert_eq!(records[0]["address"], json!({ "city": "Oslo" }));
+    assert_eq!(records[1]["joined"], json!("2024-01-02T00:00:00Z"));
+}
+
+#[test]
+fn test_jsonl_table_names() {
+    let input = "{\"_collection\": \"orders\", \"id\": 1}\n\n{\"id\": 2}\n";
+    let records: Vec<_> = stream_json_export(Cursor::new(input), "export", &default_args())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["table"], json!("orders"));
+    assert!(records[0].get("_collection").is_none());
+    assert_eq!(records[1]["table"], json!("export"));
+
+    let wrapped = r#"{"users": [{"id": 1}], "orders": [{"id": 7}, {"id": 8}]}"#;
+    let records: Vec<_> = stream_json_export(Cursor::new(wrapped), "export", &default_args())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let tables: Vec<_> = records
+        .iter()
+        .map(|r| r["table"].as_str().unwrap())
+        .collect();
+    assert_eq!(tables, vec!["orders", "orders", "users"]);
+
+    // Only a whole document is unwrapped; JSON Lines are records as they are.
+    let lines = "{\"items\": [{\"sku\": 1}, {\"sku\": 2}]}\n{\"items\": [{\"sku\": 3}]}\n";
+    let records: Vec<_> = stream_json_export(Cursor::new(lines), "orders", &default_args())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["table"], json!("orders"));
+    assert_eq!(records[0]["items"], json!([{ "sku": 1 }, { "sku": 2 }]));
+
+    let pretty = "{\n  \"id\": 1,\n  \"tags\": [\n    \"a\"\n  ]\n}\n{\n  \"id\": 2\n}\n";
+    let records: Vec<_> = stream_json_export(Cursor::new(pretty), "export", &default_args())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["tags"], json!(["a"]));
+
+    let args = Args::parse_from(["db2vec", "--json-table", "items"]);
+    let records: Vec<_> = stream_json_export(Cursor::new(input), "export", &args)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert!(records.iter().all(|r| r["table"] == json!("items")));
+
+    assert_eq!(detect_format("exports/users.ndjson", ""), "json");
+    assert_eq!(table_name_from_path("exports/users.ndjson"), "users");
+}
+
+#[test]
+fn test_csv_directory_one_table_per_file() {
+    let dir = std::env::temp_dir().join("db2vec-csv-test");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("users.csv"),
+        "\u{feff}id,name,bio,zip,active,tags,score,name\n\
+         1,Ann,\"Line one\nline \"\"two\"\"\",01234,true,\"[\"\"a\"\",\"\"b\"\"]\",4.5,Annie\n\
+         2,Bob,,90210,FALSE,,NULL,\n"
+    ).unwrap();
+    std::fs::write(dir.join("orders.tsv"), "order_id\tnote\n7\tfirst, with comma\n").unwrap();
+    std::fs::write(dir.join("readme.txt"), "not a table").unwrap();
+
+    let (records, summary) = open_dump_files(dir.to_str().unwrap(), &default_args(), &Default::default()).unwrap();
+    let records: Vec<_> = records.collect::<Result<_, _>>().unwrap();
+    assert_eq!(records.len(), 3);
+    assert_eq!(summary.lock().unwrap().files.len(), 2);
+
+    assert_eq!(records[0]["table"], json!("orders"));
+    assert_eq!(records[0]["note"], json!("first, with comma"));
+
+    let ann = &records[1];
+    assert_eq!(ann["table"], json!("users"));
+    assert_eq!(ann["source_id"], json!("1"));
+    assert_eq!(ann["bio"], json!("Line one\nline \"two\""));
+    assert_eq!(ann["zip"], json!("01234"));
+    assert_eq!(ann["active"], json!(true));
+    assert_eq!(ann["tags"], json!(["a", "b"]));
+    assert_eq!(ann["score"], json!(4.5));
+    assert_eq!(ann["name_2"], json!("Annie"));
+
+    let bob = &records[2];
+    assert_eq!(bob["zip"], json!(90210));
+    assert_eq!(bob["active"], json!(false));
+    assert_eq!(bob["bio"], json!(null));
+    assert_eq!(bob["score"], json!(null));
+
+    let args = Args::parse_from(["db2vec", "--csv-delimiter", ";"]);
+    let records: Vec<_> = stream_csv_export(Cursor::new("a;b\n1;x,y\n"), "t", b';', &args)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(records[0]["b"], json!("x,y"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_dump_file_directory_and_glob_merge_tables() {
+    let dir = std::env::temp_dir().join("db2vec-multi-file-test");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let mysql_table =
+        "CREATE TABLE `users` (\n  `id` int NOT NULL,\n  `name` varchar(50),\n  PRIMARY KEY (`id`)\n) ENGINE=InnoDB;\n";
+    std::fs::write(
+        dir.join("users.part1.sql"),
+        format!("{}INSERT INTO `users` VALUES (1,'Ann'),(2,'Bob');\n", mysql_table)
+    ).unwrap();
+    std::fs::write(
+        dir.join("users.part2.sql"),
+        format!("{}INSERT INTO `users` VALUES (3,'Cy');\n", mysql_table)
+    ).unwrap();
+    std::fs::write(dir.join("orders.jsonl"), "{\"_id\":\"o1\",\"total\":5}\n").unwrap();
+    std::fs::write(dir.join("notes.txt"), "not a dump").unwrap();
+
+    let (records, summary) = open_dump_files(dir.to_str().unwrap(), &default_args(), &Default::default()).unwrap();
+    let tables: Vec<String> = records
+        .map(|r| r.unwrap()["table"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(tables, vec!["orders", "users", "users", "users"]);
+
+    let summary = summary.lock().unwrap();
+    let files: Vec<(&str, usize)> = summary.files
+        .iter()
+        .map(|(path, format, n)| {
+            assert!(format == "json" || format == "mysql", "unexpected format {}", format);
+            (path.rsplit('/').next().unwrap(), *n)
+        })
+        .collect();
+    assert_eq!(files, vec![("orders.jsonl", 1), ("users.part1.sql", 2), ("users.part2.sql", 1)]);
+    assert_eq!(summary.tables["users"], 3);
+    assert_eq!(summary.tables["orders"], 1);
+    drop(summary);
+
+    let pattern = dir.join("users.part*.sql");
+    let (records, summary) = open_dump_files(pattern.to_str().unwrap(), &default_args(), &Default::default()).unwrap();
+    assert_eq!(records.count(), 3);
+    assert_eq!(summary.lock().unwrap().files.len(), 2);
+
+    let pattern = dir.join("*.parquet");
+    assert!(open_dump_files(pattern.to_str().unwrap(), &default_args(), &Default::default()).is_err());
+}
+
+#[test]
+fn test_compressed_dumps_are_decompressed_transparently() {
+    use std::io::Write;
+
+    let dir = std::env::temp_dir().join("db2vec-compressed-test");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    // The MSSQL sample is UTF-16LE, so transcoding must happen after decompression.
+    for (filename, expected) in [("mssql_sample.sql", 4), ("surreal_sample.surql", 3)] {
+        let raw = std::fs::read(format!("{}/{}", SAMPLE_DIR, filename)).unwrap();
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(&raw).unwrap();
+        let mut bz = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        bz.write_all(&raw).unwrap();
+        let mut xz = xz2::write::XzEncoder::new(Vec::new(), 6);
+        xz.write_all(&raw).unwrap();
+        let compressed = [
+            ("gz", gz.finish().unwrap()),
+            ("zst", zstd::encode_all(raw.as_slice(), 3).unwrap()),
+            ("bz2", bz.finish().unwrap()),
+            ("xz", xz.finish().unwrap()),
+        ];
+
+        for (ext, bytes) in compressed {
+            let path = dir.join(format!("{}.{}", filename, ext));
+            std::fs::write(&path, bytes).unwrap();
+            let (records, format) = open_export(path.to_str().unwrap(), &default_args(), &Default::default()).unwrap();
+            let records: Vec<_> = records.collect::<Result<_, _>>().unwrap();
+            assert_eq!(records.len(), expected, "Unexpected record count for {}.{}", filename, ext);
+            assert_eq!(format, if filename.ends_with(".surql") { "surreal" } else { "mssql" });
+        }
+    }
+
+    // Compressed files are picked up from a directory like plain ones.
+    let (records, summary) = open_dump_files(dir.to_str().unwrap(), &default_args(), &Default::default()).unwrap();
+    assert_eq!(records.count(), 4 * (4 + 3));
+    assert_eq!(summary.lock().unwrap().files.len(), 8);
+
+    assert_eq!(detect_format("dump.surql.zst", ""), "surreal");
+    assert_eq!(detect_format("users.CSV.GZ", ""), "csv");
+}
+
+#[test]
+fn test_scored_format_detection_and_override() {
+    // A bare "GO" inside data and an Oracle-style "Insert into" in a comment
+    // no longer outweigh MySQL's own signatures.
+    let mysql =
+        "-- Insert into users from the GOOGLE import\n\
+         CREATE TABLE `users` (`id` int AUTO_INCREMENT, `name` varchar(20)) ENGINE=InnoDB;\n\
+         INSERT INTO `users` VALUES (1,'GOOGLE'),(2,'GO');\n";
+    assert_eq!(detect_format_checked("dump.sql", mysql).unwrap(), "mysql");
+    let scores = score_formats(mysql);
+    assert_eq!(scores[0].format, "mysql");
+    assert!(scores[0].matched.contains(&"ENGINE=InnoDB"));
+    assert!(scores.iter().all(|s| s.format != "mssql"));
+
+    let mssql = "INSERT INTO t VALUES (1)\nGO\n";
+    assert_eq!(detect_format_checked("dump.sql", mssql).unwrap(), "mssql");
+
+    // Two formats with equal evidence: refuse and name both.
+    let ambiguous = "LOCK TABLES t WRITE;\nALTER TABLE ONLY t ADD PRIMARY KEY (id);\n";
+    let err = detect_format_checked("dump.sql", ambiguous).unwrap_err();
+    assert!(err.contains("ambiguous") && err.contains("mysql") && err.contains("postgres"), "{}", err);
+    assert!(detect_format_checked("dump.sql", "INSERT INTO t VALUES (1);").is_err());
+
+    let dir = std::env::temp_dir().join("db2vec-format-test");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("plain.sql");
+    std::fs::write(&path, "CREATE TABLE t (id int, name text);\nINSERT INTO t VALUES (1, 'Ann');\n").unwrap();
+    let path = path.to_str().unwrap();
+    assert!(open_export(path, &default_args(), &Default::default()).is_err());
+
+    let args = Args::parse_from(["db2vec", "--format", "mysql"]);
+    let (records, format) = open_export(path, &args, &Default::default()).unwrap();
+    assert_eq!(format, "mysql");
+    assert_eq!(records.count(), 1);
+
+    let args = Args::parse_from(["db2vec", "--format", "db2"]);
+    assert!(open_export(path, &args, &Default::default()).is_err());
+}
+
+#[test]
+fn test_postgres_inserts_schemas_and_copy_escapes() {
+    let dump = r#"--
+-- PostgreSQL database dump
+--
+CREATE TABLE public.users (
+    id integer NOT NULL,
+    name character varying(50),
+    tags text[],
+    profile jsonb,
+    active boolean,
+    CONSTRAINT users_name_check CHECK ((name <> ''::text))
+);
+INSERT INTO public.users VALUES (1, 'O''Brien', '{a,b}', '{"level": 2}', true);
+INSERT INTO public.users VALUES (2, E'tab\there', NULL, NULL, false);
+CREATE TABLE sales."Order Items" (order_id bigint, "Unit Price" numeric(10,2), note text);
+INSERT INTO sales."Order Items" ("order_id", "Unit Price", note) VALUES (10, 9.5, 'first'), (11, -2, 'a, (b)'::text);
+COPY sales.orders (id, note) FROM stdin;
+7	line\r\nbreak \x41\101 back\\slash \b
+8	\N
+\.
+COPY public.events (id, kind) FROM stdin WITH (FORMAT csv, DELIMITER ';', NULL 'NIL');
+1;"a;b"
+2;NIL
+\.
+"#;
+    let records: Vec<_> = stream_database_export(Cursor::new(dump), "postgres", &default_args())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(records.len(), 8);
+
+    assert_eq!(records[0]["table"], json!("users"));
+    assert_eq!(records[0]["source_id"], json!("1"));
+    assert_eq!(records[0]["name"], json!("O'Brien"));
+    assert_eq!(records[0]["tags"], json!(["a", "b"]));
+    assert_eq!(records[0]["profile"], json!({ "level": 2 }));
+    assert_eq!(records[0]["active"], json!(true));
+    assert_eq!(records[1]["name"], json!("tab\there"));
+    assert_eq!(records[1]["tags"], json!(null));
+
+    assert_eq!(records[2]["table"], json!("sales_Order Items"));
+    assert_eq!(records[2]["Unit Price"], json!(9.5));
+    assert_eq!(records[3]["Unit Price"], json!(-2));
+    assert_eq!(records[3]["note"], json!("a, (b)"));
+
+    assert_eq!(records[4]["table"], json!("sales_orders"));
+    assert_eq!(records[4]["note"], json!("line\r\nbreak AA back\\slash \u{8}"));
+    assert_eq!(records[5]["note"], json!(null));
+
+    assert_eq!(records[6]["table"], json!("events"));
+    assert_eq!(records[6]["kind"], json!("a;b"));
+    assert_eq!(records[7]["kind"], json!(null));
+}
+
+#[test]
+fn test_values_coerced_to_declared_column_types() {
+    assert_eq!(column_kind("tinyint(1)"), ColumnKind::Boolean);
+    assert_eq!(column_kind("NUMBER(10,0)"), ColumnKind::Integer);
+    assert_eq!(column_kind("numeric(10,2)"), ColumnKind::Float);
+    assert_eq!(column_kind("timestamp with time zone"), ColumnKind::Timestamp);
+    assert_eq!(column_kind("integer[]"), ColumnKind::Array);
+    assert_eq!(column_kind("bytea"), ColumnKind::Binary);
+    assert_eq!(column_kind("varchar(20)"), ColumnKind::Text);
+
+    let dump = r#"-- PostgreSQL database dump
+CREATE TABLE public.typed_accounts (
+    id integer NOT NULL,
+    active boolean,
+    balance numeric(12,2),
+    created_at timestamp with time zone,
+    code character varying(10),
+    avatar bytea,
+    note text,
+    labels text[],
+    meta jsonb
+);
+COPY public.typed_accounts (id, active, balance, created_at, code, avatar, note, labels, meta) FROM stdin;
+1	t	12.50	2024-01-02 03:04:05+02	007	\\x89504e47	{}	{}	{"a": 1}
+2	f	3	\N	42	\N	[1]	{x,y}	\N
+\.
+INSERT INTO public.events (id, payload) VALUES (1, '{"a": 1}');
+"#;
+    let mut stream = stream_database_export(Cursor::new(dump), "postgres", &default_args());
+    let records: Vec<_> = stream.by_ref().collect::<Result<_, _>>().unwrap();
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0]["source_id"], json!("1"));
+    assert_eq!(records[0]["active"], json!(true));
+    assert_eq!(records[0]["balance"], json!(12.5));
+    assert_eq!(records[0]["created_at"], json!("2024-01-02T03:04:05+02:00"));
+    assert_eq!(records[0]["code"], json!("007"));
+    assert_eq!(records[0]["avatar"], json!("iVBORw=="));
+    assert_eq!(records[1]["active"], json!(false));
+    assert_eq!(records[1]["balance"], json!(3.0));
+    assert_eq!(records[1]["code"], json!("42"));
+
+    // Declared text stays text; JSON is only guessed for undeclared columns.
+    assert_eq!(records[0]["note"], json!("{}"));
+    assert_eq!(records[0]["labels"], json!([]));
+    assert_eq!(records[0]["meta"], json!({ "a": 1 }));
+    assert_eq!(records[1]["note"], json!("[1]"));
+    assert_eq!(records[1]["labels"], json!(["x", "y"]));
+    assert_eq!(records[2]["payload"], json!({ "a": 1 }));
+
+    let schema = stream.schema().table("typed_accounts").unwrap();
+    assert_eq!(schema[0], ("id".to_string(), ColumnKind::Integer));
+    assert_eq!(schema[3], ("created_at".to_string(), ColumnKind::Timestamp));
+    // Each stream collects the schema of its own dump.
+    let other = stream_database_export(Cursor::new(""), "postgres", &default_args());
+    assert!(other.schema().table("typed_accounts").is_none());
+}
+
+#[test]
+fn test_mysql_values_tokenizer() {
+    let dump = r#"CREATE TABLE `items` (
+  `id` int NOT NULL,
+  `title` varchar(100),
+  `note` text,
+  `flags` bit(4),
+  `blob_col` blob,
+  PRIMARY KEY (`id`)
+) ENGINE=InnoDB;
+INSERT INTO `shop`.`items` VALUES (1,'Mug (large)','say \"hi\"), then (leave','b''0101''',_binary 'abc'),(2,'It\'s \\ fine','a\0b\rc\Zd',b'0101',0x89504E47),(3,'{\"k\": \"(v)\"}',NULL,NULL,_binary '\0\0');
+INSERT IGNORE INTO items (id, title) VALUES (4, 'ignored (dup)');
+REPLACE INTO `items` (`id`,`title`,`note`) VALUES (5,'semi;colon','0x41'),(6,X'414243',_utf8mb4'café');
+REPLACE INTO `items` (`id`,`title`) VALUES (7,'short row follows'),(8);
+"#;
+    let records: Vec<_> = stream_database_export(Cursor::new(dump), "mysql", &default_args())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(records.len(), 7);
+
+    assert_eq!(records[0]["table"], json!("items"));
+    assert_eq!(records[0]["source_id"], json!("1"));
+    assert_eq!(records[0]["title"], json!("Mug (large)"));
+    assert_eq!(records[0]["note"], json!("say \"hi\"), then (leave"));
+    assert_eq!(records[0]["flags"], json!("b'0101'"));
+    assert_eq!(records[0]["blob_col"], json!("abc"));
+
+    assert_eq!(records[1]["title"], json!("It's \\ fine"));
+    assert_eq!(records[1]["note"], json!("a\u{0}b\rc\u{1a}d"));
+    assert_eq!(records[1]["flags"], json!(5));
+    assert_eq!(records[1]["blob_col"], json!("iVBORw=="));
+    assert_eq!(records[2]["blob_col"], json!("AAA="));
+
+    assert_eq!(records[2]["title"], json!("{\"k\": \"(v)\"}"));
+    assert_eq!(records[3]["title"], json!("ignored (dup)"));
+    assert_eq!(records[4]["title"], json!("semi;colon"));
+    assert_eq!(records[4]["note"], json!("0x41"));
+    assert_eq!(records[5]["title"], json!("ABC"));
+    assert_eq!(records[5]["note"], json!("café"));
+    assert_eq!(records[6]["source_id"], json!("7"));
+}
+
+#[test]
+fn test_mssql_schemas_strings_and_conversions() {
+    let dump = r#"SET ANSI_NULLS ON
+GO
+CREATE TABLE [sales].[Orders](
+	[OrderID] [int] NOT NULL,
+	[Customer] [nvarchar](100) NULL,
+	[OrderDate] [datetime] NULL,
+	[Amount] [decimal](10, 2) NULL,
+	[Photo] [varbinary](max) NULL,
+	[Paid] [bit] NULL
+) ON [PRIMARY]
+GO
+INSERT [sales].[Orders] ([OrderID], [Customer], [OrderDate], [Amount], [Photo], [Paid]) VALUES (7, N'O''Brien, (VIP)', CAST(N'2021-01-01T00:00:00.000' AS DateTime), CAST(12.50 AS Decimal(10, 2)), 0x48690A, 1)
+GO
+INSERT INTO sales.Orders VALUES (8, N'Zoë', CONVERT(datetime2, '2021-02-03 04:05:06', 120), 3, CAST(NULL AS varbinary(max)), 0)
+GO
+INSERT INTO [dbo].[Notes] ([Id], [Body]) VALUES (1, N'plain')
+GO
+INSERT INTO Orders VALUES (2, N'x')
+GO
+"#;
+    let mut stream = stream_database_export(Cursor::new(dump), "mssql", &default_args());
+    let records: Vec<_> = stream.by_ref().collect::<Result<_, _>>().unwrap();
+    assert_eq!(records.len(), 4);
+
+    assert_eq!(records[0]["table"], json!("sales_Orders"));
+    assert_eq!(records[0]["OrderID"], json!(7));
+    assert_eq!(records[0]["Customer"], json!("O'Brien, (VIP)"));
+    assert_eq!(records[0]["OrderDate"], json!("2021-01-01T00:00:00.000"));
+    assert_eq!(records[0]["Amount"], json!(12.5));
+    assert_eq!(records[0]["Photo"], json!("SGkK"));
+
+    assert_eq!(records[1]["table"], json!("sales_Orders"));
+    assert_eq!(records[1]["Customer"], json!("Zoë"));
+    assert_eq!(records[1]["OrderDate"], json!("2021-02-03T04:05:06"));
+    assert_eq!(records[1]["Photo"], json!(null));
+
+    assert_eq!(records[2]["table"], json!("Notes"));
+    assert_eq!(records[2]["Body"], json!("plain"));
+    // Bare 0 and 1 are numbers; only declared `bit` columns become booleans.
+    assert_eq!(records[0]["Paid"], json!(true));
+    assert_eq!(records[1]["Paid"], json!(false));
+    assert_eq!(records[2]["source_id"], json!("1"));
+
+    // `Orders` is not `sales.Orders`, so its row is not checked against those columns.
+    assert_eq!(records[3]["table"], json!("Orders"));
+    assert_eq!(records[3]["source_id"], json!("2"));
+    assert_eq!(stream.schema().table("sales_Orders").unwrap().len(), 6);
+}
+
+#[test]
+fn test_oracle_functions_and_alternative_quoting() {
+    let dump = r#"REM INSERTING into SHOP.EVENTS
+SET DEFINE OFF;
+Insert into SHOP.EVENTS (ID,TITLE,STARTS_AT,CREATED,PAYLOAD,NOTES) values (1,'Launch; day one',to_date('01-JAN-20','DD-MON-RR'),to_timestamp('15-MAR-21 02.05.09.250000000 PM','DD-MON-RR HH.MI.SSXFF AM'),HEXTORAW('48690A'),EMPTY_CLOB());
+Insert into SHOP.EVENTS (ID,TITLE,STARTS_AT,CREATED,PAYLOAD,NOTES) values (2,q'[It's (quoted); really]',TO_DATE('2020-02-29 23:59:00','YYYY-MM-DD HH24:MI:SS'),NULL,NULL,'line one'||chr(10)||'line two
+continues');
+Insert into "SHOP"."EVENTS" ("ID","TITLE","STARTS_AT","CREATED","PAYLOAD","NOTES") values (3,N'Zoë',TO_DATE('1999-12-31'),NULL,NULL,TO_CLOB('{"a": 1}'));
+"#;
+    let records: Vec<_> = stream_database_export(Cursor::new(dump), "oracle", &default_args())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(records.len(), 3);
+
+    assert_eq!(records[0]["table"], json!("EVENTS"));
+    assert_eq!(records[0]["TITLE"], json!("Launch; day one"));
+    assert_eq!(records[0]["STARTS_AT"], json!("2020-01-01"));
+    assert_eq!(records[0]["CREATED"], json!("2021-03-15T14:05:09.25"));
+    assert_eq!(records[0]["PAYLOAD"], json!("SGkK"));
+    assert_eq!(records[0]["NOTES"], json!(null));
+
+    assert_eq!(records[1]["TITLE"], json!("It's (quoted); really"));
+    assert_eq!(records[1]["STARTS_AT"], json!("2020-02-29T23:59:00"));
+    assert_eq!(records[1]["NOTES"], json!("line one\nline two\ncontinues"));
+
+    assert_eq!(records[2]["TITLE"], json!("Zoë"));
+    assert_eq!(records[2]["STARTS_AT"], json!("1999-12-31"));
+    assert_eq!(records[2]["NOTES"], json!({ "a": 1 }));
+
+    // Several rows in one chunk, with multi-byte text ahead of each INSERT.
+    let chunk = "Insert into T (A,B) values ('Zoë','ü') Insert into T (A,B) values ('日本',2) Insert into T (A,B) values ('x',3)";
+    let rows = parse_with_regex(chunk, "oracle", &mut ParseContext::new(&default_args())).unwrap();
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[1], json!({ "table": "T", "A": "日本", "B": 2 }));
+    assert_eq!(rows[2]["B"], json!(3));
+}
+
+#[test]
+fn test_surreal_value_grammar_and_relate_edges() {
+    let dump = r#"OPTION IMPORT;
+
+-- ------------------------------
+-- TABLE DATA: person
+-- ------------------------------
+
+INSERT [ { id: person:⟨john smith⟩, bio: 'likes },{ braces', born: d'1990-05-01T00:00:00Z', session: 1h30m, balance: 12.50dec, score: 3.5f, pets: [{ name: "Rex", tags: ['a', 'b'] }], manager: person:jane }, { id: person:jane, bio: "it's \"quoted\"\n", born: <datetime> '1985-01-01T00:00:00Z', active: true, nothing: NONE } ];
+CREATE product:desk CONTENT { name: 'Desk', price: 250 };
+UPSERT product:lamp SET name = 'Lamp', price = 19.99;
+RELATE person:jane->bought->product:desk CONTENT { qty: 2, at: d'2024-02-03T04:05:06Z' };
+"#;
+    let records: Vec<_> = stream_database_export(Cursor::new(dump), "surreal", &default_args())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(records.len(), 5);
+
+    assert_eq!(records[0]["table"], json!("person"));
+    assert_eq!(records[0]["source_id"], json!("person:john smith"));
+    assert_eq!(records[0]["bio"], json!("likes },{ braces"));
+    assert_eq!(records[0]["born"], json!("1990-05-01T00:00:00Z"));
+    assert_eq!(records[0]["session"], json!("1h30m"));
+    assert_eq!(records[0]["balance"], json!(12.5));
+    assert_eq!(records[0]["score"], json!(3.5));
+    assert_eq!(records[0]["pets"], json!([{ "name": "Rex", "tags": ["a", "b"] }]));
+    assert_eq!(records[0]["manager"], json!("person:jane"));
+
+    assert_eq!(records[1]["bio"], json!("it's \"quoted\"\n"));
+    assert_eq!(records[1]["born"], json!("1985-01-01T00:00:00Z"));
+    assert_eq!(records[1]["active"], json!(true));
+    assert_eq!(records[1]["nothing"], json!(null));
+
+    assert_eq!(records[2]["table"], json!("product"));
+    assert_eq!(records[2]["source_id"], json!("product:desk"));
+    assert_eq!(records[2]["price"], json!(250));
+    assert_eq!(records[3]["name"], json!("Lamp"));
+    assert_eq!(records[3]["price"], json!(19.99));
+
+    assert_eq!(records[4]["table"], json!("bought"));
+    assert_eq!(records[4]["in"], json!("person:jane"));
+    assert_eq!(records[4]["out"], json!("product:desk"));
+    assert_eq!(records[4]["qty"], json!(2));
+}
+
+#[test]
+fn test_joins_denormalize_related_rows() {
+    let dump = r#"
+CREATE TABLE customers (id INT PRIMARY KEY, name VARCHAR(50));
+CREATE TABLE orders (
+  id INT PRIMARY KEY,
+  customer_id INT,
+  total DECIMAL(8,2),
+  CONSTRAINT fk_customer FOREIGN KEY (customer_id) REFERENCES customers (id)
+);
+CREATE TABLE order_items (order_id INT REFERENCES orders(id), product VARCHAR(50), qty INT);
+CREATE TABLE notes (id INT, body TEXT);
+INSERT INTO orders VALUES (10, 1, 99.50), (11, 2, 5.00), (12, 3, 1.00);
+INSERT INTO notes VALUES (1, 'unrelated');
+INSERT INTO order_items VALUES (10, 'Desk', 1), (10, 'Lamp', 2), (11, 'Pen', 5);
+INSERT INTO customers VALUES (1, 'Ada'), (2, 'Linus');
+"#;
+    let keys: Vec<String> = extract_foreign_keys(dump, normalize_identifier)
+        .iter()
+        .map(|k| k.to_string())
+        .collect();
+    assert_eq!(keys, ["orders.customer_id -> customers.id", "order_items.order_id -> orders.id"]);
+
+    let joins = parse_joins(
+        &[
+            "orders.customer_id -> customers.id".to_string(),
+            "order_items.order_id -> orders.id as items[]".to_string(),
+        ]
+    ).unwrap();
+    assert_eq!(joins[0].field, "customer");
+    assert!(parse_joins(&["orders -> customers".to_string()]).is_err());
+
+    let records = Box::new(stream_database_export(Cursor::new(dump), "mysql", &default_args()));
+    let records: Vec<_> = join_records(records, joins).collect::<Result<_, _>>().unwrap();
+
+    // Unjoined tables stream through first; nested order_items are not emitted.
+    let tables: Vec<_> = records
+        .iter()
+        .map(|r| r["table"].as_str().unwrap())
+        .collect();
+    assert_eq!(tables, ["notes", "orders", "orders", "orders", "customers", "customers"]);
+
+    assert_eq!(records[1]["source_id"], json!("10"));
+    assert_eq!(records[1]["customer"], json!({ "source_id": "1", "name": "Ada" }));
+    assert_eq!(
+        records[1]["items"],
+        json!([
+            { "order_id": 10, "product": "Desk", "qty": 1 },
+            { "order_id": 10, "product": "Lamp", "qty": 2 },
+        ])
+    );
+    assert_eq!(records[2]["customer"]["name"], json!("Linus"));
+    assert_eq!(records[2]["items"][0]["product"], json!("Pen"));
+    // No customer 3 and no items: neither field is added.
+    assert!(records[3].get("customer").is_none());
+    assert!(records[3].get("items").is_none());
+}
+
+#[test]
+fn test_rejected_rows_are_reported() {
+    let dir = std::env::temp_dir().join("db2vec-reject-test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let reject_path = dir.join("rejects.jsonl");
+    open_reject_file(reject_path.to_str().unwrap()).unwrap();
+
+    let dump = r#"CREATE TABLE `reject_people` (`id` int, `name` text) ENGINE=InnoDB;
+INSERT INTO `reject_people` VALUES (1, 'Ada'),
+(2, 'Linus', 'extra');
+
+INSERT INTO `reject_people` VALUES (3, 'Grace');
+"#;
+    let dump_path = dir.join("people.sql");
+    std::fs::write(&dump_path, dump).unwrap();
+    let (records, _) = open_export(dump_path.to_str().unwrap(), &default_args(), &Default::default()).unwrap();
+    let records: Vec<_> = records.collect::<Result<_, _>>().unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(rejected_counts().get("reject_people"), Some(&1));
+
+    let copy = "COPY public.reject_copy (id, name) FROM stdin;\n1\tAda\n2\n\\.\n";
+    let records: Vec<_> = stream_database_export(Cursor::new(copy), "postgres", &default_args())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(records.len(), 1);
+
+    let csv_path = dir.join("reject_items.csv");
+    std::fs::write(&csv_path, "id,name\n1,mug\n2,lamp,extra\n").unwrap();
+    let (records, _) = open_export(csv_path.to_str().unwrap(), &default_args(), &Default::default()).unwrap();
+    assert_eq!(records.collect::<Result<Vec<_>, _>>().unwrap().len(), 1);
+
+    let json_path = dir.join("reject_docs.json");
+    std::fs::write(&json_path, "[\n  {\"id\": 1},\n  {oops},\n  {\"id\": 3}\n]").unwrap();
+    let (records, _) = open_export(json_path.to_str().unwrap(), &default_args(), &Default::default()).unwrap();
+    assert_eq!(records.collect::<Result<Vec<_>, _>>().unwrap().len(), 2);
+
+    let jsonl_path = dir.join("reject_lines.jsonl");
+    std::fs::write(&jsonl_path, "{\"id\": 1}\n{\"id\": 2,\n{\"id\": 3}\n").unwrap();
+    let (records, _) = open_export(jsonl_path.to_str().unwrap(), &default_args(), &Default::default()).unwrap();
+    assert_eq!(records.collect::<Result<Vec<_>, _>>().unwrap().len(), 2);
+
+    let rejects: Vec<serde_json::Value> = std::fs
+        ::read_to_string(&reject_path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .filter(|r: &serde_json::Value| r["table"].as_str().is_some_and(|t| t.starts_with("reject_")))
+        .collect();
+    assert_eq!(rejects.len(), 5);
+    assert_eq!(rejects[0]["file"], json!(dump_path.to_str().unwrap()));
+    assert_eq!(rejects[0]["line"], json!(3));
+    assert_eq!(rejects[0]["reason"], json!("3 values for 2 columns"));
+    assert_eq!(rejects[0]["raw"], json!("(2, 'Linus', 'extra')"));
+    assert_eq!(rejects[1]["table"], json!("reject_copy"));
+    assert_eq!(rejects[1]["line"], json!(3));
+    assert_eq!(rejects[1]["raw"], json!("2"));
+    assert_eq!(rejects[2]["table"], json!("reject_items"));
+    assert_eq!(rejects[2]["line"], json!(3));
+    assert_eq!(rejects[2]["raw"], json!("2,lamp,extra"));
+    assert_eq!(rejects[3]["table"], json!("reject_docs"));
+    assert_eq!(rejects[3]["line"], json!(3));
+    assert_eq!(rejects[3]["raw"], json!("{oops}"));
+    assert_eq!(rejects[4]["table"], json!("reject_lines"));
+    assert_eq!(rejects[4]["line"], json!(2));
+    assert_eq!(rejects[4]["raw"], json!("{\"id\": 2,"));
+
+    // Too many rejections end the stream with an error before it is exhausted.
+    let ratio_path = dir.join("ratio_items.csv");
+    std::fs::write(&ratio_path, "id,name\n1,mug\n2,lamp,x\n3,pen,y\n").unwrap();
+    let args = Args::parse_from(["db2vec", "--max-reject-ratio", "0.5"]);
+    let (records, _) = open_dump_files(ratio_path.to_str().unwrap(), &args, &Default::default()).unwrap();
+    let results: Vec<_> = records.collect();
+    assert_eq!(results.len(), 2);
+    assert!(results[1].as_ref().is_err_and(|e| e.to_string().contains("--max-reject-ratio 0.5")));
+}
+
+/// `table|field=value|...` lines, one record per line.
+struct PipeParser;
+
+impl ExportParser for PipeParser {
+    fn name(&self) -> &'static str {
+        "pipe"
+    }
+
+    fn detect(&self, path: &str, head: &[u8]) -> u32 {
+        if path.ends_with(".pipe") || head.starts_with(b"#PIPE") { 3 } else { 0 }
+    }
+
+    fn parse(&self, _path: &str, reader: Box<dyn BufRead + Send>, _args: &Args) -> RecordIter {
+        Box::new(
+            reader
+                .lines()
+                .filter(|line| line.as_ref().map_or(true, |l| !l.starts_with('#')))
+                .map(|line| {
+                    let line = line?;
+                    let mut parts = line.split('|');
+                    let mut record = json!({ "table": parts.next().unwrap_or_default() });
+                    for (key, value) in parts.filter_map(|p| p.split_once('=')) {
+                        record[key] = json!(value);
+                    }
+                    Ok(record)
+                })
+        )
+    }
+}
+
+#[test]
+fn test_registered_parsers_detect_and_stream() {
+    for builtin in ["mysql", "postgres", "mssql", "sqlite", "oracle", "surreal", "json", "csv"] {
+        assert_eq!(find_parser(builtin).unwrap().name(), builtin);
+    }
+
+    register_parser(PipeParser);
+    assert!(formats().contains(&"pipe"));
+    assert_eq!(validate_format("PIPE").unwrap(), "pipe");
+    assert_eq!(detect_format_checked("export.dat", "#PIPE v1\nusers|name=Ada\n").unwrap(), "pipe");
+    assert_eq!(detect_format_checked("export.json", "#PIPE v1\nusers|name=Ada\n").unwrap(), "pipe");
+    assert_eq!(detect_format_checked("export.csv", "#PIPE v1\nusers|name=Ada\n").unwrap(), "pipe");
+    assert_eq!(detect_format_checked("export.json", "[{\"name\": \"Ada\"}]").unwrap(), "json");
+    assert_eq!(score_formats("#PIPE v1\n")[0].format, "pipe");
+
+    let dir = std::env::temp_dir().join("db2vec-registry-test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("people.pipe");
+    std::fs::write(&path, "users|name=Ada|role=admin\nusers|name=Linus\n").unwrap();
+    let (records, format) = open_export(path.to_str().unwrap(), &default_args(), &Default::default()).unwrap();
+    assert_eq!(format, "pipe");
+    let records: Vec<_> = records.collect::<Result<_, _>>().unwrap();
+    assert_eq!(
+        records,
+        [json!({ "table": "users", "name": "Ada", "role": "admin" }), json!({ "table": "users", "name": "Linus" })]
+    );
+}
@@ -0,0 +1,136 @@
+use clap::Parser;
+use db2vec::cli::{ Args, Command };
+use db2vec::db::redis::{ redis_field_type, redis_filter_query };
+use db2vec::db::{ Database, JsonlDatabase, QdrantDatabase };
+use db2vec::parser::schema::ColumnKind;
+use db2vec::search::parse_filter;
+use serde_json::{ json, Value };
+use std::io::{ Read, Write };
+use std::net::TcpListener;
+
+/// Serves one HTTP request with `body` and returns the raw request.
+fn serve_once(listener: TcpListener, body: &'static str) -> std::thread::JoinHandle<String> {
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut request = String::new();
+        loop {
+            let n = stream.read(&mut buf).unwrap();
+            request.push_str(&String::from_utf8_lossy(&buf[..n]));
+            let Some((head, body)) = request.split_once("\r\n\r\n") else {
+                continue;
+            };
+            let length = head
+                .lines()
+                .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().to_string()))
+                .and_then(|v| v.parse::<usize>().ok())
+                .unwrap_or(0);
+            if body.len() >= length {
+                break;
+            }
+        }
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        ).unwrap();
+        request
+    })
+}
+
+#[test]
+fn test_search_subcommand_args() {
+    let args = Args::parse_from([
+        "db2vec",
+        "-t",
+        "qdrant",
+        "search",
+        "--query",
+        "red shoes",
+        "--table",
+        "products",
+        "--filter",
+        r#"{"brand":"acme"}"#,
+    ]);
+    assert_eq!(args.vector_export_type, "qdrant");
+    let Some(Command::Search(search)) = args.command else {
+        panic!("expected the search subcommand");
+    };
+    assert_eq!(search.query, "red shoes");
+    assert_eq!(search.table, "products");
+    assert_eq!(search.top_k, 10);
+
+    assert!(Args::parse_from(["db2vec"]).command.is_none());
+}
+
+#[test]
+fn test_parse_filter() {
+    assert_eq!(parse_filter(None).unwrap(), None);
+    assert_eq!(parse_filter(Some("  ")).unwrap(), None);
+    let filter = parse_filter(Some(r#"{"brand":"acme","stock":3}"#)).unwrap().unwrap();
+    assert_eq!(filter["brand"], json!("acme"));
+    assert_eq!(filter["stock"], json!(3));
+    assert!(parse_filter(Some("[1,2]")).is_err());
+    assert!(parse_filter(Some("{brand")).is_err());
+}
+
+#[test]
+fn test_redis_filter_query() {
+    assert_eq!(redis_filter_query(None), "*");
+    let filter = parse_filter(Some(r#"{"brand":"say \"hi\"","stock":3,"active":true}"#)).unwrap();
+    assert_eq!(
+        redis_filter_query(filter.as_ref()),
+        r#"(@active:{true} @brand:"say \"hi\"" @stock:[3 3])"#
+    );
+    // Booleans stay JSON booleans, which RediSearch indexes as tags.
+    let active = redis_field_type("active", Some(ColumnKind::Boolean), &json!(1));
+    assert_eq!(active, Some(("$.active".to_string(), "TAG")));
+    assert_eq!(redis_field_type("active", None, &json!(false)).unwrap().1, "TAG");
+    assert_eq!(redis_field_type("stock", None, &json!(3)).unwrap().1, "NUMERIC");
+}
+
+#[test]
+fn test_qdrant_search_sends_filter_and_parses_hits() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = serve_once(
+        listener,
+        r#"{"result":[{"id":"a1","version":1,"score":0.92,"payload":{"name":"Ann"}},{"id":7,"version":1,"score":0.5,"payload":{"name":"Bob"}}],"status":"ok","time":0.001}"#
+    );
+
+    let args = Args::parse_from([
+        "db2vec",
+        "-t",
+        "qdrant",
+        "--vector-host",
+        &format!("http://{}", addr),
+        "--dimension",
+        "2",
+    ]);
+    let db = QdrantDatabase::new(&args).unwrap();
+    let filter = parse_filter(Some(r#"{"name":"Ann"}"#)).unwrap();
+    let hits = db.search("Users", &[0.5, 0.5], 2, filter.as_ref()).unwrap();
+
+    let request = server.join().unwrap();
+    assert!(request.starts_with("POST /collections/users/points/search"));
+    let body: Value = serde_json::from_str(request.split_once("\r\n\r\n").unwrap().1).unwrap();
+    assert_eq!(body["limit"], json!(2));
+    assert_eq!(body["filter"], json!({ "must": [{ "key": "name", "match": { "value": "Ann" } }] }));
+
+    let ids: Vec<&str> = hits
+        .iter()
+        .map(|h| h.id.as_str())
+        .collect();
+    assert_eq!(ids, vec!["a1", "7"]);
+    assert!((hits[0].score - 0.92).abs() < 1e-6);
+    assert_eq!(hits[1].metadata, json!({ "name": "Bob" }));
+}
+
+#[test]
+fn test_file_sinks_do_not_support_search() {
+    let dir = std::env::temp_dir().join("db2vec-search-test");
+    let args = Args::parse_from(["db2vec", "-t", "jsonl", "--output-dir", dir.to_str().unwrap()]);
+    let db = JsonlDatabase::new(&args).unwrap();
+    assert!(db.search("users", &[0.0; 768], 5, None).is_err());
+}
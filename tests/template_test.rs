@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+use db2vec::embedding::template::{ TemplateEntry, TextTemplates };
+use serde_json::json;
+
+fn templates(entries: Vec<(&str, TemplateEntry)>) -> TextTemplates {
+    let entries: HashMap<_, _> = entries
+        .into_iter()
+        .map(|(t, e)| (t.to_string(), e))
+        .collect();
+    TextTemplates::from_entries(entries).unwrap()
+}
+
+#[test]
+fn test_template_renders_fields_and_filters() {
+    let templates = templates(
+        vec![
+            (
+                "posts",
+                TemplateEntry {
+                    template: Some(
+                        "{title|upper}\n\n{body}\nTags: {tags|join(', ')} {{{missing|default('n/a')}}}".into()
+                    ),
+                    ..Default::default()
+                },
+            ),
+            (
+                "users",
+                TemplateEntry {
+                    columns: vec!["name".into(), "bio".into()],
+                    ..Default::default()
+                },
+            )
+        ]
+    );
+
+    let post = json!({
+        "table": "posts",
+        "title": "Hello",
+        "body": "First post",
+        "tags": "[\"rust\",\"db\"]",
+        "created_at": "2024-01-01"
+    });
+    assert_eq!(templates.render(&post).unwrap(), "HELLO\n\nFirst post\nTags: rust, db {n/a}");
+
+    let user = json!({ "table": "users", "name": "Ann", "bio": "Dev", "password": "x" });
+    assert_eq!(templates.render(&user).unwrap(), "name: \"Ann\", bio: \"Dev\"");
+
+    assert!(templates.render(&json!({ "table": "other", "a": 1 })).is_none());
+}
+
+#[test]
+fn test_invalid_template_is_rejected() {
+    for bad in ["{title", "{title|shout}", "a } b", "{}"] {
+        let entries = HashMap::from([
+            (
+                "posts".to_string(),
+                TemplateEntry { template: Some(bad.to_string()), ..Default::default() },
+            ),
+        ]);
+        assert!(TextTemplates::from_entries(entries).is_err(), "accepted {:?}", bad);
+    }
+}